@@ -11,7 +11,7 @@ use super::prompt::{
 };
 use super::types::ChangesetAnalysis;
 use crate::ai::{find_acp_agent, find_acp_agent_by_id, run_acp_prompt, AcpAgent};
-use crate::git::{self, DiffSpec, FileContent};
+use crate::git::{self, DiffOptions, DiffSpec, FileContent};
 
 /// Find an available AI agent, optionally by provider ID.
 ///
@@ -68,7 +68,7 @@ fn load_after_content_if_small(
     spec: &DiffSpec,
     file_path: &Path,
 ) -> Result<(Option<String>, usize), String> {
-    let diff = git::get_file_diff(repo_path, spec, file_path)
+    let diff = git::get_file_diff(repo_path, spec, file_path, &DiffOptions::default())
         .map_err(|e| format!("Failed to get file diff: {e}"))?;
 
     let (content, line_count) = match &diff.after {
@@ -82,7 +82,9 @@ fn load_after_content_if_small(
                     (None, count)
                 }
             }
-            FileContent::Binary => (None, 0),
+            FileContent::Binary(_)
+            | FileContent::Submodule { .. }
+            | FileContent::LfsPointer { .. } => (None, 0),
         },
         None => (None, 0), // Deleted file
     };
@@ -114,7 +116,7 @@ pub async fn analyze_diff(
     })?;
 
     // List files in the diff
-    let files = git::list_diff_files(repo_path, spec)
+    let files = git::list_diff_files(repo_path, spec, &DiffOptions::default())
         .map_err(|e| format!("Failed to list diff files: {e}"))?;
 
     if files.is_empty() {
@@ -129,7 +131,7 @@ pub async fn analyze_diff(
         let path_str = file_path.to_string_lossy().to_string();
 
         // Get unified diff
-        let diff = git::get_unified_diff(repo_path, spec, file_path)
+        let diff = git::get_unified_diff(repo_path, spec, file_path, &DiffOptions::default())
             .map_err(|e| format!("Failed to get diff for {path_str}: {e}"))?;
 
         // Load after content if small enough
@@ -143,14 +145,15 @@ pub async fn analyze_diff(
         // Skip binary files (no diff and no content)
         if diff.is_empty() && after_content.is_none() && !is_new_file && !is_deleted {
             // Check if it's actually binary by looking at the file
-            let file_diff = git::get_file_diff(repo_path, spec, file_path).ok();
+            let file_diff =
+                git::get_file_diff(repo_path, spec, file_path, &DiffOptions::default()).ok();
             let is_binary = file_diff.is_some_and(|d| {
                 matches!(
                     d.after.as_ref().map(|f| &f.content),
-                    Some(FileContent::Binary)
+                    Some(FileContent::Binary(_))
                 ) || matches!(
                     d.before.as_ref().map(|f| &f.content),
-                    Some(FileContent::Binary)
+                    Some(FileContent::Binary(_))
                 )
             });
             if is_binary {
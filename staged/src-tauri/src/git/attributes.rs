@@ -0,0 +1,180 @@
+//! `.gitattributes` handling for diffs.
+//!
+//! This module provides:
+//! - `diff_attributes`: The `diff` attribute of a path (`-diff`/`binary`, or a
+//!   driver with a `textconv` command)
+//! - `textconv_blob`/`textconv_workdir`: Run a driver's textconv
+//! - `attach_linguist_flags`: Mark `linguist-generated`/`linguist-vendored` files
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+use git2::{AttrCheckFlags, AttrValue, Repository};
+
+use super::cli::{self, GitError};
+use super::types::FileDiffSummary;
+
+/// How git should diff a path, per its `diff` attribute.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct DiffAttributes {
+    /// `-diff` (or the `binary` macro): never show a text diff
+    pub binary: bool,
+    /// `diff.<driver>.textconv` for the path's `diff=<driver>`, if configured
+    pub textconv: Option<String>,
+}
+
+/// Look up the `diff` attribute of `path` (working tree, then index).
+pub(super) fn diff_attributes(repo: &Repository, path: &Path) -> DiffAttributes {
+    let Ok(value) = repo.get_attr(path, "diff", AttrCheckFlags::FILE_THEN_INDEX) else {
+        return DiffAttributes::default();
+    };
+
+    match AttrValue::from_string(value) {
+        AttrValue::False => DiffAttributes {
+            binary: true,
+            textconv: None,
+        },
+        AttrValue::String(driver) => {
+            let textconv = repo
+                .config()
+                .and_then(|config| config.get_string(&format!("diff.{driver}.textconv")))
+                .ok();
+            DiffAttributes {
+                binary: false,
+                textconv,
+            }
+        }
+        _ => DiffAttributes::default(),
+    }
+}
+
+/// Textconv a blob, given as `<rev>:<path>` or `:<path>` for the index.
+pub(super) fn textconv_blob(repo: &Path, object: &str) -> Result<Vec<u8>, GitError> {
+    cli::run_bytes(repo, &["cat-file", "--textconv", object])
+}
+
+/// Run a textconv command on a working-tree file. Like git, the command is
+/// run through the shell with the file's path appended.
+pub(super) fn textconv_workdir(command: &str, file: &Path) -> Result<Vec<u8>, GitError> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{command} \"$@\""))
+        .arg(command)
+        .arg(file)
+        .output()
+        .map_err(|e| GitError::CommandFailed(format!("Cannot run textconv: {e}")))?;
+
+    if !output.status.success() {
+        return Err(GitError::CommandFailed(format!(
+            "textconv failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+    Ok(output.stdout)
+}
+
+/// Set `generated`/`vendored` on each file from its `linguist-generated` and
+/// `linguist-vendored` attributes (as defined in the working tree and index).
+pub(super) fn attach_linguist_flags(
+    repo: &Path,
+    files: &mut [FileDiffSummary],
+) -> Result<(), GitError> {
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let paths: Vec<String> = files
+        .iter()
+        .map(|f| f.path().to_string_lossy().into_owned())
+        .collect();
+    let positions: HashMap<&str, usize> = paths
+        .iter()
+        .enumerate()
+        .map(|(i, path)| (path.as_str(), i))
+        .collect();
+    let input: String = paths.iter().map(|path| format!("{path}\0")).collect();
+    let output = cli::run_with_input(
+        repo,
+        &[
+            "check-attr",
+            "-z",
+            "--stdin",
+            "linguist-generated",
+            "linguist-vendored",
+        ],
+        &input,
+    )?;
+
+    // Output: <path>\0<attribute>\0<value>\0 per path and attribute
+    let fields: Vec<&str> = output.split('\0').collect();
+    for record in fields.chunks_exact(3) {
+        let [path, attribute, value] = record else {
+            continue;
+        };
+        let Some(&i) = positions.get(path) else {
+            continue;
+        };
+        let set = matches!(*value, "set" | "true");
+        match *attribute {
+            "linguist-generated" => files[i].generated = set,
+            "linguist-vendored" => files[i].vendored = set,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn git(repo: &Path, args: &[&str]) {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(repo)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?} failed");
+    }
+
+    #[test]
+    fn test_attributes() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        git(repo, &["init"]);
+        git(repo, &["config", "diff.upper.textconv", "tr a-z A-Z <"]);
+        std::fs::write(
+            repo.join(".gitattributes"),
+            "*.bin binary\n*.up diff=upper\ngen/** linguist-generated\nvendor/** linguist-vendored=true\n",
+        )
+        .unwrap();
+
+        let git_repo = Repository::open(repo).unwrap();
+        assert!(diff_attributes(&git_repo, Path::new("a.bin")).binary);
+        assert_eq!(
+            diff_attributes(&git_repo, Path::new("a.up"))
+                .textconv
+                .as_deref(),
+            Some("tr a-z A-Z <")
+        );
+        assert_eq!(
+            diff_attributes(&git_repo, Path::new("a.txt")),
+            DiffAttributes::default()
+        );
+
+        std::fs::write(repo.join("a.up"), "hello\n").unwrap();
+        let converted = textconv_workdir("tr a-z A-Z <", &repo.join("a.up")).unwrap();
+        assert_eq!(converted, b"HELLO\n");
+
+        let mut files: Vec<FileDiffSummary> = ["gen/a.pb.rs", "vendor/x.js", "src/main.rs"]
+            .iter()
+            .map(|p| FileDiffSummary::new(None, Some(PathBuf::from(p))))
+            .collect();
+        attach_linguist_flags(repo, &mut files).unwrap();
+        assert!(files[0].generated && !files[0].vendored);
+        assert!(!files[1].generated && files[1].vendored);
+        assert!(!files[2].generated && !files[2].vendored);
+    }
+}
@@ -0,0 +1,348 @@
+//! Metadata and raw content for binary files.
+//!
+//! This module provides:
+//! - `binary_info`: Size, blob hash, MIME type and image dimensions of a
+//!   binary file, reported in `FileContent::Binary`
+//! - `get_binary_pair`: Both sides of a binary file as base64 or temp-file
+//!   paths, for side-by-side and onion-skin image views
+
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use super::cli::GitError;
+use super::diff::{load_file_bytes, FileBytes};
+use super::types::{BinaryInfo, DiffSpec, ImageSize};
+
+/// Files larger than this can't be sent as base64 (use temp files instead).
+pub const MAX_BASE64_BYTES: u64 = 32 * 1024 * 1024;
+
+/// How `get_binary_pair` hands over file content.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BinaryTransport {
+    /// Inline, base64-encoded
+    #[default]
+    Base64,
+    /// Written to a temp file named after the content hash
+    TempFile,
+}
+
+/// Content of one side of a binary file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BinaryData {
+    Base64 { data: String },
+    TempFile { path: PathBuf },
+}
+
+/// One side of a binary file: its metadata and content.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BinarySide {
+    pub info: BinaryInfo,
+    pub data: BinaryData,
+}
+
+/// Both sides of a binary file; a side is None if the file doesn't exist there.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BinaryPair {
+    pub before: Option<BinarySide>,
+    pub after: Option<BinarySide>,
+}
+
+/// Get both sides of a file's raw content, for rendering binary files
+/// (images, mostly) in the UI.
+pub fn get_binary_pair(
+    repo: &Path,
+    spec: &DiffSpec,
+    path: &Path,
+    transport: BinaryTransport,
+) -> Result<BinaryPair, GitError> {
+    let (before, after) = load_file_bytes(repo, spec, path)?;
+    let path_str = path.to_string_lossy();
+    let side = |bytes: FileBytes| -> Result<Option<BinarySide>, GitError> {
+        let Some(bytes) = bytes else {
+            return Ok(None);
+        };
+        let info = binary_info(&path_str, &bytes);
+        let data = match transport {
+            BinaryTransport::Base64 if info.size > MAX_BASE64_BYTES => {
+                return Err(GitError::CommandFailed(format!(
+                    "{path_str} is too large to send inline ({} bytes)",
+                    info.size
+                )));
+            }
+            BinaryTransport::Base64 => BinaryData::Base64 {
+                data: base64::engine::general_purpose::STANDARD.encode(&bytes),
+            },
+            BinaryTransport::TempFile => BinaryData::TempFile {
+                path: write_temp_file(path, &info.hash, &bytes)?,
+            },
+        };
+        Ok(Some(BinarySide { info, data }))
+    };
+
+    Ok(BinaryPair {
+        before: side(before)?,
+        after: side(after)?,
+    })
+}
+
+/// Write `bytes` to `<temp>/staged-binary/<hash>.<ext>`, keeping the
+/// original extension so viewers recognize the format. Content-addressed,
+/// so an existing file is reused.
+fn write_temp_file(path: &Path, hash: &str, bytes: &[u8]) -> Result<PathBuf, GitError> {
+    let dir = std::env::temp_dir().join("staged-binary");
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| GitError::CommandFailed(format!("Cannot create temp dir: {e}")))?;
+
+    let mut target = dir.join(hash);
+    if let Some(ext) = path.extension() {
+        target.set_extension(ext);
+    }
+    if !target.exists() {
+        std::fs::write(&target, bytes)
+            .map_err(|e| GitError::CommandFailed(format!("Cannot write temp file: {e}")))?;
+    }
+    Ok(target)
+}
+
+/// Describe a binary file's content.
+pub(super) fn binary_info(path: &str, bytes: &[u8]) -> BinaryInfo {
+    let hash = git2::Oid::hash_object(git2::ObjectType::Blob, bytes)
+        .map(|oid| oid.to_string())
+        .unwrap_or_default();
+    let mime = sniff_mime(bytes)
+        .or_else(|| mime_from_extension(path))
+        .unwrap_or("application/octet-stream");
+
+    BinaryInfo {
+        size: bytes.len() as u64,
+        hash,
+        mime: mime.to_string(),
+        image: image_size(bytes),
+    }
+}
+
+/// MIME type from a file's magic bytes.
+fn sniff_mime(bytes: &[u8]) -> Option<&'static str> {
+    const MAGIC: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"BM", "image/bmp"),
+        (b"\x00\x00\x01\x00", "image/x-icon"),
+        (b"II*\x00", "image/tiff"),
+        (b"MM\x00*", "image/tiff"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"\x00asm", "application/wasm"),
+        (b"wOFF", "font/woff"),
+        (b"wOF2", "font/woff2"),
+    ];
+
+    if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        return Some("image/webp");
+    }
+    if bytes.len() >= 12 && &bytes[4..8] == b"ftyp" {
+        match &bytes[8..12] {
+            b"avif" | b"avis" => return Some("image/avif"),
+            b"heic" | b"heix" => return Some("image/heic"),
+            _ => {}
+        }
+    }
+    MAGIC
+        .iter()
+        .find(|(magic, _)| bytes.starts_with(magic))
+        .map(|(_, mime)| *mime)
+}
+
+/// MIME type from a path's extension, for formats without magic bytes.
+fn mime_from_extension(path: &str) -> Option<&'static str> {
+    let ext = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+    let mime = match ext.as_str() {
+        "svg" => "image/svg+xml",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "jar" => "application/java-archive",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+/// Read image dimensions from the header of a PNG, JPEG, GIF, WebP, BMP or
+/// ICO file.
+fn image_size(bytes: &[u8]) -> Option<ImageSize> {
+    let u16_be = |at: usize| Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?));
+    let u16_le = |at: usize| Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?));
+    let u32_be = |at: usize| Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+    let i32_le = |at: usize| Some(i32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+    let u24_le = |at: usize| {
+        let b = bytes.get(at..at + 3)?;
+        Some(u32::from(b[0]) | u32::from(b[1]) << 8 | u32::from(b[2]) << 16)
+    };
+    let size = |width: u32, height: u32| Some(ImageSize { width, height });
+
+    match sniff_mime(bytes)? {
+        "image/png" => size(u32_be(16)?, u32_be(20)?),
+        "image/gif" => size(u16_le(6)?.into(), u16_le(8)?.into()),
+        "image/bmp" => match i32_le(14)? {
+            // BITMAPCOREHEADER has 16-bit dimensions
+            12 => size(u16_le(18)?.into(), u16_le(20)?.into()),
+            _ => size(i32_le(18)?.unsigned_abs(), i32_le(22)?.unsigned_abs()),
+        },
+        "image/x-icon" => {
+            // First image in the directory; 0 means 256
+            let dimension = |b: u8| if b == 0 { 256 } else { u32::from(b) };
+            size(dimension(*bytes.get(6)?), dimension(*bytes.get(7)?))
+        }
+        "image/webp" => match bytes.get(12..16)? {
+            b"VP8 " => size(
+                u32::from(u16_le(26)? & 0x3fff),
+                u32::from(u16_le(28)? & 0x3fff),
+            ),
+            b"VP8L" => {
+                let b = bytes.get(21..25)?;
+                let (b0, b1, b2, b3) = (
+                    u32::from(b[0]),
+                    u32::from(b[1]),
+                    u32::from(b[2]),
+                    u32::from(b[3]),
+                );
+                size(
+                    1 + ((b1 & 0x3f) << 8 | b0),
+                    1 + ((b3 & 0xf) << 10 | b2 << 2 | (b1 & 0xc0) >> 6),
+                )
+            }
+            b"VP8X" => size(1 + u24_le(24)?, 1 + u24_le(27)?),
+            _ => None,
+        },
+        "image/jpeg" => {
+            // Walk the segments up to the first start-of-frame marker
+            let mut at = 2;
+            loop {
+                if *bytes.get(at)? != 0xff {
+                    return None;
+                }
+                let marker = *bytes.get(at + 1)?;
+                match marker {
+                    0xff => at += 1,
+                    0xd0..=0xd9 | 0x01 => at += 2,
+                    0xc0..=0xcf if !matches!(marker, 0xc4 | 0xc8 | 0xcc) => {
+                        return size(u16_be(at + 7)?.into(), u16_be(at + 5)?.into());
+                    }
+                    _ => at += 2 + usize::from(u16_be(at + 2)?),
+                }
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        bytes.extend(width.to_be_bytes());
+        bytes.extend(height.to_be_bytes());
+        bytes.extend([8, 6, 0, 0, 0, 0, 0, 0, 0]);
+        bytes
+    }
+
+    #[test]
+    fn test_image_size() {
+        let dims = |bytes: &[u8]| image_size(bytes).map(|s| (s.width, s.height));
+        assert_eq!(dims(&png(640, 480)), Some((640, 480)));
+        assert_eq!(dims(b"GIF89a\x20\x00\x10\x00"), Some((32, 16)));
+
+        // SOI, an APP0 segment, then SOF0 with height 100 and width 200
+        let jpeg = b"\xff\xd8\xff\xe0\x00\x04\x00\x00\xff\xc0\x00\x11\x08\x00\x64\x00\xc8\x03";
+        assert_eq!(dims(jpeg), Some((200, 100)));
+
+        let mut webp = b"RIFF\x00\x00\x00\x00WEBPVP8X\x0a\x00\x00\x00\x00\x00\x00\x00".to_vec();
+        webp.extend([0x3f, 0x00, 0x00, 0x1f, 0x00, 0x00]);
+        assert_eq!(dims(&webp), Some((64, 32)));
+
+        assert_eq!(dims(b"\x00\x01\x02\x03"), None);
+    }
+
+    #[test]
+    fn test_binary_info() {
+        let info = binary_info("icon.png", &png(16, 16));
+        assert_eq!(info.mime, "image/png");
+        assert_eq!(info.size, 33);
+        assert_eq!(info.hash.len(), 40);
+        assert_eq!(
+            binary_info("data.bin", b"\x00\x01").mime,
+            "application/octet-stream"
+        );
+    }
+
+    #[test]
+    fn test_get_binary_pair() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .args(args)
+                .current_dir(repo)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {args:?} failed");
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "test@test.com"]);
+        git(&["config", "user.name", "Test"]);
+        std::fs::write(repo.join("icon.png"), png(16, 16)).unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-m", "init"]);
+        std::fs::write(repo.join("icon.png"), png(32, 32)).unwrap();
+
+        let spec = DiffSpec::uncommitted();
+        let path = Path::new("icon.png");
+        let pair = get_binary_pair(repo, &spec, path, BinaryTransport::Base64).unwrap();
+        let before = pair.before.unwrap();
+        assert_eq!(
+            before.info.image,
+            Some(ImageSize {
+                width: 16,
+                height: 16
+            })
+        );
+        let BinaryData::Base64 { data } = before.data else {
+            panic!("expected base64");
+        };
+        assert_eq!(
+            base64::engine::general_purpose::STANDARD
+                .decode(data)
+                .unwrap(),
+            png(16, 16)
+        );
+
+        let pair = get_binary_pair(repo, &spec, path, BinaryTransport::TempFile).unwrap();
+        let after = pair.after.unwrap();
+        assert_eq!(
+            after.info.image,
+            Some(ImageSize {
+                width: 32,
+                height: 32
+            })
+        );
+        let BinaryData::TempFile { path } = after.data else {
+            panic!("expected a temp file");
+        };
+        assert_eq!(std::fs::read(&path).unwrap(), png(32, 32));
+        assert_eq!(path.extension().unwrap(), "png");
+    }
+}
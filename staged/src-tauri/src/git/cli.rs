@@ -1,5 +1,6 @@
+use std::io::{BufRead, BufReader, Write};
 use std::path::Path;
-use std::process::Command;
+use std::process::{Command, Stdio};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -22,6 +23,13 @@ pub enum GitError {
 
 /// Run a git command and return stdout as a string
 pub fn run(repo: &Path, args: &[&str]) -> Result<String, GitError> {
+    let stdout = run_bytes(repo, args)?;
+    String::from_utf8(stdout).map_err(|_| GitError::InvalidUtf8)
+}
+
+/// Run a git command and return raw stdout, for output that may not be UTF-8
+/// (e.g. `git show <rev>:<path>` of a UTF-16 or binary file).
+pub fn run_bytes(repo: &Path, args: &[&str]) -> Result<Vec<u8>, GitError> {
     let repo_str = repo
         .to_str()
         .ok_or_else(|| GitError::InvalidPath(repo.display().to_string()))?;
@@ -46,5 +54,108 @@ pub fn run(repo: &Path, args: &[&str]) -> Result<String, GitError> {
         return Err(GitError::CommandFailed(stderr.into_owned()));
     }
 
+    Ok(output.stdout)
+}
+
+/// Run a git command, passing each line of stdout (without its newline) to
+/// `on_line` as it's read. Once `on_line` returns false, git is killed and
+/// the rest of its output is never read.
+pub fn run_lines(
+    repo: &Path,
+    args: &[&str],
+    mut on_line: impl FnMut(&[u8]) -> bool,
+) -> Result<(), GitError> {
+    let repo_str = repo
+        .to_str()
+        .ok_or_else(|| GitError::InvalidPath(repo.display().to_string()))?;
+
+    let mut child = Command::new("git")
+        .args(["-C", repo_str])
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                GitError::GitNotFound
+            } else {
+                GitError::CommandFailed(e.to_string())
+            }
+        })?;
+
+    if let Some(stdout) = child.stdout.take() {
+        let mut reader = BufReader::new(stdout);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = reader
+                .read_until(b'\n', &mut line)
+                .map_err(|e| GitError::CommandFailed(e.to_string()))?;
+            if read == 0 {
+                break;
+            }
+            if !on_line(line.strip_suffix(b"\n").unwrap_or(&line)) {
+                // Stopped early: git's exit status no longer matters
+                let _ = child.kill();
+                let _ = child.wait();
+                return Ok(());
+            }
+        }
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| GitError::CommandFailed(e.to_string()))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("not a git repository") {
+            return Err(GitError::NotARepo(repo.display().to_string()));
+        }
+        return Err(GitError::CommandFailed(stderr.into_owned()));
+    }
+    Ok(())
+}
+
+/// Run a git command with `input` piped to stdin and return stdout as a string.
+/// Used for commands that read patches or object data from stdin (e.g. `git apply -`).
+pub fn run_with_input(repo: &Path, args: &[&str], input: &str) -> Result<String, GitError> {
+    let repo_str = repo
+        .to_str()
+        .ok_or_else(|| GitError::InvalidPath(repo.display().to_string()))?;
+
+    let mut child = Command::new("git")
+        .args(["-C", repo_str])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                GitError::GitNotFound
+            } else {
+                GitError::CommandFailed(e.to_string())
+            }
+        })?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input.as_bytes())
+            .map_err(|e| GitError::CommandFailed(format!("Failed to write to git stdin: {e}")))?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| GitError::CommandFailed(e.to_string()))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("not a git repository") {
+            return Err(GitError::NotARepo(repo.display().to_string()));
+        }
+        return Err(GitError::CommandFailed(stderr.into_owned()));
+    }
+
     String::from_utf8(output.stdout).map_err(|_| GitError::InvalidUtf8)
 }
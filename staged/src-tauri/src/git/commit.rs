@@ -1,8 +1,20 @@
 //! Git commit operations.
 
 use super::cli::{self, GitError};
+use super::stage;
+use super::types::{DiffSpec, LineSelection};
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
+/// A file to include in a commit, optionally restricted to selected lines.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitFile {
+    pub path: PathBuf,
+    /// Lines to commit. None commits the whole file.
+    #[serde(default)]
+    pub selection: Option<LineSelection>,
+}
+
 /// Create a commit with the specified files.
 /// All listed files are fully staged, then committed together.
 /// Returns the short SHA of the new commit.
@@ -24,6 +36,35 @@ pub fn commit(repo: &Path, paths: &[PathBuf], message: &str) -> Result<String, G
     Ok(output.trim().to_string())
 }
 
+/// Create a commit from whole files and selected lines.
+/// The index is reset to HEAD, then each file is staged fully or partially.
+/// Selections refer to `spec`'s diff, whose base should be HEAD.
+/// Returns the short SHA of the new commit.
+pub fn commit_selection(
+    repo: &Path,
+    spec: &DiffSpec,
+    files: &[CommitFile],
+    message: &str,
+) -> Result<String, GitError> {
+    // Reset the index to HEAD first so selections apply against a known state
+    cli::run(repo, &["reset", "HEAD"])?;
+
+    for file in files {
+        match &file.selection {
+            Some(selection) => stage::stage_selection(repo, spec, &file.path, selection)?,
+            None => {
+                let path_str = file.path.to_string_lossy();
+                cli::run(repo, &["add", "--", &path_str])?;
+            }
+        }
+    }
+
+    cli::run(repo, &["commit", "-m", message])?;
+
+    let output = cli::run(repo, &["rev-parse", "--short", "HEAD"])?;
+    Ok(output.trim().to_string())
+}
+
 #[cfg(test)]
 mod tests {
     // Integration tests would require a real git repo
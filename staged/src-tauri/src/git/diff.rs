@@ -1,11 +1,22 @@
+use super::attributes;
 use super::cli::{self, GitError};
+use super::encoding;
+use super::filesystem;
+use super::inline;
+use super::lfs::{self, LfsPointer};
+use super::lockfile;
+use super::patch::{self, PatchView};
+use super::range_diff;
 use super::refs;
+use super::stash;
 use super::types::*;
-use git2::{DiffOptions, Repository};
+use git2::Repository;
 use std::cell::RefCell;
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-/// Resolve a GitRef, converting MergeBase/MergeBaseOf to a concrete SHA.
+/// Resolve a GitRef, converting MergeBase/MergeBaseOf to a concrete SHA
+/// and Stash/Rebased to the trees they describe.
 fn resolve_ref(repo: &Path, git_ref: &GitRef) -> Result<GitRef, GitError> {
     match git_ref {
         GitRef::MergeBase => {
@@ -17,23 +28,103 @@ fn resolve_ref(repo: &Path, git_ref: &GitRef) -> Result<GitRef, GitError> {
             let sha = refs::merge_base(repo, base_branch, head_ref)?;
             Ok(GitRef::Rev(sha))
         }
+        GitRef::Stash(stash) => Ok(GitRef::Rev(stash::stash_tree(repo, stash)?)),
+        GitRef::Rebased([rev, onto, upstream]) => Ok(GitRef::Rev(range_diff::rebased_tree(
+            repo, rev, onto, upstream,
+        )?)),
         other => Ok(other.clone()),
     }
 }
 
-/// Resolve a DiffSpec, converting any MergeBase refs to concrete SHAs.
-fn resolve_spec(repo: &Path, spec: &DiffSpec) -> Result<DiffSpec, GitError> {
-    Ok(DiffSpec {
+/// Resolve a DiffSpec, converting any MergeBase/Stash refs to concrete revs
+/// and a patch file to the trees before and after applying it.
+pub(super) fn resolve_spec(repo: &Path, spec: &DiffSpec) -> Result<DiffSpec, GitError> {
+    match resolve_view(repo, spec)? {
+        PatchView::Trees(spec) => Ok(spec),
+        PatchView::HunksOnly(_) => Err(GitError::CommandFailed(
+            "The patch's base isn't available, so only its hunks can be shown".to_string(),
+        )),
+    }
+}
+
+/// Like `resolve_spec`, but a patch that can't be applied to a base gives
+/// its hunks instead of an error. Patch files are read (and applied) once.
+fn resolve_view(repo: &Path, spec: &DiffSpec) -> Result<PatchView, GitError> {
+    if let GitRef::Patch(file) = &spec.head {
+        let base = resolve_ref(repo, &spec.base)?;
+        return patch::open(repo, &base, Path::new(file));
+    }
+    if let GitRef::Patch(_) = &spec.base {
+        return Err(GitError::CommandFailed(
+            "A patch can only be diffed against itself or a base rev".to_string(),
+        ));
+    }
+
+    Ok(PatchView::Trees(DiffSpec {
         base: resolve_ref(repo, &spec.base)?,
         head: resolve_ref(repo, &spec.head)?,
-    })
+    }))
+}
+
+/// Command-line flags for the whitespace and algorithm settings in `options`.
+fn content_args(options: &DiffOptions) -> Vec<String> {
+    let mut args = Vec::new();
+    match options.whitespace {
+        WhitespaceMode::None => {}
+        WhitespaceMode::IgnoreAll => args.push("--ignore-all-space".to_string()),
+        WhitespaceMode::IgnoreChange => args.push("--ignore-space-change".to_string()),
+        WhitespaceMode::IgnoreEol => args.push("--ignore-space-at-eol".to_string()),
+    }
+    if options.ignore_blank_lines {
+        args.push("--ignore-blank-lines".to_string());
+    }
+    // Myers is git's default; leave it implicit so `diff.algorithm` config still applies
+    match options.algorithm {
+        DiffAlgorithm::Myers => {}
+        DiffAlgorithm::Minimal => args.push("--diff-algorithm=minimal".to_string()),
+        DiffAlgorithm::Patience => args.push("--diff-algorithm=patience".to_string()),
+        DiffAlgorithm::Histogram => args.push("--diff-algorithm=histogram".to_string()),
+    }
+    args
+}
+
+/// Command-line flags for rename/copy detection in `git diff`.
+fn rename_args(options: &DiffOptions) -> Vec<String> {
+    let mut args = Vec::new();
+    match options.rename_threshold {
+        Some(pct) => args.push(format!("--find-renames={}%", pct.min(100))),
+        None => args.push("--find-renames".to_string()),
+    }
+    if let Some(pct) = options.copy_threshold {
+        args.push(format!("--find-copies={}%", pct.min(100)));
+    }
+    args
+}
+
+/// Error for index comparisons other than rev..index and index..@.
+fn unsupported_index_spec() -> GitError {
+    GitError::CommandFailed(
+        "The index can only be diffed against a commit (rev..index) or the working tree (index..@)"
+            .to_string(),
+    )
+}
+
+fn unsupported_path_spec() -> GitError {
+    GitError::CommandFailed(
+        "Filesystem paths can only be diffed against each other, and only for viewing".to_string(),
+    )
 }
 
 /// Get unified diff output for a single file.
 ///
 /// Returns the standard unified diff format (like `git diff`).
 /// This is used for AI analysis prompts.
-pub fn get_unified_diff(repo: &Path, spec: &DiffSpec, path: &Path) -> Result<String, GitError> {
+pub fn get_unified_diff(
+    repo: &Path,
+    spec: &DiffSpec,
+    path: &Path,
+    options: &DiffOptions,
+) -> Result<String, GitError> {
     // Resolve MergeBase to concrete SHA
     let spec = resolve_spec(repo, spec)?;
 
@@ -41,26 +132,68 @@ pub fn get_unified_diff(repo: &Path, spec: &DiffSpec, path: &Path) -> Result<Str
         .to_str()
         .ok_or_else(|| GitError::InvalidPath(path.display().to_string()))?;
 
+    let mut args = vec!["diff".to_string()];
+    args.extend(content_args(options));
+    if let Some(n) = options.context_lines {
+        args.push(format!("--unified={n}"));
+    }
+
     match (&spec.base, &spec.head) {
         (GitRef::Rev(base), GitRef::WorkingTree) => {
             // Diff from commit to working tree
-            cli::run(repo, &["diff", base.as_str(), "--", path_str])
+            args.extend([base.clone(), "--".to_string(), path_str.to_string()]);
         }
         (GitRef::Rev(base), GitRef::Rev(head)) => {
             // Diff between two commits
-            cli::run(
-                repo,
-                &["diff", base.as_str(), head.as_str(), "--", path_str],
-            )
+            args.extend([
+                base.clone(),
+                head.clone(),
+                "--".to_string(),
+                path_str.to_string(),
+            ]);
         }
-        (GitRef::WorkingTree, _) => Err(GitError::CommandFailed(
-            "Cannot use working tree as base".to_string(),
-        )),
-        (GitRef::MergeBase | GitRef::MergeBaseOf(_), _)
-        | (_, GitRef::MergeBase | GitRef::MergeBaseOf(_)) => {
-            unreachable!("MergeBase/MergeBaseOf should have been resolved")
+        (GitRef::Rev(base), GitRef::Index) => {
+            // Staged changes
+            args.extend([
+                "--cached".to_string(),
+                base.clone(),
+                "--".to_string(),
+                path_str.to_string(),
+            ]);
+        }
+        (GitRef::Index, GitRef::WorkingTree) => {
+            // Unstaged changes: plain `git diff` compares the index to the working tree
+            args.extend(["--".to_string(), path_str.to_string()]);
+        }
+        (GitRef::WorkingTree, _) => {
+            return Err(GitError::CommandFailed(
+                "Cannot use working tree as base".to_string(),
+            ))
+        }
+        (GitRef::Index, _) | (_, GitRef::Index) => return Err(unsupported_index_spec()),
+        (GitRef::Path(_), _) | (_, GitRef::Path(_)) => return Err(unsupported_path_spec()),
+        (
+            GitRef::MergeBase
+            | GitRef::MergeBaseOf(_)
+            | GitRef::Stash(_)
+            | GitRef::Patch(_)
+            | GitRef::Rebased(_),
+            _,
+        )
+        | (
+            _,
+            GitRef::MergeBase
+            | GitRef::MergeBaseOf(_)
+            | GitRef::Stash(_)
+            | GitRef::Patch(_)
+            | GitRef::Rebased(_),
+        ) => {
+            unreachable!("MergeBase/MergeBaseOf/Stash/Patch/Rebased should have been resolved")
         }
     }
+
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    cli::run(repo, &args)
 }
 
 /// A hunk from git diff (0-indexed line numbers)
@@ -83,28 +216,249 @@ struct Hunk {
 ///
 /// For commit..commit diffs: uses `git diff --name-status -z` since status doesn't
 /// support arbitrary commit ranges.
-pub fn list_diff_files(repo: &Path, spec: &DiffSpec) -> Result<Vec<FileDiffSummary>, GitError> {
+///
+/// Each file also gets line counts, a binary flag and its old/new modes.
+///
+/// Only the rename/copy settings of `options` affect which files are listed.
+/// `git status` can't detect copies, so `copy_threshold` only applies to committed changes.
+pub fn list_diff_files(
+    repo: &Path,
+    spec: &DiffSpec,
+    options: &DiffOptions,
+) -> Result<Vec<FileDiffSummary>, GitError> {
+    if let Some((base, head)) = spec.as_paths() {
+        return filesystem::list_files(base, head, options);
+    }
     // Resolve MergeBase to concrete SHA
-    let spec = resolve_spec(repo, spec)?;
+    let spec = match resolve_view(repo, spec)? {
+        PatchView::Trees(spec) => spec,
+        PatchView::HunksOnly(patches) => return Ok(patch::list_files(&patches)),
+    };
 
-    match (&spec.base, &spec.head) {
+    // Each arm also says which revs `git diff` should compare for stats
+    let (mut files, stat_revs): (Vec<FileDiffSummary>, Vec<&str>) = match (&spec.base, &spec.head) {
         (GitRef::Rev(base), GitRef::WorkingTree) => {
             // Working tree diff - use git status for fsmonitor support
-            list_working_tree_changes(repo, base)
+            (list_working_tree_changes(repo, base, options)?, vec![base])
         }
         (GitRef::Rev(base), GitRef::Rev(head)) => {
             // Commit range - use git diff
-            let args = ["diff", "--name-status", "-z", base.as_str(), head.as_str()];
-            let output = cli::run(repo, &args)?;
-            parse_name_status(&output)
+            let output = run_name_status(repo, &[base, head], options)?;
+            (parse_name_status(&output)?, vec![base, head])
         }
-        (GitRef::WorkingTree, _) => Err(GitError::CommandFailed(
-            "Cannot use working tree as base".to_string(),
-        )),
-        (GitRef::MergeBase | GitRef::MergeBaseOf(_), _)
-        | (_, GitRef::MergeBase | GitRef::MergeBaseOf(_)) => {
-            unreachable!("MergeBase/MergeBaseOf should have been resolved")
+        (GitRef::Rev(base), GitRef::Index) => {
+            // Staged changes
+            let output = run_name_status(repo, &["--cached", base], options)?;
+            (parse_name_status(&output)?, vec!["--cached", base])
         }
+        (GitRef::Index, GitRef::WorkingTree) => (list_unstaged_changes(repo, options)?, vec![]),
+        (GitRef::WorkingTree, _) => {
+            return Err(GitError::CommandFailed(
+                "Cannot use working tree as base".to_string(),
+            ))
+        }
+        (GitRef::Index, _) | (_, GitRef::Index) => return Err(unsupported_index_spec()),
+        (GitRef::Path(_), _) | (_, GitRef::Path(_)) => return Err(unsupported_path_spec()),
+        (
+            GitRef::MergeBase
+            | GitRef::MergeBaseOf(_)
+            | GitRef::Stash(_)
+            | GitRef::Patch(_)
+            | GitRef::Rebased(_),
+            _,
+        )
+        | (
+            _,
+            GitRef::MergeBase
+            | GitRef::MergeBaseOf(_)
+            | GitRef::Stash(_)
+            | GitRef::Patch(_)
+            | GitRef::Rebased(_),
+        ) => {
+            unreachable!("MergeBase/MergeBaseOf/Stash/Patch/Rebased should have been resolved")
+        }
+    };
+
+    // Stats are best-effort: failing to compute them shouldn't hide the file list
+    if let Err(e) = attach_stats(repo, &mut files, &stat_revs, options) {
+        log::warn!("Cannot compute diff stats: {e}");
+    }
+    if let Err(e) = attributes::attach_linguist_flags(repo, &mut files) {
+        log::warn!("Cannot read linguist attributes: {e}");
+    }
+    lockfile::attach_lockfile_summaries(repo, &spec.base, &spec.head, &mut files);
+
+    Ok(files)
+}
+
+/// Per-path stats collected from `git diff --raw --numstat`.
+#[derive(Default)]
+struct PathStats {
+    additions: Option<u32>,
+    deletions: Option<u32>,
+    binary: bool,
+    old_mode: Option<FileMode>,
+    new_mode: Option<FileMode>,
+}
+
+/// Fill in line counts, binary flags and modes for `files`.
+///
+/// Tracked files get their stats from a single `git diff --raw --numstat -z`
+/// over `revs` (the working tree when no head rev is given); the file list itself
+/// still comes from `git status` for working-tree diffs. Untracked files aren't
+/// covered by `git diff`, so they're read from disk.
+fn attach_stats(
+    repo: &Path,
+    files: &mut [FileDiffSummary],
+    revs: &[&str],
+    options: &DiffOptions,
+) -> Result<(), GitError> {
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let mut args = vec![
+        "diff".to_string(),
+        "--raw".to_string(),
+        "--numstat".to_string(),
+        "-z".to_string(),
+    ];
+    args.extend(rename_args(options));
+    args.extend(revs.iter().map(|r| r.to_string()));
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let stats = parse_raw_numstat(&cli::run(repo, &args)?);
+
+    for file in files.iter_mut() {
+        let stat = match stats.get(file.path()) {
+            Some(stat) => stat,
+            None if file.before.is_none() => {
+                // Untracked
+                if let Some(stat) = untracked_stats(repo, file.path()) {
+                    apply_stats(file, &stat);
+                }
+                continue;
+            }
+            None => continue,
+        };
+        apply_stats(file, stat);
+    }
+
+    Ok(())
+}
+
+fn apply_stats(file: &mut FileDiffSummary, stat: &PathStats) {
+    file.additions = stat.additions;
+    file.deletions = stat.deletions;
+    file.binary = stat.binary;
+    file.old_mode = stat.old_mode;
+    file.new_mode = stat.new_mode;
+}
+
+/// Parse `git diff --raw --numstat -z` output, keyed by the new path
+/// (the old path for deletions).
+///
+/// Raw records: `:<old mode> <new mode> <old sha> <new sha> <status>\0<path>\0[<new path>\0]`
+/// Numstat records: `<added>\t<deleted>\t<path>\0`, or
+/// `<added>\t<deleted>\t\0<old path>\0<new path>\0` for renames and copies.
+/// Binary files have `-` for both counts.
+fn parse_raw_numstat(output: &str) -> HashMap<PathBuf, PathStats> {
+    let mut stats: HashMap<PathBuf, PathStats> = HashMap::new();
+    let mut parts = output.split('\0');
+
+    while let Some(record) = parts.next() {
+        if record.is_empty() {
+            continue;
+        }
+
+        if let Some(raw) = record.strip_prefix(':') {
+            let fields: Vec<&str> = raw.split(' ').collect();
+            let [old_mode, new_mode, _, _, status] = fields.as_slice() else {
+                continue;
+            };
+            let path = if status.starts_with('R') || status.starts_with('C') {
+                parts.next();
+                parts.next()
+            } else {
+                parts.next()
+            };
+            let Some(path) = path else { break };
+            let entry = stats.entry(PathBuf::from(path)).or_default();
+            entry.old_mode = FileMode::from_octal(old_mode);
+            entry.new_mode = FileMode::from_octal(new_mode);
+            continue;
+        }
+
+        let mut fields = record.splitn(3, '\t');
+        let (Some(added), Some(deleted), Some(path)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let path = if path.is_empty() {
+            // Rename or copy: old and new paths follow
+            parts.next();
+            match parts.next() {
+                Some(new) => new,
+                None => break,
+            }
+        } else {
+            path
+        };
+        let entry = stats.entry(PathBuf::from(path)).or_default();
+        entry.binary = added == "-" && deleted == "-";
+        entry.additions = added.parse().ok();
+        entry.deletions = deleted.parse().ok();
+    }
+
+    stats
+}
+
+/// Stats for an untracked file: every line is an addition.
+fn untracked_stats(repo: &Path, path: &Path) -> Option<PathStats> {
+    let full_path = repo.join(path);
+    let metadata = std::fs::symlink_metadata(&full_path).ok()?;
+
+    if metadata.file_type().is_symlink() {
+        return Some(PathStats {
+            additions: Some(1),
+            deletions: Some(0),
+            new_mode: Some(FileMode::Symlink),
+            ..Default::default()
+        });
+    }
+
+    #[cfg(unix)]
+    let executable = {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o111 != 0
+    };
+    #[cfg(not(unix))]
+    let executable = false;
+
+    let bytes = std::fs::read(&full_path).ok()?;
+    let binary = is_binary(&bytes);
+    let lines = count_lines(&bytes);
+
+    Some(PathStats {
+        additions: (!binary).then_some(lines),
+        deletions: (!binary).then_some(0),
+        binary,
+        old_mode: None,
+        new_mode: Some(if executable {
+            FileMode::Executable
+        } else {
+            FileMode::Regular
+        }),
+    })
+}
+
+/// Number of lines as git counts them (a final line without a newline still counts).
+fn count_lines(bytes: &[u8]) -> u32 {
+    let newlines = bytes.iter().filter(|&&b| b == b'\n').count() as u32;
+    if bytes.last().is_some_and(|&b| b != b'\n') {
+        newlines + 1
+    } else {
+        newlines
     }
 }
 
@@ -113,9 +467,17 @@ pub fn list_diff_files(repo: &Path, spec: &DiffSpec) -> Result<Vec<FileDiffSumma
 ///
 /// When base is HEAD, we show all uncommitted changes (staged + unstaged + untracked).
 /// When base is another ref, we show what would change if you committed now and compared to that ref.
-fn list_working_tree_changes(repo: &Path, base: &str) -> Result<Vec<FileDiffSummary>, GitError> {
+fn list_working_tree_changes(
+    repo: &Path,
+    base: &str,
+    options: &DiffOptions,
+) -> Result<Vec<FileDiffSummary>, GitError> {
     // Get status (includes staged, unstaged, and untracked)
-    let output = cli::run(repo, &["status", "--porcelain", "-z"])?;
+    let find_renames = match options.rename_threshold {
+        Some(pct) => format!("--find-renames={}%", pct.min(100)),
+        None => "--find-renames".to_string(),
+    };
+    let output = cli::run(repo, &["status", "--porcelain", "-z", &find_renames])?;
     let status_files = parse_porcelain_status(repo, &output)?;
 
     // If base is HEAD, status gives us exactly what we need
@@ -126,7 +488,7 @@ fn list_working_tree_changes(repo: &Path, base: &str) -> Result<Vec<FileDiffSumm
     // For other bases (e.g., main), we need to combine:
     // 1. Files changed between base and HEAD (committed changes)
     // 2. Files with uncommitted changes (from status)
-    let diff_output = cli::run(repo, &["diff", "--name-status", "-z", base, "HEAD"])?;
+    let diff_output = run_name_status(repo, &[base, "HEAD"], options)?;
     let committed_files = parse_name_status(&diff_output)?;
 
     // Merge: status files take precedence (they reflect current working tree state)
@@ -147,6 +509,40 @@ fn list_working_tree_changes(repo: &Path, base: &str) -> Result<Vec<FileDiffSumm
     Ok(result_map.into_values().collect())
 }
 
+/// List unstaged changes (index..@): tracked files that differ from the index,
+/// plus untracked files.
+fn list_unstaged_changes(
+    repo: &Path,
+    options: &DiffOptions,
+) -> Result<Vec<FileDiffSummary>, GitError> {
+    let output = run_name_status(repo, &[], options)?;
+    let mut results = parse_name_status(&output)?;
+
+    let untracked = cli::run(repo, &["ls-files", "--others", "--exclude-standard", "-z"])?;
+    results.extend(
+        untracked
+            .split('\0')
+            .filter(|s| !s.is_empty())
+            .map(|path| FileDiffSummary::new(None, Some(path.into()))),
+    );
+
+    Ok(results)
+}
+
+/// Run `git diff --name-status -z` with rename/copy detection.
+/// `revs` selects what is compared (e.g. `[base, head]` or `["--cached", base]`).
+fn run_name_status(repo: &Path, revs: &[&str], options: &DiffOptions) -> Result<String, GitError> {
+    let mut args = vec![
+        "diff".to_string(),
+        "--name-status".to_string(),
+        "-z".to_string(),
+    ];
+    args.extend(rename_args(options));
+    args.extend(revs.iter().map(|r| r.to_string()));
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    cli::run(repo, &args)
+}
+
 /// Parse `git status --porcelain -z` output.
 /// Format: XY PATH\0 (or XY OLD\0NEW\0 for renames)
 /// X = index status, Y = worktree status
@@ -192,42 +588,30 @@ fn parse_porcelain_status(repo: &Path, output: &str) -> Result<Vec<FileDiffSumma
                         // It's a directory - expand into individual files
                         let files = expand_untracked_dir(repo, p)?;
                         for file in files {
-                            results.push(FileDiffSummary {
-                                before: None,
-                                after: Some(file.into()),
-                            });
+                            results.push(FileDiffSummary::new(None, Some(file.into())));
                         }
                     } else {
-                        results.push(FileDiffSummary {
-                            before: None,
-                            after: Some(p.clone().into()),
-                        });
+                        results.push(FileDiffSummary::new(None, Some(p.clone().into())));
                     }
                 }
             }
             ('A', _) | (_, 'A') => {
-                results.push(FileDiffSummary {
-                    before: None,
-                    after: new_path.map(Into::into),
-                });
+                results.push(FileDiffSummary::new(None, new_path.map(Into::into)));
             }
             ('D', _) | (_, 'D') => {
-                results.push(FileDiffSummary {
-                    before: new_path.map(Into::into),
-                    after: None,
-                });
+                results.push(FileDiffSummary::new(new_path.map(Into::into), None));
             }
             ('R', _) | ('C', _) => {
-                results.push(FileDiffSummary {
-                    before: old_path.map(Into::into),
-                    after: new_path.map(Into::into),
-                });
+                results.push(FileDiffSummary::new(
+                    old_path.map(Into::into),
+                    new_path.map(Into::into),
+                ));
             }
             _ => {
-                results.push(FileDiffSummary {
-                    before: new_path.clone().map(Into::into),
-                    after: new_path.map(Into::into),
-                });
+                results.push(FileDiffSummary::new(
+                    new_path.clone().map(Into::into),
+                    new_path.map(Into::into),
+                ));
             }
         };
     }
@@ -267,38 +651,26 @@ fn parse_name_status(output: &str) -> Result<Vec<FileDiffSummary>, GitError> {
             'A' => {
                 // Added: just one path
                 if let Some(path) = parts.next() {
-                    results.push(FileDiffSummary {
-                        before: None,
-                        after: Some(path.into()),
-                    });
+                    results.push(FileDiffSummary::new(None, Some(path.into())));
                 }
             }
             'D' => {
                 // Deleted: just one path
                 if let Some(path) = parts.next() {
-                    results.push(FileDiffSummary {
-                        before: Some(path.into()),
-                        after: None,
-                    });
+                    results.push(FileDiffSummary::new(Some(path.into()), None));
                 }
             }
             'M' | 'T' => {
                 // Modified or Type changed: just one path
                 if let Some(path) = parts.next() {
-                    results.push(FileDiffSummary {
-                        before: Some(path.into()),
-                        after: Some(path.into()),
-                    });
+                    results.push(FileDiffSummary::new(Some(path.into()), Some(path.into())));
                 }
             }
             'R' | 'C' => {
                 // Renamed or Copied: two paths (old, new)
                 // Status might include similarity percentage like R100
                 if let (Some(old), Some(new)) = (parts.next(), parts.next()) {
-                    results.push(FileDiffSummary {
-                        before: Some(old.into()),
-                        after: Some(new.into()),
-                    });
+                    results.push(FileDiffSummary::new(Some(old.into()), Some(new.into())));
                 }
             }
             _ => {
@@ -314,33 +686,111 @@ fn parse_name_status(output: &str) -> Result<Vec<FileDiffSummary>, GitError> {
 /// Get full diff content for a single file using libgit2.
 /// This is reliable and battle-tested - we use git CLI only for list_diff_files
 /// where fsmonitor support matters for performance.
-pub fn get_file_diff(repo_path: &Path, spec: &DiffSpec, path: &Path) -> Result<FileDiff, GitError> {
+///
+/// libgit2 has no histogram algorithm, so histogram diffs take their hunks from
+/// the git CLI instead.
+pub fn get_file_diff(
+    repo_path: &Path,
+    spec: &DiffSpec,
+    path: &Path,
+    options: &DiffOptions,
+) -> Result<FileDiff, GitError> {
+    let mut diff = load_file_diff(repo_path, spec, path, options)?;
+    diff.inline_changes = inline::compute_inline_changes(
+        &diff.before,
+        &diff.after,
+        &diff.alignments,
+        options.inline_tokenizer,
+    );
+    Ok(diff)
+}
+
+/// Load both sides of a file and align them, without inline changes
+/// (callers decide which alignments are worth tokenizing).
+pub(super) fn load_file_diff(
+    repo_path: &Path,
+    spec: &DiffSpec,
+    path: &Path,
+    options: &DiffOptions,
+) -> Result<FileDiff, GitError> {
+    if let Some((base, head)) = spec.as_paths() {
+        return filesystem::load_file_diff(base, head, path, options);
+    }
     // Resolve MergeBase to concrete SHA
-    let spec = resolve_spec(repo_path, spec)?;
+    let spec = match resolve_view(repo_path, spec)? {
+        PatchView::Trees(spec) => spec,
+        PatchView::HunksOnly(patches) => return patch::load_file_diff(&patches, path),
+    };
+
+    match (&spec.base, &spec.head) {
+        (GitRef::WorkingTree, _) => {
+            return Err(GitError::CommandFailed(
+                "Cannot use working tree as base".to_string(),
+            ))
+        }
+        (GitRef::Rev(_), GitRef::Index) | (GitRef::Index, GitRef::WorkingTree) => {}
+        (GitRef::Index, _) | (_, GitRef::Index) => return Err(unsupported_index_spec()),
+        _ => {}
+    }
 
     let repo = Repository::discover(repo_path).map_err(|e| GitError::NotARepo(e.to_string()))?;
 
     // Resolve trees
     let base_tree = resolve_to_tree(&repo, &spec.base)?;
     let head_tree = resolve_to_tree(&repo, &spec.head)?;
-    let is_working_tree = matches!(spec.head, GitRef::WorkingTree);
 
     // Load file content
-    let before = load_file_from_tree(&repo, base_tree.as_ref(), path)?;
-    let after = if is_working_tree {
-        load_file_from_workdir(&repo, path)?
+    let (before, before_lfs) = match spec.base {
+        GitRef::Index => load_file_from_index(&repo, path)?,
+        _ => load_file_from_tree(&repo, base_tree.as_ref(), path)?,
+    };
+    let (after, after_lfs) = match spec.head {
+        GitRef::WorkingTree => load_file_from_workdir(&repo, path)?,
+        GitRef::Index => load_file_from_index(&repo, path)?,
+        _ => load_file_from_tree(&repo, head_tree.as_ref(), path)?,
+    };
+
+    // .gitattributes: files with a textconv driver are shown (and diffed by
+    // git) as their converted text. `-diff` files were loaded as binary.
+    let attrs = attributes::diff_attributes(&repo, path);
+    let (before, after) = if let Some(command) = &attrs.textconv {
+        (
+            textconv_file(repo_path, &repo, &spec.base, path, command, before),
+            textconv_file(repo_path, &repo, &spec.head, path, command, after),
+        )
     } else {
-        load_file_from_tree(&repo, head_tree.as_ref(), path)?
+        (before, after)
     };
 
-    // Get hunks via libgit2
-    let hunks = get_hunks_libgit2(
-        &repo,
-        base_tree.as_ref(),
-        head_tree.as_ref(),
-        is_working_tree,
-        path,
-    )?;
+    // Encoding- or line-ending-only changes decode to the same lines; report
+    // them as unchanged rather than as every line changed. The difference
+    // is visible in each side's `encoding`/`eol`.
+    let same_text = matches!(
+        (&before, &after),
+        (Some(b), Some(a)) if matches!(b.content, FileContent::Text { .. }) && b.content == a.content
+    );
+
+    let hunks = if same_text {
+        vec![]
+    } else if before_lfs || after_lfs {
+        // git only sees the LFS pointers; diff the objects' text instead
+        text_hunks(&before, &after, options)?
+    } else if !lines_match_blob(&before) || !lines_match_blob(&after) {
+        // git would diff raw UTF-16 as binary, or count lines differently
+        text_hunks(&before, &after, options)?
+    } else if options.algorithm == DiffAlgorithm::Histogram || attrs.textconv.is_some() {
+        // libgit2 doesn't run textconv drivers
+        get_hunks_cli(repo_path, &spec, path, options)?
+    } else {
+        get_hunks_libgit2(
+            &repo,
+            &spec,
+            base_tree.as_ref(),
+            head_tree.as_ref(),
+            path,
+            options,
+        )?
+    };
 
     // Compute alignments from hunks
     let alignments = compute_alignments_from_hunks(&hunks, &before, &after);
@@ -349,17 +799,79 @@ pub fn get_file_diff(repo_path: &Path, spec: &DiffSpec, path: &Path) -> Result<F
         before,
         after,
         alignments,
+        inline_changes: vec![],
+        highlights: None,
     })
 }
 
-/// Resolve a GitRef to a tree (or None for working tree)
-/// Note: MergeBase/MergeBaseOf should already be resolved before calling this
+/// Whether git's diff of the stored bytes lines up with a side's decoded
+/// lines: true unless it's text in another encoding than UTF-8, or with
+/// line breaks other than LF/CRLF.
+fn lines_match_blob(file: &Option<File>) -> bool {
+    let Some(file) = file else {
+        return true;
+    };
+    let utf8 = match &file.encoding {
+        Some(encoding) => encoding.name == "UTF-8",
+        None => true,
+    };
+    let eol_ok = !matches!(file.eol, Some(LineEnding::Cr | LineEnding::Mixed));
+    utf8 && eol_ok
+}
+
+/// Replace a file's content with its textconv output. Keeps the original
+/// content if conversion fails.
+fn textconv_file(
+    repo_path: &Path,
+    repo: &Repository,
+    git_ref: &GitRef,
+    path: &Path,
+    command: &str,
+    file: Option<File>,
+) -> Option<File> {
+    let file = file?;
+    if !matches!(
+        file.content,
+        FileContent::Text { .. } | FileContent::Binary(_)
+    ) {
+        return Some(file);
+    }
+
+    let converted = match git_ref {
+        GitRef::WorkingTree => match repo.workdir() {
+            Some(workdir) => attributes::textconv_workdir(command, &workdir.join(path)),
+            None => return Some(file),
+        },
+        GitRef::Index => attributes::textconv_blob(repo_path, &format!(":{}", file.path)),
+        GitRef::Rev(rev) => attributes::textconv_blob(repo_path, &format!("{rev}:{}", file.path)),
+        GitRef::Path(_) => return Some(file),
+        GitRef::MergeBase
+        | GitRef::MergeBaseOf(_)
+        | GitRef::Stash(_)
+        | GitRef::Patch(_)
+        | GitRef::Rebased(_) => {
+            unreachable!("MergeBase/MergeBaseOf/Stash/Patch/Rebased should have been resolved")
+        }
+    };
+
+    match converted {
+        Ok(bytes) => Some(encoding::decode_file(file.path, &bytes, None)),
+        Err(e) => {
+            log::warn!("textconv failed for {}: {e}", file.path);
+            Some(file)
+        }
+    }
+}
+
+/// Resolve a GitRef to a tree (or None for working tree and index)
+/// Note: MergeBase/MergeBaseOf/Stash should already be resolved before calling this
 fn resolve_to_tree<'a>(
     repo: &'a Repository,
     git_ref: &GitRef,
 ) -> Result<Option<git2::Tree<'a>>, GitError> {
     match git_ref {
-        GitRef::WorkingTree => Ok(None),
+        GitRef::WorkingTree | GitRef::Index => Ok(None),
+        GitRef::Path(_) => Err(unsupported_path_spec()),
         GitRef::Rev(rev) => {
             let obj = repo
                 .revparse_single(rev)
@@ -369,102 +881,257 @@ fn resolve_to_tree<'a>(
             })?;
             Ok(Some(tree))
         }
-        GitRef::MergeBase | GitRef::MergeBaseOf(_) => {
+        GitRef::MergeBase
+        | GitRef::MergeBaseOf(_)
+        | GitRef::Stash(_)
+        | GitRef::Patch(_)
+        | GitRef::Rebased(_) => {
             unreachable!(
-                "MergeBase/MergeBaseOf should have been resolved before calling resolve_to_tree"
+                "MergeBase/MergeBaseOf/Stash/Patch/Rebased should have been resolved before calling resolve_to_tree"
             )
         }
     }
 }
 
+/// A loaded side of a file (None if it doesn't exist there), and whether
+/// its content was read from the LFS store rather than the blob git diffs.
+type LoadedFile = (Option<File>, bool);
+
 /// Load file content from a git tree
 fn load_file_from_tree(
     repo: &Repository,
     tree: Option<&git2::Tree>,
     path: &Path,
-) -> Result<Option<File>, GitError> {
+) -> Result<LoadedFile, GitError> {
     let tree = match tree {
         Some(t) => t,
-        None => return Ok(None),
+        None => return Ok((None, false)),
     };
 
     let entry = match tree.get_path(path) {
         Ok(e) => e,
-        Err(_) => return Ok(None), // File doesn't exist in this tree
+        Err(_) => return Ok((None, false)), // File doesn't exist in this tree
     };
 
+    if entry.kind() == Some(git2::ObjectType::Commit) {
+        // Submodule: the entry points at a commit in the submodule's repo
+        return Ok((Some(submodule_file(path, entry.id(), false)), false));
+    }
+
     let obj = entry
         .to_object(repo)
         .map_err(|e| GitError::CommandFailed(format!("Cannot load object: {e}")))?;
 
     let blob = match obj.as_blob() {
         Some(b) => b,
-        None => return Ok(None), // Not a file (maybe a submodule)
+        None => return Ok((None, false)), // Not a file (e.g. a directory)
     };
 
-    let content = bytes_to_content(blob.content());
+    Ok(blob_to_file(repo, path, blob.content(), None))
+}
 
-    Ok(Some(File {
-        path: path.to_string_lossy().to_string(),
-        content,
-    }))
+/// Load file content from the index (stage 0)
+fn load_file_from_index(repo: &Repository, path: &Path) -> Result<LoadedFile, GitError> {
+    let index = repo
+        .index()
+        .map_err(|e| GitError::CommandFailed(format!("Cannot read index: {e}")))?;
+
+    let entry = match index.get_path(path, 0) {
+        Some(e) => e,
+        None => return Ok((None, false)), // Not staged
+    };
+
+    if entry.mode == GITLINK_MODE {
+        return Ok((Some(submodule_file(path, entry.id, false)), false));
+    }
+
+    let blob = match repo.find_blob(entry.id) {
+        Ok(b) => b,
+        Err(_) => return Ok((None, false)), // Not a file
+    };
+
+    Ok(blob_to_file(repo, path, blob.content(), None))
+}
+
+/// Raw content of one side of a file (None if it doesn't exist there).
+pub(super) type FileBytes = Option<Vec<u8>>;
+
+/// Raw bytes of both sides of a file, with Git LFS objects read in full
+/// from the local store. A side is None if the file doesn't exist there or
+/// isn't a blob (e.g. a submodule).
+pub(super) fn load_file_bytes(
+    repo_path: &Path,
+    spec: &DiffSpec,
+    path: &Path,
+) -> Result<(FileBytes, FileBytes), GitError> {
+    if let Some((base, head)) = spec.as_paths() {
+        return filesystem::load_file_bytes(base, head, path);
+    }
+
+    let spec = resolve_spec(repo_path, spec)?;
+    let repo = Repository::discover(repo_path).map_err(|e| GitError::NotARepo(e.to_string()))?;
+    Ok((
+        load_bytes(&repo, &spec.base, path)?,
+        load_bytes(&repo, &spec.head, path)?,
+    ))
+}
+
+fn load_bytes(repo: &Repository, git_ref: &GitRef, path: &Path) -> Result<FileBytes, GitError> {
+    let blob_id = match git_ref {
+        GitRef::WorkingTree => {
+            let workdir = repo
+                .workdir()
+                .ok_or_else(|| GitError::CommandFailed("Bare repository".into()))?;
+            let full_path = workdir.join(path);
+            if !full_path.is_file() {
+                return Ok(None);
+            }
+            let bytes = std::fs::read(&full_path)
+                .map_err(|e| GitError::CommandFailed(format!("Cannot read file: {e}")))?;
+            return Ok(Some(resolve_lfs_bytes(repo, bytes)));
+        }
+        GitRef::Index => {
+            let index = repo
+                .index()
+                .map_err(|e| GitError::CommandFailed(format!("Cannot read index: {e}")))?;
+            match index.get_path(path, 0) {
+                Some(entry) if entry.mode != GITLINK_MODE => entry.id,
+                _ => return Ok(None),
+            }
+        }
+        _ => {
+            let tree = resolve_to_tree(repo, git_ref)?;
+            match tree.as_ref().and_then(|t| t.get_path(path).ok()) {
+                Some(entry) if entry.kind() == Some(git2::ObjectType::Blob) => entry.id(),
+                _ => return Ok(None),
+            }
+        }
+    };
+
+    let blob = repo
+        .find_blob(blob_id)
+        .map_err(|e| GitError::CommandFailed(format!("Cannot load object: {e}")))?;
+    Ok(Some(resolve_lfs_bytes(repo, blob.content().to_vec())))
+}
+
+/// Replace LFS pointer bytes with the object's, when it's available locally.
+fn resolve_lfs_bytes(repo: &Repository, bytes: Vec<u8>) -> Vec<u8> {
+    LfsPointer::parse(&bytes)
+        .and_then(|pointer| pointer.read_full_object(&lfs::common_dir(repo.path())))
+        .unwrap_or(bytes)
+}
+
+/// Git file mode of submodule entries.
+const GITLINK_MODE: u32 = 0o160000;
+
+fn submodule_file(path: &Path, commit: git2::Oid, dirty: bool) -> File {
+    File::new(
+        path.to_string_lossy(),
+        FileContent::Submodule {
+            commit: commit.to_string(),
+            dirty,
+        },
+    )
+}
+
+/// Load a submodule's checked-out commit and dirty state.
+/// Returns None if `path` isn't a checked-out submodule.
+fn load_submodule_from_workdir(repo: &Repository, path: &Path) -> Result<Option<File>, GitError> {
+    let Some(name) = path.to_str() else {
+        return Ok(None);
+    };
+    let Ok(submodule) = repo.find_submodule(name) else {
+        return Ok(None);
+    };
+    let Some(commit) = submodule.workdir_id() else {
+        return Ok(None); // Not initialized
+    };
+
+    let status = repo
+        .submodule_status(
+            submodule.name().unwrap_or(name),
+            git2::SubmoduleIgnore::None,
+        )
+        .map_err(|e| GitError::CommandFailed(format!("Cannot get submodule status: {e}")))?;
+    let dirty = status.intersects(
+        git2::SubmoduleStatus::WD_INDEX_MODIFIED
+            | git2::SubmoduleStatus::WD_WD_MODIFIED
+            | git2::SubmoduleStatus::WD_UNTRACKED,
+    );
+
+    Ok(Some(submodule_file(path, commit, dirty)))
 }
 
 /// Load file content from the working directory
-fn load_file_from_workdir(repo: &Repository, path: &Path) -> Result<Option<File>, GitError> {
+fn load_file_from_workdir(repo: &Repository, path: &Path) -> Result<LoadedFile, GitError> {
     let workdir = repo
         .workdir()
         .ok_or_else(|| GitError::CommandFailed("Bare repository".into()))?;
     let full_path = workdir.join(path);
 
     if !full_path.exists() {
-        return Ok(None);
+        return Ok((None, false));
     }
 
-    // Skip directories (e.g., submodules)
+    // Directories are skipped, unless they're checked-out submodules
     if full_path.is_dir() {
-        return Ok(None);
+        return Ok((load_submodule_from_workdir(repo, path)?, false));
     }
 
     let bytes = std::fs::read(&full_path)
         .map_err(|e| GitError::CommandFailed(format!("Cannot read file: {e}")))?;
 
-    Ok(Some(File {
-        path: path.to_string_lossy().to_string(),
-        content: bytes_to_content(&bytes),
-    }))
+    // Git stores files with a working-tree-encoding as UTF-8, so the
+    // attribute only applies here
+    let declared = repo
+        .get_attr(
+            path,
+            "working-tree-encoding",
+            git2::AttrCheckFlags::FILE_THEN_INDEX,
+        )
+        .ok()
+        .flatten();
+
+    // LFS pointers end up in the working tree when smudging was skipped
+    Ok(blob_to_file(repo, path, &bytes, declared))
 }
 
-/// Convert raw bytes to FileContent, detecting binary
-fn bytes_to_content(bytes: &[u8]) -> FileContent {
-    // Check for binary: look for null bytes in first 8KB
-    let check_len = bytes.len().min(8192);
-    if bytes[..check_len].contains(&0) {
-        return FileContent::Binary;
-    }
+/// Build a File from blob bytes; see `lfs::load_file`.
+fn blob_to_file(
+    repo: &Repository,
+    path: &Path,
+    bytes: &[u8],
+    declared: Option<&str>,
+) -> LoadedFile {
+    let (file, from_lfs) = lfs::load_file(repo, path, bytes, declared);
+    (Some(file), from_lfs)
+}
 
-    // Parse as UTF-8 (lossy for display)
-    let text = String::from_utf8_lossy(bytes);
-    let lines: Vec<String> = text.lines().map(|s| s.to_string()).collect();
-    FileContent::Text { lines }
+/// Check for binary: look for null bytes in first 8KB
+fn is_binary(bytes: &[u8]) -> bool {
+    let check_len = bytes.len().min(8192);
+    bytes[..check_len].contains(&0)
 }
 
 /// Get hunks for a single file using libgit2
 fn get_hunks_libgit2(
     repo: &Repository,
+    spec: &DiffSpec,
     base_tree: Option<&git2::Tree>,
     head_tree: Option<&git2::Tree>,
-    is_working_tree: bool,
     path: &Path,
+    options: &DiffOptions,
 ) -> Result<Vec<Hunk>, GitError> {
-    let mut opts = DiffOptions::new();
-    opts.context_lines(0); // No context, just the changes
+    let mut opts = libgit2_options(options);
     opts.pathspec(path);
 
-    let diff = if is_working_tree {
-        repo.diff_tree_to_workdir_with_index(base_tree, Some(&mut opts))
-    } else {
-        repo.diff_tree_to_tree(base_tree, head_tree, Some(&mut opts))
+    let diff = match (&spec.base, &spec.head) {
+        (GitRef::Index, GitRef::WorkingTree) => repo.diff_index_to_workdir(None, Some(&mut opts)),
+        (_, GitRef::WorkingTree) => {
+            repo.diff_tree_to_workdir_with_index(base_tree, Some(&mut opts))
+        }
+        (_, GitRef::Index) => repo.diff_tree_to_index(base_tree, None, Some(&mut opts)),
+        _ => repo.diff_tree_to_tree(base_tree, head_tree, Some(&mut opts)),
     }
     .map_err(|e| GitError::CommandFailed(format!("Failed to compute diff: {e}")))?;
 
@@ -502,6 +1169,140 @@ fn get_hunks_libgit2(
     Ok(hunks.into_inner())
 }
 
+/// libgit2 options for a zero-context diff honoring `options`.
+fn libgit2_options(options: &DiffOptions) -> git2::DiffOptions {
+    let mut opts = git2::DiffOptions::new();
+    opts.context_lines(0); // No context, just the changes
+    match options.whitespace {
+        WhitespaceMode::None => {}
+        WhitespaceMode::IgnoreAll => {
+            opts.ignore_whitespace(true);
+        }
+        WhitespaceMode::IgnoreChange => {
+            opts.ignore_whitespace_change(true);
+        }
+        WhitespaceMode::IgnoreEol => {
+            opts.ignore_whitespace_eol(true);
+        }
+    }
+    opts.ignore_blank_lines(options.ignore_blank_lines);
+    opts.patience(options.algorithm == DiffAlgorithm::Patience);
+    opts.minimal(options.algorithm == DiffAlgorithm::Minimal);
+    opts
+}
+
+/// Align two texts that aren't files in the repo (e.g. notebook cells).
+pub(super) fn align_texts(
+    before: &Option<File>,
+    after: &Option<File>,
+    options: &DiffOptions,
+) -> Result<Vec<Alignment>, GitError> {
+    let hunks = text_hunks(before, after, options)?;
+    Ok(compute_alignments_from_hunks(&hunks, before, after))
+}
+
+/// Hunks between the lines of two loaded files, diffed in memory rather
+/// than from the blobs git stores. Empty unless both sides are text.
+fn text_hunks(
+    before: &Option<File>,
+    after: &Option<File>,
+    options: &DiffOptions,
+) -> Result<Vec<Hunk>, GitError> {
+    let text = |file: &Option<File>| match file {
+        Some(File {
+            content: FileContent::Text { lines },
+            ..
+        }) => Some(lines.iter().map(|l| format!("{l}\n")).collect::<String>()),
+        _ => None,
+    };
+
+    let mut hunks = Vec::new();
+    if let (Some(old), Some(new)) = (text(before), text(after)) {
+        let mut opts = libgit2_options(options);
+        let patch =
+            git2::Patch::from_buffers(old.as_bytes(), None, new.as_bytes(), None, Some(&mut opts))
+                .map_err(|e| GitError::CommandFailed(format!("Failed to compute diff: {e}")))?;
+        for i in 0..patch.num_hunks() {
+            let (hunk, _) = patch
+                .hunk(i)
+                .map_err(|e| GitError::CommandFailed(format!("Failed to read hunk: {e}")))?;
+            hunks.push(Hunk {
+                old_start: hunk.old_start().saturating_sub(1),
+                old_lines: hunk.old_lines(),
+                new_start: hunk.new_start().saturating_sub(1),
+                new_lines: hunk.new_lines(),
+            });
+        }
+    }
+    Ok(hunks)
+}
+
+/// Get hunks for a single file from `git diff -U0` output.
+/// Used for options libgit2 doesn't support (the histogram algorithm).
+fn get_hunks_cli(
+    repo: &Path,
+    spec: &DiffSpec,
+    path: &Path,
+    options: &DiffOptions,
+) -> Result<Vec<Hunk>, GitError> {
+    let options = DiffOptions {
+        context_lines: Some(0),
+        ..options.clone()
+    };
+    let output = get_unified_diff(repo, spec, path, &options)?;
+
+    Ok(output
+        .lines()
+        .filter_map(parse_hunk_header)
+        .map(|(old_start, old_lines, new_start, new_lines)| Hunk {
+            old_start: old_start.saturating_sub(1),
+            old_lines,
+            new_start: new_start.saturating_sub(1),
+            new_lines,
+        })
+        .collect())
+}
+
+/// Parse a `@@ -a,b +c,d @@` header into (old_start, old_lines, new_start, new_lines).
+/// Starts are 1-indexed as in the diff; omitted counts default to 1.
+pub(super) fn parse_hunk_header(line: &str) -> Option<(u32, u32, u32, u32)> {
+    let inner = line.strip_prefix("@@ ")?;
+    let end = inner.find(" @@")?;
+    let mut parts = inner[..end].split(' ');
+    let old = parts.next()?.strip_prefix('-')?;
+    let new = parts.next()?.strip_prefix('+')?;
+
+    let parse_range = |s: &str| -> Option<(u32, u32)> {
+        match s.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((s.parse().ok()?, 1)),
+        }
+    };
+
+    let (old_start, old_lines) = parse_range(old)?;
+    let (new_start, new_lines) = parse_range(new)?;
+    Some((old_start, old_lines, new_start, new_lines))
+}
+
+/// Alignments for hunks given as 0-indexed (old_start, old_lines,
+/// new_start, new_lines), e.g. parsed from a patch file.
+pub(super) fn align_hunks(
+    hunks: &[(u32, u32, u32, u32)],
+    before: &Option<File>,
+    after: &Option<File>,
+) -> Vec<Alignment> {
+    let hunks: Vec<Hunk> = hunks
+        .iter()
+        .map(|&(old_start, old_lines, new_start, new_lines)| Hunk {
+            old_start,
+            old_lines,
+            new_start,
+            new_lines,
+        })
+        .collect();
+    compute_alignments_from_hunks(&hunks, before, after)
+}
+
 /// Compute alignments from git hunks.
 /// This uses git's authoritative diff output rather than recomputing.
 fn compute_alignments_from_hunks(
@@ -659,6 +1460,327 @@ mod tests {
         assert_eq!(result.len(), 3);
     }
 
+    #[test]
+    fn test_parse_hunk_header() {
+        assert_eq!(parse_hunk_header("@@ -2 +2 @@"), Some((2, 1, 2, 1)));
+        assert_eq!(
+            parse_hunk_header("@@ -5,0 +6,2 @@ fn x"),
+            Some((5, 0, 6, 2))
+        );
+        assert_eq!(parse_hunk_header("not a header"), None);
+    }
+
+    fn init_repo_with_commit(repo: &Path, file: &str, content: &str) {
+        for args in [
+            vec!["init"],
+            vec!["config", "user.email", "test@example.com"],
+            vec!["config", "user.name", "Test"],
+        ] {
+            std::process::Command::new("git")
+                .args(&args)
+                .current_dir(repo)
+                .output()
+                .unwrap();
+        }
+        std::fs::write(repo.join(file), content).unwrap();
+        for args in [vec!["add", "."], vec!["commit", "-m", "init"]] {
+            std::process::Command::new("git")
+                .args(&args)
+                .current_dir(repo)
+                .output()
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_file_diff_ignore_whitespace() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        init_repo_with_commit(repo, "f.rs", "fn a() {\nx();\n}\n");
+        std::fs::write(repo.join("f.rs"), "fn a() {\n    x();\n}\n").unwrap();
+
+        let path = Path::new("f.rs");
+        let spec = DiffSpec::uncommitted();
+
+        let diff = get_file_diff(repo, &spec, path, &DiffOptions::default()).unwrap();
+        assert!(diff.alignments.iter().any(|a| a.changed));
+
+        for algorithm in [DiffAlgorithm::Myers, DiffAlgorithm::Histogram] {
+            let options = DiffOptions {
+                whitespace: WhitespaceMode::IgnoreAll,
+                algorithm,
+                ..Default::default()
+            };
+            let diff = get_file_diff(repo, &spec, path, &options).unwrap();
+            assert!(!diff.alignments.iter().any(|a| a.changed));
+        }
+    }
+
+    #[test]
+    fn test_staged_and_unstaged_specs() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        init_repo_with_commit(repo, "f.txt", "one\n");
+
+        std::fs::write(repo.join("f.txt"), "two\n").unwrap();
+        std::process::Command::new("git")
+            .args(["add", "f.txt"])
+            .current_dir(repo)
+            .output()
+            .unwrap();
+        std::fs::write(repo.join("f.txt"), "three\n").unwrap();
+
+        let path = Path::new("f.txt");
+        let options = DiffOptions::default();
+        let text = |f: &Option<File>| match f {
+            Some(File {
+                content: FileContent::Text { lines },
+                ..
+            }) => lines.join("\n"),
+            _ => String::new(),
+        };
+
+        let staged = get_file_diff(repo, &DiffSpec::staged(), path, &options).unwrap();
+        assert_eq!(text(&staged.before), "one");
+        assert_eq!(text(&staged.after), "two");
+
+        let unstaged = get_file_diff(repo, &DiffSpec::unstaged(), path, &options).unwrap();
+        assert_eq!(text(&unstaged.before), "two");
+        assert_eq!(text(&unstaged.after), "three");
+
+        let files = list_diff_files(repo, &DiffSpec::staged(), &options).unwrap();
+        assert_eq!(files.len(), 1);
+        let unified = get_unified_diff(repo, &DiffSpec::unstaged(), path, &options).unwrap();
+        assert!(unified.contains("-two") && unified.contains("+three"));
+
+        let bad = DiffSpec::custom(GitRef::Index, GitRef::Rev("HEAD".into()));
+        assert!(get_file_diff(repo, &bad, path, &options).is_err());
+    }
+
+    #[test]
+    fn test_line_ending_only_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        init_repo_with_commit(repo, "f.txt", "one\ntwo\n");
+        std::fs::write(repo.join("f.txt"), "one\r\ntwo\r\n").unwrap();
+
+        let diff = get_file_diff(
+            repo,
+            &DiffSpec::uncommitted(),
+            Path::new("f.txt"),
+            &DiffOptions::default(),
+        )
+        .unwrap();
+        assert!(!diff.alignments.iter().any(|a| a.changed));
+        assert_eq!(diff.before.unwrap().eol, Some(LineEnding::Lf));
+        let after = diff.after.unwrap();
+        assert_eq!(after.eol, Some(LineEnding::Crlf));
+        assert_eq!(after.encoding.unwrap().name, "UTF-8");
+    }
+
+    #[test]
+    fn test_utf16_and_cr_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        let utf16 = |text: &str| -> Vec<u8> {
+            [0xff, 0xfe]
+                .into_iter()
+                .chain(text.encode_utf16().flat_map(|u| u.to_le_bytes()))
+                .collect()
+        };
+        init_repo_with_commit(repo, "cr.txt", "a\rb\rc\r");
+        std::fs::write(repo.join("wide.txt"), utf16("a\nb\nc\n")).unwrap();
+        for args in [vec!["add", "."], vec!["commit", "-m", "wide"]] {
+            std::process::Command::new("git")
+                .args(&args)
+                .current_dir(repo)
+                .output()
+                .unwrap();
+        }
+        std::fs::write(repo.join("wide.txt"), utf16("a\nB\nc\n")).unwrap();
+        std::fs::write(repo.join("cr.txt"), "a\rB\rc\r").unwrap();
+
+        for file in ["wide.txt", "cr.txt"] {
+            let diff = get_file_diff(
+                repo,
+                &DiffSpec::uncommitted(),
+                Path::new(file),
+                &DiffOptions::default(),
+            )
+            .unwrap();
+            let changed: Vec<_> = diff
+                .alignments
+                .iter()
+                .filter(|a| a.changed)
+                .map(|a| (a.before, a.after))
+                .collect();
+            assert_eq!(changed, vec![(Span::new(1, 2), Span::new(1, 2))], "{file}");
+        }
+    }
+
+    #[test]
+    fn test_diff_attributes() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        init_repo_with_commit(repo, "a.up", "one\ntwo\n");
+        std::process::Command::new("git")
+            .args(["config", "diff.upper.textconv", "tr a-z A-Z <"])
+            .current_dir(repo)
+            .output()
+            .unwrap();
+        std::fs::write(repo.join("a.up"), "one\nthree\n").unwrap();
+        let path = Path::new("a.up");
+        let spec = DiffSpec::uncommitted();
+        let text = |f: Option<File>| match f.map(|f| f.content) {
+            Some(FileContent::Text { lines }) => lines.join("\n"),
+            other => panic!("expected text, got {other:?}"),
+        };
+
+        std::fs::write(repo.join(".gitattributes"), "*.up diff=upper\n").unwrap();
+        let diff = get_file_diff(repo, &spec, path, &DiffOptions::default()).unwrap();
+        assert_eq!(text(diff.before), "ONE\nTWO");
+        assert_eq!(text(diff.after), "ONE\nTHREE");
+        assert_eq!(diff.alignments.iter().filter(|a| a.changed).count(), 1);
+
+        std::fs::write(repo.join(".gitattributes"), "*.up -diff\n").unwrap();
+        let diff = get_file_diff(repo, &spec, path, &DiffOptions::default()).unwrap();
+        assert!(matches!(
+            diff.after.unwrap().content,
+            FileContent::Binary(info) if info.size == 10
+        ));
+        assert!(diff.alignments.is_empty());
+    }
+
+    #[test]
+    fn test_lfs_pointer_resolution() {
+        let oid = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        let pointer =
+            format!("version https://git-lfs.github.com/spec/v1\noid sha256:{oid}\nsize 5\n");
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        init_repo_with_commit(repo, "asset.txt", &pointer);
+
+        let path = Path::new("asset.txt");
+        let spec = DiffSpec::custom(GitRef::Rev("HEAD".into()), GitRef::WorkingTree);
+        let diff = get_file_diff(repo, &spec, path, &DiffOptions::default()).unwrap();
+        assert_eq!(
+            diff.before.unwrap().content,
+            FileContent::LfsPointer {
+                oid: oid.to_string(),
+                size: 5
+            }
+        );
+
+        let object_dir = repo
+            .join(".git/lfs/objects")
+            .join(&oid[0..2])
+            .join(&oid[2..4]);
+        std::fs::create_dir_all(&object_dir).unwrap();
+        std::fs::write(object_dir.join(oid), "hello").unwrap();
+        let diff = get_file_diff(repo, &spec, path, &DiffOptions::default()).unwrap();
+        assert_eq!(
+            diff.before.unwrap().content,
+            FileContent::Text {
+                lines: vec!["hello".to_string()]
+            }
+        );
+    }
+
+    #[test]
+    fn test_lfs_object_alignments() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        let lines: Vec<String> = (1..=10).map(|i| format!("line {i}")).collect();
+        let old = lines.join("\n") + "\n";
+        let mut new_lines = lines.clone();
+        new_lines[6] = "line seven".to_string();
+        let new = new_lines.join("\n") + "\n";
+
+        // Store both objects and commit their pointers, as git-lfs would
+        // (the store is keyed by oid; contents aren't re-hashed)
+        let mut pointers = Vec::new();
+        for (content, oid) in [(&old, "a".repeat(64)), (&new, "b".repeat(64))] {
+            let object_dir = repo
+                .join(".git/lfs/objects")
+                .join(&oid[0..2])
+                .join(&oid[2..4]);
+            std::fs::create_dir_all(&object_dir).unwrap();
+            std::fs::write(object_dir.join(&oid), content).unwrap();
+            pointers.push(format!(
+                "version https://git-lfs.github.com/spec/v1\noid sha256:{oid}\nsize {}\n",
+                content.len()
+            ));
+        }
+        init_repo_with_commit(repo, "data.txt", &pointers[0]);
+        std::fs::write(repo.join("data.txt"), &pointers[1]).unwrap();
+
+        let diff = get_file_diff(
+            repo,
+            &DiffSpec::uncommitted(),
+            Path::new("data.txt"),
+            &DiffOptions::default(),
+        )
+        .unwrap();
+        let changed: Vec<_> = diff
+            .alignments
+            .iter()
+            .filter(|a| a.changed)
+            .map(|a| (a.before, a.after))
+            .collect();
+        assert_eq!(changed, vec![(Span::new(6, 7), Span::new(6, 7))]);
+    }
+
+    #[test]
+    fn test_parse_raw_numstat() {
+        let output = ":100644 100755 aaa bbb M\0run.sh\0\
+                      :100644 100644 ccc ddd R090\0old.rs\0new.rs\0\
+                      :000000 100644 000 eee A\0logo.png\0\
+                      3\t1\trun.sh\0\
+                      2\t2\t\0old.rs\0new.rs\0\
+                      -\t-\tlogo.png\0";
+        let stats = parse_raw_numstat(output);
+
+        let run = &stats[Path::new("run.sh")];
+        assert_eq!((run.additions, run.deletions), (Some(3), Some(1)));
+        assert_eq!(run.old_mode, Some(FileMode::Regular));
+        assert_eq!(run.new_mode, Some(FileMode::Executable));
+
+        let renamed = &stats[Path::new("new.rs")];
+        assert_eq!(renamed.additions, Some(2));
+        assert!(!stats.contains_key(Path::new("old.rs")));
+
+        let logo = &stats[Path::new("logo.png")];
+        assert!(logo.binary);
+        assert_eq!(logo.additions, None);
+        assert_eq!(logo.old_mode, None);
+    }
+
+    #[test]
+    fn test_list_diff_files_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        init_repo_with_commit(repo, "f.txt", "a\nb\nc\n");
+
+        std::fs::write(repo.join("f.txt"), "a\nB\nc\nd\n").unwrap();
+        std::fs::write(repo.join("new.txt"), "x\ny").unwrap();
+
+        let files =
+            list_diff_files(repo, &DiffSpec::uncommitted(), &DiffOptions::default()).unwrap();
+        let find = |name: &str| files.iter().find(|f| f.path() == Path::new(name)).unwrap();
+
+        let modified = find("f.txt");
+        assert_eq!((modified.additions, modified.deletions), (Some(2), Some(1)));
+        assert_eq!(modified.new_mode, Some(FileMode::Regular));
+        assert!(!modified.is_mode_changed());
+
+        let untracked = find("new.txt");
+        assert_eq!(
+            (untracked.additions, untracked.deletions),
+            (Some(2), Some(0))
+        );
+        assert!(!untracked.binary);
+    }
+
     #[test]
     fn test_parse_porcelain_untracked() {
         let dir = tempfile::tempdir().unwrap();
@@ -0,0 +1,202 @@
+//! Text encoding and line-ending detection for loaded files.
+//!
+//! Files are decoded by, in order: byte-order mark, the `working-tree-encoding`
+//! attribute (working tree only; git stores such files as UTF-8), a UTF-16
+//! heuristic for BOM-less files, then UTF-8 with a Windows-1252 fallback.
+//! Anything else containing null bytes is binary. A BOM only counts if the
+//! rest of the file decodes cleanly, since binary files can start with one.
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+use super::binary;
+use super::types::{File, FileContent, LineEnding, TextEncoding};
+
+/// How much of a file is sampled for the binary and UTF-16 checks.
+const SAMPLE_LEN: usize = 8192;
+
+/// Build a File from raw bytes, detecting binary content, encoding and line
+/// endings. `declared` is the `working-tree-encoding` attribute, if any.
+pub(super) fn decode_file(path: String, bytes: &[u8], declared: Option<&str>) -> File {
+    let Some((encoding, bom_len)) = detect(bytes, declared) else {
+        let info = binary::binary_info(&path, bytes);
+        return File::new(path, FileContent::Binary(info));
+    };
+
+    let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+    let eol = detect_eol(&text);
+    let lines = match eol {
+        // str::lines only splits on \n
+        Some(LineEnding::Cr) => text
+            .strip_suffix('\r')
+            .unwrap_or(&text)
+            .split('\r')
+            .map(|s| s.to_string())
+            .collect(),
+        _ => text.lines().map(|s| s.to_string()).collect(),
+    };
+
+    File {
+        path,
+        content: FileContent::Text { lines },
+        encoding: Some(TextEncoding {
+            name: encoding.name().to_string(),
+            bom: bom_len > 0,
+        }),
+        eol,
+    }
+}
+
+/// Pick an encoding and the length of its BOM; None means binary.
+fn detect(bytes: &[u8], declared: Option<&str>) -> Option<(&'static Encoding, usize)> {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        let text = encoding.decode_without_bom_handling_and_without_replacement(&bytes[bom_len..]);
+        if text.is_some_and(|text| !text.contains('\0')) {
+            return Some((encoding, bom_len));
+        }
+    }
+    if let Some(encoding) = declared.and_then(|label| Encoding::for_label(label.as_bytes())) {
+        return Some((encoding, 0));
+    }
+
+    let sample = &bytes[..bytes.len().min(SAMPLE_LEN)];
+    if let Some(encoding) = guess_utf16(sample) {
+        return Some((encoding, 0));
+    }
+    if sample.contains(&0) {
+        return None;
+    }
+
+    if std::str::from_utf8(bytes).is_ok() {
+        Some((UTF_8, 0))
+    } else {
+        // Latin-1 and friends: every byte decodes to something sensible
+        Some((WINDOWS_1252, 0))
+    }
+}
+
+/// Recognize BOM-less UTF-16 by its null bytes: mostly-ASCII text has a null
+/// in (nearly) every other byte, always on the same side of each code unit.
+fn guess_utf16(sample: &[u8]) -> Option<&'static Encoding> {
+    // The sample is an even-length prefix or the whole file
+    let pairs = sample.chunks_exact(2);
+    if sample.len() < 2 || !pairs.remainder().is_empty() {
+        return None;
+    }
+    let units = sample.len() / 2;
+    let (mut even_nulls, mut odd_nulls) = (0, 0);
+    for pair in pairs {
+        even_nulls += usize::from(pair[0] == 0);
+        odd_nulls += usize::from(pair[1] == 0);
+    }
+
+    if even_nulls == 0 && odd_nulls * 4 >= units * 3 {
+        Some(UTF_16LE)
+    } else if odd_nulls == 0 && even_nulls * 4 >= units * 3 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Classify the line endings used in `text`.
+fn detect_eol(text: &str) -> Option<LineEnding> {
+    let (mut crlf, mut lf, mut cr) = (0, 0, 0);
+    let mut bytes = text.bytes().peekable();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'\r' if bytes.peek() == Some(&b'\n') => {
+                bytes.next();
+                crlf += 1;
+            }
+            b'\r' => cr += 1,
+            b'\n' => lf += 1,
+            _ => {}
+        }
+    }
+
+    match (crlf > 0, lf > 0, cr > 0) {
+        (false, false, false) => None,
+        (true, false, false) => Some(LineEnding::Crlf),
+        (false, true, false) => Some(LineEnding::Lf),
+        (false, false, true) => Some(LineEnding::Cr),
+        _ => Some(LineEnding::Mixed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(file: &File) -> Vec<&str> {
+        match &file.content {
+            FileContent::Text { lines } => lines.iter().map(|s| s.as_str()).collect(),
+            _ => panic!("expected text"),
+        }
+    }
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(|u| u.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_decode_encodings() {
+        let file = decode_file("a".into(), "héllo\nworld\n".as_bytes(), None);
+        assert_eq!(lines(&file), vec!["héllo", "world"]);
+        assert_eq!(file.encoding.as_ref().unwrap().name, "UTF-8");
+        assert_eq!(file.eol, Some(LineEnding::Lf));
+
+        // UTF-16LE with and without a BOM
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(utf16le("héllo\r\nworld\r\n"));
+        let file = decode_file("a".into(), &bytes, None);
+        assert_eq!(lines(&file), vec!["héllo", "world"]);
+        let encoding = file.encoding.unwrap();
+        assert_eq!(encoding.name, "UTF-16LE");
+        assert!(encoding.bom);
+        assert_eq!(file.eol, Some(LineEnding::Crlf));
+
+        let file = decode_file("a".into(), &utf16le("plain\n"), None);
+        assert_eq!(lines(&file), vec!["plain"]);
+        assert!(!file.encoding.unwrap().bom);
+
+        // Latin-1 falls back to Windows-1252 instead of replacement characters
+        let file = decode_file("a".into(), b"caf\xe9\n", None);
+        assert_eq!(lines(&file), vec!["café"]);
+        assert_eq!(file.encoding.unwrap().name, "windows-1252");
+
+        let file = decode_file("a".into(), b"\x00\x01\x02\x03\x00\xff", None);
+        assert!(matches!(file.content, FileContent::Binary(_)));
+        assert!(file.encoding.is_none());
+
+        // Binary data that happens to start with a BOM
+        for bytes in [
+            &b"\xFF\xFE\x00\xD8\x41\x00"[..],
+            &b"\xEF\xBB\xBF\x00\x01\x02"[..],
+        ] {
+            let file = decode_file("a".into(), bytes, None);
+            assert!(matches!(file.content, FileContent::Binary(_)));
+        }
+    }
+
+    #[test]
+    fn test_declared_encoding() {
+        let bytes = "x\ny\n"
+            .encode_utf16()
+            .flat_map(|u| u.to_be_bytes())
+            .collect::<Vec<_>>();
+        let file = decode_file("a".into(), &bytes, Some("UTF-16BE"));
+        assert_eq!(lines(&file), vec!["x", "y"]);
+        assert_eq!(file.encoding.unwrap().name, "UTF-16BE");
+    }
+
+    #[test]
+    fn test_detect_eol() {
+        assert_eq!(detect_eol("a"), None);
+        assert_eq!(detect_eol("a\r\nb\r\n"), Some(LineEnding::Crlf));
+        assert_eq!(detect_eol("a\rb\r"), Some(LineEnding::Cr));
+        assert_eq!(detect_eol("a\r\nb\n"), Some(LineEnding::Mixed));
+
+        let file = decode_file("a".into(), b"a\rb\r", None);
+        assert_eq!(lines(&file), vec!["a", "b"]);
+    }
+}
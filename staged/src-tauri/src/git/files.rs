@@ -2,11 +2,15 @@
 //!
 //! This module provides:
 //! - `search_files`: Fuzzy search for files in a git tree
+//! - `search_content`: Search file contents at a ref with `git grep`
 //! - `get_file_at_ref`: Load file content at a specific ref
 
+use git2::Repository;
 use std::path::Path;
 
 use super::cli::{self, GitError};
+use super::encoding;
+use super::lfs;
 use super::types::{File, FileContent, WORKDIR};
 
 /// Search for files matching a query in the repository at a given ref.
@@ -126,6 +130,145 @@ fn fuzzy_match(path: &str, query_lower: &str) -> Option<MatchScore> {
     })
 }
 
+/// Options for content search.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ContentSearchOptions {
+    /// Treat the query as an extended regex instead of a fixed string
+    pub regex: bool,
+    /// Match case-insensitively
+    pub ignore_case: bool,
+    /// Only search paths matching at least one of these globs (e.g. "src/**/*.rs")
+    pub globs: Vec<String>,
+    /// Lines of context to return before and after each match
+    pub context_lines: u32,
+}
+
+/// A line matching a content search.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentMatch {
+    pub path: String,
+    /// Line number (0-indexed)
+    pub line: u32,
+    pub text: String,
+    /// Up to `context_lines` lines immediately before the match
+    pub context_before: Vec<String>,
+    /// Up to `context_lines` lines immediately after the match
+    pub context_after: Vec<String>,
+}
+
+/// Search file contents at a ref using `git grep`.
+///
+/// For WORKDIR, searches the working tree, including untracked files that
+/// are not ignored. Binary files are skipped. Returns up to `limit` matches,
+/// ordered by path then line; git is stopped once that many are found.
+/// Lines that aren't UTF-8 are decoded lossily.
+pub fn search_content(
+    repo: &Path,
+    ref_name: &str,
+    query: &str,
+    options: &ContentSearchOptions,
+    limit: usize,
+) -> Result<Vec<ContentMatch>, GitError> {
+    if limit == 0 {
+        return Ok(vec![]);
+    }
+    let max_count = format!("--max-count={limit}");
+    let mut args = vec![
+        "grep",
+        "--null",
+        "--line-number",
+        "--no-color",
+        "-I",
+        &max_count,
+    ];
+    args.push(if options.regex { "-E" } else { "-F" });
+    if options.ignore_case {
+        args.push("-i");
+    }
+    if ref_name == WORKDIR {
+        args.push("--untracked");
+    }
+    args.extend(["-e", query]);
+    if ref_name != WORKDIR {
+        args.push(ref_name);
+    }
+    args.push("--");
+    let pathspecs: Vec<String> = options
+        .globs
+        .iter()
+        .map(|g| format!(":(glob){g}"))
+        .collect();
+    args.extend(pathspecs.iter().map(String::as_str));
+
+    // With a tree-ish, git grep prefixes paths with "<ref>:"
+    let prefix = if ref_name == WORKDIR {
+        String::new()
+    } else {
+        format!("{ref_name}:")
+    };
+    let mut matches = Vec::new();
+    let searched = cli::run_lines(repo, &args, |raw| {
+        matches.extend(parse_grep_line(&String::from_utf8_lossy(raw), &prefix));
+        matches.len() < limit
+    });
+    match searched {
+        Ok(()) => {}
+        // git grep exits with status 1 and no stderr when nothing matches
+        Err(GitError::CommandFailed(msg)) if msg.trim().is_empty() => return Ok(vec![]),
+        Err(e) => return Err(e),
+    }
+
+    // git grep's context output can't be parsed unambiguously (its separators
+    // may appear in paths), so read context from the matched files instead.
+    if options.context_lines > 0 {
+        add_context(repo, ref_name, &mut matches, options.context_lines as usize);
+    }
+
+    Ok(matches)
+}
+
+/// Parse a line of `git grep --null --line-number` output (`<path>\0<line>\0<text>`).
+fn parse_grep_line(raw: &str, prefix: &str) -> Option<ContentMatch> {
+    let mut parts = raw.splitn(3, '\0');
+    let path = parts.next()?;
+    let line_number: u32 = parts.next()?.parse().ok()?;
+    let text = parts.next()?;
+    Some(ContentMatch {
+        path: path.strip_prefix(prefix).unwrap_or(path).to_string(),
+        line: line_number.saturating_sub(1),
+        text: text.trim_end_matches('\r').to_string(),
+        context_before: Vec::new(),
+        context_after: Vec::new(),
+    })
+}
+
+/// Fill in `context_before`/`context_after`, loading each matched file once.
+/// Files that can no longer be read keep empty context.
+fn add_context(repo: &Path, ref_name: &str, matches: &mut [ContentMatch], context_lines: usize) {
+    // Matches are grouped by path, so each chunk is one file
+    for file_matches in matches.chunk_by_mut(|a, b| a.path == b.path) {
+        let lines = match get_file_at_ref(repo, ref_name, &file_matches[0].path) {
+            Ok(File {
+                content: FileContent::Text { lines },
+                ..
+            }) => lines,
+            _ => continue,
+        };
+        for m in file_matches {
+            let line = m.line as usize;
+            if line >= lines.len() {
+                continue;
+            }
+            let start = line.saturating_sub(context_lines);
+            let end = (line + 1 + context_lines).min(lines.len());
+            m.context_before = lines[start..line].to_vec();
+            m.context_after = lines[line + 1..end].to_vec();
+        }
+    }
+}
+
 /// Get the content of a file at a specific ref.
 ///
 /// For WORKDIR, reads from the working directory.
@@ -148,52 +291,40 @@ pub fn get_file_at_ref(repo: &Path, ref_name: &str, path: &str) -> Result<File,
         let bytes = std::fs::read(&full_path)
             .map_err(|e| GitError::CommandFailed(format!("Cannot read file: {e}")))?;
 
-        let content = if is_binary(&bytes) {
-            FileContent::Binary
-        } else {
-            let text = String::from_utf8_lossy(&bytes);
-            text_to_content(&text)
-        };
-
-        Ok(File {
-            path: path.to_string(),
-            content,
-        })
+        let declared = working_tree_encoding(repo, path);
+        Ok(load_file(repo, path, &bytes, declared.as_deref()))
     } else {
         // Read from git tree: git show <ref>:<path>
         let spec = format!("{ref_name}:{path}");
-        let output = cli::run(repo, &["show", &spec]).map_err(|e| match e {
+        let output = cli::run_bytes(repo, &["show", &spec]).map_err(|e| match e {
             GitError::CommandFailed(msg) if msg.contains("does not exist") => {
                 GitError::CommandFailed(format!("File not found: {path}"))
             }
             other => other,
         })?;
 
-        // git show returns text, check if it looks binary
-        let bytes = output.as_bytes();
-        let content = if is_binary(bytes) {
-            FileContent::Binary
-        } else {
-            text_to_content(&output)
-        };
-
-        Ok(File {
-            path: path.to_string(),
-            content,
-        })
+        // Blobs of files with a working-tree-encoding are stored as UTF-8
+        Ok(load_file(repo, path, &output, None))
     }
 }
 
-/// Check if data appears to be binary (contains null bytes in first 8KB)
-fn is_binary(data: &[u8]) -> bool {
-    let check_len = data.len().min(8192);
-    data[..check_len].contains(&0)
+/// Build a File from raw bytes; see `lfs::load_file`.
+fn load_file(repo: &Path, path: &str, bytes: &[u8], declared: Option<&str>) -> File {
+    match Repository::discover(repo) {
+        Ok(repo) => lfs::load_file(&repo, Path::new(path), bytes, declared).0,
+        Err(_) => encoding::decode_file(path.to_string(), bytes, declared),
+    }
 }
 
-/// Convert text to FileContent with lines
-fn text_to_content(text: &str) -> FileContent {
-    let lines: Vec<String> = text.lines().map(|s| s.to_string()).collect();
-    FileContent::Text { lines }
+/// The `working-tree-encoding` attribute of `path`, if set.
+fn working_tree_encoding(repo: &Path, path: &str) -> Option<String> {
+    // Output: "<path>: working-tree-encoding: <value>"
+    let output = cli::run(repo, &["check-attr", "working-tree-encoding", "--", path]).ok()?;
+    let value = output.trim_end().rsplit(": ").next()?;
+    match value {
+        "unspecified" | "unset" | "set" => None,
+        _ => Some(value.to_string()),
+    }
 }
 
 #[cfg(test)]
@@ -232,4 +363,98 @@ mod tests {
         // Empty query matches everything
         assert!(fuzzy_match("any/path.rs", "").is_some());
     }
+
+    #[test]
+    fn test_parse_grep_line() {
+        let first = parse_grep_line("HEAD:a-b:c.rs\x002\x00    foo();", "HEAD:").unwrap();
+        assert_eq!(first.path, "a-b:c.rs");
+        assert_eq!(first.line, 1);
+        assert_eq!(first.text, "    foo();");
+
+        let second = parse_grep_line("HEAD:a-b:c.rs\x0010\x00x:1-2", "HEAD:").unwrap();
+        assert_eq!(second.line, 9);
+        assert_eq!(second.text, "x:1-2");
+
+        assert!(parse_grep_line("Binary file matches", "HEAD:").is_none());
+    }
+
+    #[test]
+    fn test_search_content_workdir_and_ref() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .args(args)
+                .current_dir(repo)
+                .output()
+                .unwrap()
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "test@test.com"]);
+        git(&["config", "user.name", "Test"]);
+        std::fs::write(repo.join("lib.rs"), "// parser\nfn Parse() {}\n}\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-m", "initial"]);
+        std::fs::write(repo.join("new.ts"), "parse();\n").unwrap();
+
+        let options = ContentSearchOptions {
+            ignore_case: true,
+            ..Default::default()
+        };
+        let at_head = search_content(repo, "HEAD", "parse()", &options, 10).unwrap();
+        assert_eq!(at_head.len(), 1);
+        assert_eq!(at_head[0].path, "lib.rs");
+        assert_eq!(at_head[0].line, 1);
+
+        let options = ContentSearchOptions {
+            ignore_case: true,
+            context_lines: 1,
+            ..Default::default()
+        };
+        let in_workdir = search_content(repo, WORKDIR, "parse()", &options, 10).unwrap();
+        assert_eq!(in_workdir.len(), 2);
+        assert_eq!(in_workdir[0].context_before, vec!["// parser"]);
+        assert_eq!(in_workdir[0].context_after, vec!["}"]);
+
+        let options = ContentSearchOptions {
+            regex: true,
+            globs: vec!["*.ts".to_string()],
+            ..Default::default()
+        };
+        let globbed = search_content(repo, WORKDIR, "^pa.se", &options, 10).unwrap();
+        assert_eq!(globbed.len(), 1);
+        assert_eq!(globbed[0].path, "new.ts");
+
+        let none = search_content(repo, "HEAD", "nothing here", &options, 10).unwrap();
+        assert!(none.is_empty());
+
+        // Latin-1 lines don't fail the search, and the limit stops it early
+        std::fs::write(repo.join("latin1.txt"), b"caf\xe9 parse\nparse\nparse\n").unwrap();
+        let options = ContentSearchOptions {
+            globs: vec!["*.txt".to_string()],
+            ..Default::default()
+        };
+        let latin1 = search_content(repo, WORKDIR, "parse", &options, 2).unwrap();
+        assert_eq!(latin1.len(), 2);
+        assert_eq!(latin1[0].text, "caf\u{fffd} parse");
+    }
+
+    #[test]
+    fn test_get_file_at_ref_honors_diff_attribute() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        let git = |args: &[&str]| cli::run(repo, args).unwrap();
+        git(&["init"]);
+        git(&["config", "user.email", "test@test.com"]);
+        git(&["config", "user.name", "Test"]);
+        std::fs::write(repo.join(".gitattributes"), "*.dat -diff\n").unwrap();
+        std::fs::write(repo.join("table.dat"), "plain text\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-m", "initial"]);
+
+        for ref_name in ["HEAD", WORKDIR] {
+            let file = get_file_at_ref(repo, ref_name, "table.dat").unwrap();
+            assert!(matches!(file.content, FileContent::Binary(_)), "{ref_name}");
+        }
+    }
 }
@@ -0,0 +1,364 @@
+//! Diffs between files or directories on disk, outside git.
+//!
+//! A `DiffSpec` whose base and head are both `GitRef::Path` compares two
+//! filesystem paths (e.g. generated output directories, or a vendored copy
+//! against upstream). Listing and loading produce the same
+//! `FileDiffSummary`/`FileDiff` types as git diffs, so the viewer and reviews
+//! work unchanged; `content_hash` gives reviews a stable key.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime};
+
+use super::cli::GitError;
+use super::diff::{align_texts, FileBytes};
+use super::encoding;
+use super::types::{DiffOptions, File, FileContent, FileDiff, FileDiffSummary};
+
+/// List the files that differ between two files or two directories.
+///
+/// Two files compare as one entry named after each file. Directories are
+/// compared recursively (skipping `.git`), by relative path.
+pub(super) fn list_files(
+    base: &Path,
+    head: &Path,
+    options: &DiffOptions,
+) -> Result<Vec<FileDiffSummary>, GitError> {
+    if base.is_file() && head.is_file() {
+        if read(base)? == read(head)? {
+            return Ok(vec![]);
+        }
+        let name = |path: &Path| path.file_name().map(PathBuf::from);
+        let mut summary = FileDiffSummary::new(name(base), name(head));
+        attach_stats(base, head, &mut summary, options)?;
+        return Ok(vec![summary]);
+    }
+    if !base.is_dir() || !head.is_dir() {
+        return Err(GitError::InvalidPath(format!(
+            "{} and {} must both be files or both be directories",
+            base.display(),
+            head.display()
+        )));
+    }
+
+    let before = walk(base)?;
+    let after = walk(head)?;
+    let mut paths: Vec<&PathBuf> = before.keys().chain(after.keys()).collect();
+    paths.sort();
+    paths.dedup();
+
+    let mut files = Vec::new();
+    for path in paths {
+        let (old, new) = (before.get(path), after.get(path));
+        if let (Some(old), Some(new)) = (old, new) {
+            if read(old)? == read(new)? {
+                continue;
+            }
+        }
+        let mut summary =
+            FileDiffSummary::new(old.map(|_| path.clone()), new.map(|_| path.clone()));
+        attach_stats(base, head, &mut summary, options)?;
+        files.push(summary);
+    }
+    Ok(files)
+}
+
+/// Load both sides of a file, aligned but without inline changes (like
+/// `diff::load_file_diff`).
+pub(super) fn load_file_diff(
+    base: &Path,
+    head: &Path,
+    path: &Path,
+    options: &DiffOptions,
+) -> Result<FileDiff, GitError> {
+    let (before, after) = load_file_bytes(base, head, path)?;
+    let decode = |bytes: FileBytes| {
+        bytes.map(|b| encoding::decode_file(path.to_string_lossy().to_string(), &b, None))
+    };
+    let before = decode(before);
+    let after = decode(after);
+    let alignments = align_texts(&before, &after, options)?;
+
+    Ok(FileDiff {
+        before,
+        after,
+        alignments,
+        inline_changes: vec![],
+        highlights: None,
+    })
+}
+
+/// Raw bytes of both sides of a file. When `base`/`head` are files, they
+/// are read directly and `path` is ignored.
+pub(super) fn load_file_bytes(
+    base: &Path,
+    head: &Path,
+    path: &Path,
+) -> Result<(FileBytes, FileBytes), GitError> {
+    let side = |root: &Path| -> Result<FileBytes, GitError> {
+        let file = if root.is_file() {
+            root.to_path_buf()
+        } else {
+            root.join(path)
+        };
+        if !file.is_file() {
+            return Ok(None);
+        }
+        read(&file).map(Some)
+    };
+    Ok((side(base)?, side(head)?))
+}
+
+/// A content hash of a file or directory, for `DiffId`s of path diffs.
+/// Files hash as their git blob id; directories hash their sorted listing
+/// of relative paths and blob ids, so identical copies share reviews
+/// wherever they live.
+///
+/// This runs for every review command, so files' blob ids are cached until
+/// their size or modification time changes.
+pub fn content_hash(path: &Path) -> Result<String, GitError> {
+    let hash = if path.is_dir() {
+        let mut listing = String::new();
+        for (relative, file) in walk(path)? {
+            let id = file_blob_id(&file)?;
+            listing.push_str(&format!("{}\0{id}\n", relative.to_string_lossy()));
+        }
+        blob_id(listing.as_bytes())?
+    } else {
+        file_blob_id(path)?
+    };
+    Ok(format!("fs:{hash}"))
+}
+
+fn blob_id(bytes: &[u8]) -> Result<String, GitError> {
+    git2::Oid::hash_object(git2::ObjectType::Blob, bytes)
+        .map(|oid| oid.to_string())
+        .map_err(|e| GitError::CommandFailed(format!("Cannot hash content: {e}")))
+}
+
+/// What identifies a version of a file without reading it. On Unix the
+/// inode and ctime are included too, as they change on replacing writes
+/// and on edits that keep the size and mtime.
+#[derive(PartialEq, Eq)]
+struct FileStamp {
+    size: u64,
+    modified: SystemTime,
+    inode: u64,
+    changed: (i64, i64),
+}
+
+impl FileStamp {
+    #[cfg(unix)]
+    fn new(metadata: &std::fs::Metadata) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+        Some(Self {
+            size: metadata.len(),
+            modified: metadata.modified().ok()?,
+            inode: metadata.ino(),
+            changed: (metadata.ctime(), metadata.ctime_nsec()),
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn new(metadata: &std::fs::Metadata) -> Option<Self> {
+        Some(Self {
+            size: metadata.len(),
+            modified: metadata.modified().ok()?,
+            inode: 0,
+            changed: (0, 0),
+        })
+    }
+}
+
+/// Files modified more recently than this aren't cached: a second edit
+/// within the filesystem's timestamp granularity would leave the stamp as is.
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+/// Blob ids kept before the cache is emptied and starts over.
+const MAX_CACHED_BLOB_IDS: usize = 10_000;
+
+/// A file's blob id, as of its stamp.
+struct CachedBlobId {
+    stamp: FileStamp,
+    id: String,
+}
+
+/// Global cache of file blob ids, keyed by path.
+static BLOB_ID_CACHE: RwLock<Option<HashMap<PathBuf, CachedBlobId>>> = RwLock::new(None);
+
+/// The blob id of a file, from the cache if it hasn't changed since.
+fn file_blob_id(path: &Path) -> Result<String, GitError> {
+    let metadata = std::fs::metadata(path)
+        .map_err(|e| GitError::CommandFailed(format!("Cannot read {}: {e}", path.display())))?;
+    let stamp = FileStamp::new(&metadata).filter(|stamp| {
+        SystemTime::now()
+            .duration_since(stamp.modified)
+            .is_ok_and(|age| age >= RACY_WINDOW)
+    });
+
+    if let (Some(stamp), Ok(cache)) = (&stamp, BLOB_ID_CACHE.read()) {
+        if let Some(entry) = cache.as_ref().and_then(|c| c.get(path)) {
+            if entry.stamp == *stamp {
+                return Ok(entry.id.clone());
+            }
+        }
+    }
+
+    let id = blob_id(&read(path)?)?;
+    if let (Some(stamp), Ok(mut cache)) = (stamp, BLOB_ID_CACHE.write()) {
+        let cache = cache.get_or_insert_with(HashMap::new);
+        if cache.len() >= MAX_CACHED_BLOB_IDS {
+            cache.clear();
+        }
+        cache.insert(
+            path.to_path_buf(),
+            CachedBlobId {
+                stamp,
+                id: id.clone(),
+            },
+        );
+    }
+    Ok(id)
+}
+
+/// Fill in line counts and the binary flag for one file.
+fn attach_stats(
+    base: &Path,
+    head: &Path,
+    summary: &mut FileDiffSummary,
+    options: &DiffOptions,
+) -> Result<(), GitError> {
+    let diff = load_file_diff(base, head, summary.path(), options)?;
+    let is_binary = |file: &Option<File>| {
+        matches!(
+            file,
+            Some(File {
+                content: FileContent::Binary(_),
+                ..
+            })
+        )
+    };
+    if is_binary(&diff.before) || is_binary(&diff.after) {
+        summary.binary = true;
+        return Ok(());
+    }
+
+    let changed = diff.alignments.iter().filter(|a| a.changed);
+    summary.additions = Some(changed.clone().map(|a| a.after.len()).sum());
+    summary.deletions = Some(changed.map(|a| a.before.len()).sum());
+    Ok(())
+}
+
+/// All files under `root`, by path relative to it. Symlinks to files are
+/// followed; symlinks to directories are skipped, since they can loop.
+fn walk(root: &Path) -> Result<BTreeMap<PathBuf, PathBuf>, GitError> {
+    let mut files = BTreeMap::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|e| GitError::CommandFailed(format!("Cannot read {}: {e}", dir.display())))?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if entry.file_name() == ".git" {
+                continue;
+            }
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if path.is_file() {
+                let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+                files.insert(relative, path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, GitError> {
+    std::fs::read(path)
+        .map_err(|e| GitError::CommandFailed(format!("Cannot read {}: {e}", path.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::diff::{get_file_diff, list_diff_files};
+    use super::super::types::DiffSpec;
+    use super::*;
+
+    #[test]
+    fn test_diff_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let (old, new) = (dir.path().join("old"), dir.path().join("new"));
+        for root in [&old, &new] {
+            std::fs::create_dir_all(root.join("sub")).unwrap();
+            std::fs::write(root.join("same.txt"), "same\n").unwrap();
+        }
+        std::fs::write(old.join("sub/a.txt"), "one\ntwo\nthree\n").unwrap();
+        std::fs::write(new.join("sub/a.txt"), "one\ntwo too\nthree\nfour\n").unwrap();
+        std::fs::write(old.join("gone.txt"), "bye\n").unwrap();
+        std::fs::write(new.join("new.bin"), b"\x00\x01\x02\x03\x00\xff").unwrap();
+
+        let spec = DiffSpec::paths(old.to_string_lossy(), new.to_string_lossy());
+        // The repo argument is unused for path diffs
+        let options = DiffOptions::default();
+        let files = list_diff_files(Path::new("/nonexistent"), &spec, &options).unwrap();
+        let paths: Vec<_> = files.iter().map(|f| f.path().to_path_buf()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("gone.txt"),
+                PathBuf::from("new.bin"),
+                PathBuf::from("sub/a.txt")
+            ]
+        );
+        assert!(files[0].is_deleted());
+        assert!(files[1].is_added() && files[1].binary);
+        assert_eq!((files[2].additions, files[2].deletions), (Some(2), Some(1)));
+
+        let diff = get_file_diff(
+            Path::new("/nonexistent"),
+            &spec,
+            Path::new("sub/a.txt"),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(diff.alignments.iter().filter(|a| a.changed).count(), 2);
+        assert_eq!(diff.inline_changes.len(), 1);
+
+        // Identical content hashes the same wherever it lives
+        let copy = dir.path().join("copy");
+        std::fs::create_dir_all(copy.join("sub")).unwrap();
+        for file in ["same.txt", "sub/a.txt", "gone.txt"] {
+            std::fs::copy(old.join(file), copy.join(file)).unwrap();
+        }
+        assert_eq!(content_hash(&old).unwrap(), content_hash(&copy).unwrap());
+        assert_ne!(content_hash(&old).unwrap(), content_hash(&new).unwrap());
+        assert!(content_hash(&old).unwrap().starts_with("fs:"));
+
+        // Edits change the hash, even right after it was computed and
+        // without changing the size
+        let same = std::fs::read(copy.join("same.txt")).unwrap();
+        std::fs::write(copy.join("same.txt"), vec![b'x'; same.len()]).unwrap();
+        assert_ne!(content_hash(&old).unwrap(), content_hash(&copy).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_skips_directory_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join("sub/a.txt"), "a\n").unwrap();
+        std::os::unix::fs::symlink(&root, root.join("sub/loop")).unwrap();
+        std::os::unix::fs::symlink(root.join("sub/a.txt"), root.join("link.txt")).unwrap();
+
+        let files: Vec<PathBuf> = walk(&root).unwrap().into_keys().collect();
+        assert_eq!(
+            files,
+            vec![PathBuf::from("link.txt"), PathBuf::from("sub/a.txt")]
+        );
+    }
+}
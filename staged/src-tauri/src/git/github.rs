@@ -405,7 +405,7 @@ pub fn fetch_pr(repo: &Path, base_ref: &str, pr_number: u64) -> Result<DiffSpec,
 // Review Sync
 // =============================================================================
 
-use crate::review::Comment;
+use crate::review::{self, Comment, CommentThread};
 
 /// Result of syncing a review to GitHub.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -539,14 +539,38 @@ struct OutOfDiffComment {
     content: String,
 }
 
-/// Convert a local Comment to a GitHub review comment.
+/// The body of a thread as one GitHub comment: the root, then each reply
+/// quoted with its author (a pending review can't contain replies).
+fn thread_body(thread: &CommentThread) -> String {
+    let mut body = thread.root.content.clone();
+    for reply in &thread.replies {
+        body.push_str(&format!(
+            "\n\n> **{}:** {}",
+            review::author_label(&reply.author),
+            reply.content.replace('\n', "\n> ")
+        ));
+    }
+    body
+}
+
+/// The threads worth posting: those still open locally. A pending review
+/// can't mark a thread resolved, so resolved ones are left out.
+fn threads_to_sync(comments: &[Comment]) -> Vec<CommentThread<'_>> {
+    review::threads(comments)
+        .into_iter()
+        .filter(|thread| !thread.root.resolved)
+        .collect()
+}
+
+/// Convert a local comment thread to a GitHub review comment.
 ///
 /// If `valid_lines` is provided, checks if the comment's lines are within the diff.
 /// Returns Err for comments outside the diff (they'll be added to the review body).
 fn convert_comment(
-    comment: &Comment,
+    thread: &CommentThread,
     valid_lines: Option<&std::collections::HashSet<u32>>,
 ) -> std::result::Result<GitHubReviewComment, OutOfDiffComment> {
+    let comment = thread.root;
     // Convert 0-indexed span to 1-indexed line numbers
     let line = comment.span.end; // end line (1-indexed, since end is exclusive)
     let start_line = comment.span.start + 1; // start line (1-indexed)
@@ -562,7 +586,7 @@ fn convert_comment(
 
         Ok(GitHubReviewComment {
             path: comment.path.clone(),
-            body: comment.content.clone(),
+            body: thread_body(thread),
             line,
             side: "RIGHT", // Always RIGHT since we only support comments on new code
             start_line: if is_multiline { Some(start_line) } else { None },
@@ -578,7 +602,7 @@ fn convert_comment(
         Err(OutOfDiffComment {
             path: comment.path.clone(),
             line_info,
-            content: comment.content.clone(),
+            content: thread_body(thread),
         })
     }
 }
@@ -765,22 +789,26 @@ async fn delete_pending_review(
 ///
 /// This will:
 /// 1. Delete any existing pending review by the current user
-/// 2. Create a new pending review with all comments
+/// 2. Create a new pending review with one comment per unresolved thread,
+///    replies quoted below the thread's first comment
 /// 3. Return the URL to the review
 pub async fn sync_review_to_github(
     repo: &Path,
     pr_number: u64,
     comments: &[Comment],
 ) -> Result<GitHubSyncResult, GitError> {
-    if comments.is_empty() {
-        return Err(GitError::CommandFailed("No comments to sync".to_string()));
+    let threads = threads_to_sync(comments);
+    if threads.is_empty() {
+        return Err(GitError::CommandFailed(
+            "No unresolved comments to sync".to_string(),
+        ));
     }
 
     let token = get_github_token()?;
     let (owner, repo_name) = get_github_repo(repo)?;
     log::info!(
-        "Syncing {} comments to GitHub PR #{} in {}/{}",
-        comments.len(),
+        "Syncing {} comment threads to GitHub PR #{} in {}/{}",
+        threads.len(),
         pr_number,
         owner,
         repo_name
@@ -806,8 +834,8 @@ pub async fn sync_review_to_github(
     let mut gh_comments: Vec<GitHubReviewComment> = Vec::new();
     let mut out_of_diff_comments: Vec<OutOfDiffComment> = Vec::new();
 
-    for comment in comments {
-        match convert_comment(comment, valid_lines_by_file.get(&comment.path)) {
+    for thread in &threads {
+        match convert_comment(thread, valid_lines_by_file.get(&thread.root.path)) {
             Ok(gh_comment) => gh_comments.push(gh_comment),
             Err(out_of_diff) => out_of_diff_comments.push(out_of_diff),
         }
@@ -1140,6 +1168,7 @@ pub async fn update_pull_request(
 
 #[cfg(test)]
 mod tests {
+    use super::super::Span;
     use super::*;
 
     #[test]
@@ -1150,4 +1179,23 @@ mod tests {
         // Either authenticated or has a setup hint
         assert!(status.authenticated || status.setup_hint.is_some());
     }
+
+    #[test]
+    fn test_threads_to_sync_skips_resolved() {
+        let open = Comment::new("a.rs", Span::new(0, 1), "Rename this");
+        let reply = Comment {
+            parent_id: Some(open.id.clone()),
+            ..Comment::new("a.rs", Span::new(0, 1), "Done")
+        };
+        let resolved = Comment {
+            resolved: true,
+            ..Comment::new("b.rs", Span::new(0, 1), "Typo")
+        };
+        let comments = [open, reply, resolved];
+        let threads = threads_to_sync(&comments);
+        assert_eq!(threads.len(), 1);
+        let body = thread_body(&threads[0]);
+        assert!(body.starts_with("Rename this"));
+        assert!(body.contains("Done"));
+    }
 }
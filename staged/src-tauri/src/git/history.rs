@@ -0,0 +1,295 @@
+//! Line blame and per-file history.
+//!
+//! This module provides:
+//! - `blame_file`: Per-line origin commit for a file at a ref (or WORKDIR)
+//! - `get_file_history`: Commits that touched a file, following renames
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::cli::{self, GitError};
+use super::types::WORKDIR;
+
+/// SHA git blame reports for lines that are not committed yet.
+const UNCOMMITTED_SHA: &str = "0000000000000000000000000000000000000000";
+
+/// A commit referenced from blame output.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlameCommit {
+    pub sha: String,
+    pub short_sha: String,
+    pub author: String,
+    pub author_email: String,
+    /// Author time as a Unix timestamp
+    pub timestamp: i64,
+    pub summary: String,
+    /// True for working-tree lines that have not been committed
+    pub is_uncommitted: bool,
+}
+
+/// Origin of a single line.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlameLine {
+    /// SHA of the commit that last changed this line (key into `Blame::commits`)
+    pub sha: String,
+    /// Line number (0-indexed) in that commit's version of the file
+    pub original_line: u32,
+    /// Path of the file in that commit (differs from the blamed path after renames)
+    pub original_path: String,
+}
+
+/// Blame for a whole file.
+#[derive(Debug, Clone, Serialize)]
+pub struct Blame {
+    /// One entry per line of the file, in order
+    pub lines: Vec<BlameLine>,
+    /// Commits referenced by `lines`, keyed by SHA
+    pub commits: HashMap<String, BlameCommit>,
+}
+
+/// A commit in a file's history.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileHistoryEntry {
+    pub sha: String,
+    pub short_sha: String,
+    pub author: String,
+    pub author_email: String,
+    /// Author time as a Unix timestamp
+    pub timestamp: i64,
+    pub subject: String,
+    /// Path of the file in this commit
+    pub path: String,
+    /// Previous path, if this commit renamed or copied the file
+    pub previous_path: Option<String>,
+}
+
+/// Blame a file at a ref.
+///
+/// For WORKDIR, blames the working-tree file; uncommitted lines are
+/// attributed to a commit with `is_uncommitted` set.
+pub fn blame_file(repo: &Path, ref_name: &str, path: &str) -> Result<Blame, GitError> {
+    let mut args = vec!["blame", "--porcelain"];
+    if ref_name != WORKDIR {
+        args.push(ref_name);
+    }
+    args.extend(["--", path]);
+
+    let output = cli::run(repo, &args)?;
+    Ok(parse_blame_porcelain(&output))
+}
+
+/// Parse `git blame --porcelain` output.
+///
+/// Each line is introduced by a header `<sha> <orig> <final> [<count>]` and
+/// ends with its content prefixed by a tab. The first header of a group is
+/// followed by a `filename` line, and by commit details the first time a
+/// commit appears.
+fn parse_blame_porcelain(output: &str) -> Blame {
+    let mut lines = Vec::new();
+    let mut commits: HashMap<String, BlameCommit> = HashMap::new();
+
+    let mut sha = String::new();
+    let mut original_line = 0u32;
+    let mut filename = String::new();
+
+    for line in output.lines() {
+        if line.starts_with('\t') {
+            // Content line ends the current entry
+            lines.push(BlameLine {
+                sha: sha.clone(),
+                original_line,
+                original_path: filename.clone(),
+            });
+            continue;
+        }
+
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+
+        if key.len() == 40 && key.bytes().all(|b| b.is_ascii_hexdigit()) {
+            sha = key.to_string();
+            original_line = value
+                .split(' ')
+                .next()
+                .and_then(|n| n.parse::<u32>().ok())
+                .map(|n| n.saturating_sub(1))
+                .unwrap_or(0);
+            commits.entry(sha.clone()).or_insert_with(|| BlameCommit {
+                sha: sha.clone(),
+                short_sha: sha[..7].to_string(),
+                author: String::new(),
+                author_email: String::new(),
+                timestamp: 0,
+                summary: String::new(),
+                is_uncommitted: sha == UNCOMMITTED_SHA,
+            });
+            continue;
+        }
+
+        let Some(commit) = commits.get_mut(&sha) else {
+            continue;
+        };
+        match key {
+            "author" => commit.author = value.to_string(),
+            "author-mail" => {
+                commit.author_email = value
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string()
+            }
+            "author-time" => commit.timestamp = value.parse().unwrap_or(0),
+            "summary" => commit.summary = value.to_string(),
+            "filename" => filename = value.to_string(),
+            _ => {}
+        }
+    }
+
+    Blame { lines, commits }
+}
+
+/// List commits that touched a file, newest first, following renames.
+///
+/// For WORKDIR, history starts at HEAD.
+pub fn get_file_history(
+    repo: &Path,
+    ref_name: &str,
+    path: &str,
+    limit: Option<usize>,
+) -> Result<Vec<FileHistoryEntry>, GitError> {
+    let rev = if ref_name == WORKDIR {
+        "HEAD"
+    } else {
+        ref_name
+    };
+    let limit_arg = limit.map(|n| format!("--max-count={n}"));
+
+    // Records are separated by \x1e; each is a header line followed by name-status
+    let mut args = vec![
+        "-c",
+        "core.quotePath=false",
+        "log",
+        "--follow",
+        "--name-status",
+        "--format=%x1e%H|%h|%an|%ae|%at|%s",
+    ];
+    if let Some(limit_arg) = &limit_arg {
+        args.push(limit_arg);
+    }
+    args.extend([rev, "--", path]);
+
+    let output = cli::run(repo, &args)?;
+    Ok(parse_file_history(&output))
+}
+
+/// Parse the `git log --follow --name-status` output produced by `get_file_history`.
+fn parse_file_history(output: &str) -> Vec<FileHistoryEntry> {
+    let mut entries = Vec::new();
+
+    for record in output.split('\x1e') {
+        let mut record_lines = record.lines().filter(|l| !l.is_empty());
+        let Some(header) = record_lines.next() else {
+            continue;
+        };
+        let parts: Vec<&str> = header.splitn(6, '|').collect();
+        if parts.len() < 6 {
+            continue;
+        }
+
+        // Name-status: "M\tpath", or "R100\told\tnew" for renames and copies
+        let (path, previous_path) = match record_lines.next() {
+            Some(status) => {
+                let fields: Vec<&str> = status.split('\t').collect();
+                match fields.as_slice() {
+                    [kind, old, new] if kind.starts_with('R') || kind.starts_with('C') => {
+                        (new.to_string(), Some(old.to_string()))
+                    }
+                    [_, path, ..] => (path.to_string(), None),
+                    _ => continue,
+                }
+            }
+            None => continue,
+        };
+
+        entries.push(FileHistoryEntry {
+            sha: parts[0].to_string(),
+            short_sha: parts[1].to_string(),
+            author: parts[2].to_string(),
+            author_email: parts[3].to_string(),
+            timestamp: parts[4].parse().unwrap_or(0),
+            subject: parts[5].to_string(),
+            path,
+            previous_path,
+        });
+    }
+
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    fn git(repo: &Path, args: &[&str]) {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(repo)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?} failed");
+    }
+
+    fn init_repo(repo: &Path) {
+        git(repo, &["init"]);
+        git(repo, &["config", "user.email", "test@test.com"]);
+        git(repo, &["config", "user.name", "Test"]);
+    }
+
+    #[test]
+    fn test_blame_workdir_and_ref() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        init_repo(repo);
+        std::fs::write(repo.join("f.txt"), "one\ntwo\n").unwrap();
+        git(repo, &["add", "f.txt"]);
+        git(repo, &["commit", "-m", "add f"]);
+        std::fs::write(repo.join("f.txt"), "one\nchanged\n").unwrap();
+
+        let blame = blame_file(repo, WORKDIR, "f.txt").unwrap();
+        assert_eq!(blame.lines.len(), 2);
+        let first = &blame.commits[&blame.lines[0].sha];
+        assert_eq!(first.summary, "add f");
+        assert_eq!(first.author_email, "test@test.com");
+        assert!(blame.commits[&blame.lines[1].sha].is_uncommitted);
+
+        let blame = blame_file(repo, "HEAD", "f.txt").unwrap();
+        assert!(blame.lines.iter().all(|l| l.sha == blame.lines[0].sha));
+        assert_eq!(blame.lines[1].original_line, 1);
+    }
+
+    #[test]
+    fn test_file_history_follows_renames() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        init_repo(repo);
+        std::fs::write(repo.join("old.txt"), "a\nb\nc\nd\n").unwrap();
+        git(repo, &["add", "old.txt"]);
+        git(repo, &["commit", "-m", "create"]);
+        git(repo, &["mv", "old.txt", "new.txt"]);
+        git(repo, &["commit", "-m", "rename"]);
+
+        let history = get_file_history(repo, WORKDIR, "new.txt", None).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].subject, "rename");
+        assert_eq!(history[0].path, "new.txt");
+        assert_eq!(history[0].previous_path.as_deref(), Some("old.txt"));
+        assert_eq!(history[1].path, "old.txt");
+
+        let limited = get_file_history(repo, "HEAD", "new.txt", Some(1)).unwrap();
+        assert_eq!(limited.len(), 1);
+    }
+}
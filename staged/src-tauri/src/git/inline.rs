@@ -0,0 +1,337 @@
+//! Intra-line (word/character) change detection.
+//!
+//! Within each changed alignment, before and after lines are paired up by
+//! position and diffed token-by-token, so the viewer can highlight exactly
+//! what changed inside a long line.
+
+use super::types::{Alignment, CharRange, File, FileContent, InlineChange, InlineTokenizer};
+
+/// Skip token-level diffing when the LCS table would exceed this many cells.
+/// Such lines are treated as fully changed (no inline ranges).
+const MAX_LCS_CELLS: usize = 1_000_000;
+
+/// Compute inline changes for every paired line in the changed alignments.
+pub fn compute_inline_changes(
+    before: &Option<File>,
+    after: &Option<File>,
+    alignments: &[Alignment],
+    tokenizer: InlineTokenizer,
+) -> Vec<InlineChange> {
+    let (before_lines, after_lines) = match (before, after) {
+        (
+            Some(File {
+                content: FileContent::Text { lines: b },
+                ..
+            }),
+            Some(File {
+                content: FileContent::Text { lines: a },
+                ..
+            }),
+        ) => (b, a),
+        _ => return vec![],
+    };
+
+    let mut changes = Vec::new();
+
+    for alignment in alignments.iter().filter(|a| a.changed) {
+        // Pair lines by position; unpaired lines are wholly added/removed
+        let pairs = alignment.before.len().min(alignment.after.len());
+        for i in 0..pairs {
+            let before_line = alignment.before.start + i;
+            let after_line = alignment.after.start + i;
+            let (Some(old), Some(new)) = (
+                before_lines.get(before_line as usize),
+                after_lines.get(after_line as usize),
+            ) else {
+                continue;
+            };
+
+            if let Some((before_ranges, after_ranges)) = diff_line(old, new, tokenizer) {
+                changes.push(InlineChange {
+                    before_line,
+                    after_line,
+                    before: before_ranges,
+                    after: after_ranges,
+                });
+            }
+        }
+    }
+
+    changes
+}
+
+/// Diff a single pair of lines.
+///
+/// Returns the changed character ranges on each side, or None if the lines
+/// share nothing worth highlighting (identical, too long, or entirely different).
+fn diff_line(
+    old: &str,
+    new: &str,
+    tokenizer: InlineTokenizer,
+) -> Option<(Vec<CharRange>, Vec<CharRange>)> {
+    if old == new {
+        return None;
+    }
+
+    let old_tokens = tokenize(old, tokenizer);
+    let new_tokens = tokenize(new, tokenizer);
+
+    // Trim the common prefix and suffix so the LCS only covers the middle
+    let prefix = old_tokens
+        .iter()
+        .zip(&new_tokens)
+        .take_while(|(a, b)| a.text == b.text)
+        .count();
+    let suffix = old_tokens[prefix..]
+        .iter()
+        .rev()
+        .zip(new_tokens[prefix..].iter().rev())
+        .take_while(|(a, b)| a.text == b.text)
+        .count();
+
+    let old_mid = &old_tokens[prefix..old_tokens.len() - suffix];
+    let new_mid = &new_tokens[prefix..new_tokens.len() - suffix];
+
+    if old_mid.len().saturating_mul(new_mid.len()) > MAX_LCS_CELLS {
+        return None;
+    }
+
+    let (old_kept, new_kept) = lcs(old_mid, new_mid);
+
+    // Nothing in common besides whitespace: the line was rewritten, not edited
+    let common_outside_mid = prefix + suffix > 0;
+    let common_in_mid = old_mid
+        .iter()
+        .zip(&old_kept)
+        .any(|(t, &kept)| kept && !t.text.trim().is_empty());
+    if !common_outside_mid && !common_in_mid {
+        return None;
+    }
+
+    Some((
+        changed_ranges(old_mid, &old_kept),
+        changed_ranges(new_mid, &new_kept),
+    ))
+}
+
+/// A token with its offset in the line, in UTF-16 code units (what
+/// JavaScript strings index by).
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    /// UTF-16 offset of the token start
+    start: u32,
+    /// Length in UTF-16 code units
+    len: u32,
+}
+
+/// Split a line into tokens.
+///
+/// Word mode groups runs of identifier characters and runs of whitespace;
+/// every other character is its own token. Char mode emits one token per char.
+fn tokenize(line: &str, tokenizer: InlineTokenizer) -> Vec<Token<'_>> {
+    #[derive(PartialEq, Clone, Copy)]
+    enum Class {
+        Word,
+        Space,
+        Other,
+    }
+
+    let class_of = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            Class::Word
+        } else if c.is_whitespace() {
+            Class::Space
+        } else {
+            Class::Other
+        }
+    };
+
+    let mut tokens: Vec<Token> = Vec::new();
+    // (byte start, UTF-16 start, UTF-16 len, class) of the token being built
+    let mut current: Option<(usize, u32, u32, Class)> = None;
+    let mut offset = 0u32;
+
+    for (byte_idx, c) in line.char_indices() {
+        let class = class_of(c);
+        let width = c.len_utf16() as u32;
+        let unit_start = offset;
+        offset += width;
+
+        let extend = tokenizer == InlineTokenizer::Word
+            && class != Class::Other
+            && matches!(current, Some((_, _, _, cls)) if cls == class);
+
+        if extend {
+            if let Some((_, _, len, _)) = current.as_mut() {
+                *len += width;
+            }
+            continue;
+        }
+
+        if let Some((start_byte, start, len, _)) = current.take() {
+            tokens.push(Token {
+                text: &line[start_byte..byte_idx],
+                start,
+                len,
+            });
+        }
+        current = Some((byte_idx, unit_start, width, class));
+    }
+
+    if let Some((start_byte, start, len, _)) = current {
+        tokens.push(Token {
+            text: &line[start_byte..],
+            start,
+            len,
+        });
+    }
+
+    tokens
+}
+
+/// Longest common subsequence of two token lists.
+/// Returns, for each side, whether each token is part of the common subsequence.
+fn lcs(old: &[Token], new: &[Token]) -> (Vec<bool>, Vec<bool>) {
+    let n = old.len();
+    let m = new.len();
+    let mut old_kept = vec![false; n];
+    let mut new_kept = vec![false; m];
+    if n == 0 || m == 0 {
+        return (old_kept, new_kept);
+    }
+
+    // table[i][j] = LCS length of old[i..] and new[j..]
+    let width = m + 1;
+    let mut table = vec![0u32; (n + 1) * width];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            table[i * width + j] = if old[i].text == new[j].text {
+                table[(i + 1) * width + j + 1] + 1
+            } else {
+                table[(i + 1) * width + j].max(table[i * width + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if old[i].text == new[j].text {
+            old_kept[i] = true;
+            new_kept[j] = true;
+            i += 1;
+            j += 1;
+        } else if table[(i + 1) * width + j] >= table[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    (old_kept, new_kept)
+}
+
+/// Merge runs of tokens not in the LCS into character ranges.
+fn changed_ranges(tokens: &[Token], kept: &[bool]) -> Vec<CharRange> {
+    let mut ranges: Vec<CharRange> = Vec::new();
+
+    for (token, &kept) in tokens.iter().zip(kept) {
+        if kept {
+            continue;
+        }
+        let end = token.start + token.len;
+        match ranges.last_mut() {
+            Some(last) if last.end == token.start => last.end = end,
+            _ => ranges.push(CharRange::new(token.start, end)),
+        }
+    }
+
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::Span;
+
+    fn text_file(lines: &[&str]) -> Option<File> {
+        Some(File::new(
+            "f.txt",
+            FileContent::Text {
+                lines: lines.iter().map(|s| s.to_string()).collect(),
+            },
+        ))
+    }
+
+    #[test]
+    fn test_word_diff_highlights_changed_word() {
+        let (before, after) = diff_line(
+            "let x = foo(a, b);",
+            "let x = foo(a, c);",
+            InlineTokenizer::Word,
+        )
+        .unwrap();
+        assert_eq!(before, vec![CharRange::new(15, 16)]);
+        assert_eq!(after, vec![CharRange::new(15, 16)]);
+    }
+
+    #[test]
+    fn test_word_diff_groups_identifiers() {
+        let (before, after) =
+            diff_line("call(alpha)", "call(alphabet)", InlineTokenizer::Word).unwrap();
+        assert_eq!(before, vec![CharRange::new(5, 10)]);
+        assert_eq!(after, vec![CharRange::new(5, 13)]);
+    }
+
+    #[test]
+    fn test_char_diff() {
+        let (before, after) =
+            diff_line("call(alpha)", "call(alphabet)", InlineTokenizer::Char).unwrap();
+        assert!(before.is_empty());
+        assert_eq!(after, vec![CharRange::new(10, 13)]);
+    }
+
+    #[test]
+    fn test_unrelated_lines_not_highlighted() {
+        assert!(diff_line("foo", "bar", InlineTokenizer::Word).is_none());
+        assert!(diff_line("same", "same", InlineTokenizer::Word).is_none());
+    }
+
+    #[test]
+    fn test_offsets_are_utf16_units() {
+        let (_, after) = diff_line("é = 1", "é = 2", InlineTokenizer::Word).unwrap();
+        assert_eq!(after, vec![CharRange::new(4, 5)]);
+        // An emoji is two UTF-16 units, as in the frontend's strings
+        let (_, after) = diff_line("😀 = 1", "😀 = 2", InlineTokenizer::Char).unwrap();
+        assert_eq!(after, vec![CharRange::new(5, 6)]);
+    }
+
+    #[test]
+    fn test_compute_inline_changes_pairs_lines() {
+        let before = text_file(&["a", "value = 1", "z"]);
+        let after = text_file(&["a", "value = 2", "extra", "z"]);
+        let alignments = vec![
+            Alignment {
+                before: Span::new(0, 1),
+                after: Span::new(0, 1),
+                changed: false,
+            },
+            Alignment {
+                before: Span::new(1, 2),
+                after: Span::new(1, 3),
+                changed: true,
+            },
+            Alignment {
+                before: Span::new(2, 3),
+                after: Span::new(3, 4),
+                changed: false,
+            },
+        ];
+
+        let changes = compute_inline_changes(&before, &after, &alignments, InlineTokenizer::Word);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].before_line, 1);
+        assert_eq!(changes[0].after_line, 1);
+        assert_eq!(changes[0].after, vec![CharRange::new(8, 9)]);
+    }
+}
@@ -0,0 +1,234 @@
+//! Git LFS pointer detection and resolution.
+//!
+//! LFS-tracked files are stored in git as small pointer files. When the real
+//! object is in the local LFS store we load that instead; otherwise callers
+//! report `FileContent::LfsPointer` so the UI can say what's missing.
+
+use git2::Repository;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use super::attributes;
+use super::binary;
+use super::encoding;
+use super::types::{File, FileContent};
+
+/// Pointer files are always smaller than this (per the LFS spec).
+const MAX_POINTER_SIZE: usize = 1024;
+
+const POINTER_VERSION: &str = "version https://git-lfs.github.com/spec/v1";
+
+/// Bytes sniffed for a null byte before reading a whole object; matches the
+/// binary check in `diff`/`files`.
+const SNIFF_LEN: usize = 8192;
+
+/// A parsed LFS pointer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct LfsPointer {
+    /// SHA-256 of the object, hex-encoded
+    pub oid: String,
+    /// Object size in bytes
+    pub size: u64,
+}
+
+impl LfsPointer {
+    /// Parse `bytes` as an LFS pointer file.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() >= MAX_POINTER_SIZE {
+            return None;
+        }
+        let text = std::str::from_utf8(bytes).ok()?;
+        let mut lines = text.lines();
+        if lines.next()? != POINTER_VERSION {
+            return None;
+        }
+
+        let mut oid = None;
+        let mut size = None;
+        for line in lines {
+            if let Some(hash) = line.strip_prefix("oid sha256:") {
+                oid = Some(hash.to_string());
+            } else if let Some(n) = line.strip_prefix("size ") {
+                size = n.parse().ok();
+            }
+        }
+
+        let oid = oid.filter(|h| h.len() == 64 && h.bytes().all(|b| b.is_ascii_hexdigit()))?;
+        Some(Self { oid, size: size? })
+    }
+
+    /// Path of the object in the local store: `lfs/objects/ab/cd/abcd...`
+    fn object_path(&self, common_dir: &Path) -> PathBuf {
+        common_dir
+            .join("lfs")
+            .join("objects")
+            .join(&self.oid[0..2])
+            .join(&self.oid[2..4])
+            .join(&self.oid)
+    }
+
+    /// Read the object from the local LFS store under `common_dir`
+    /// (the repository's common git dir).
+    ///
+    /// Returns None if the object isn't present (or is incomplete). Binary
+    /// objects are only read as far as the first null byte's chunk, which is
+    /// enough for the binary check, so large assets aren't loaded into memory.
+    pub fn read_object(&self, common_dir: &Path) -> Option<Vec<u8>> {
+        let path = self.object_path(common_dir);
+        let metadata = std::fs::metadata(&path).ok()?;
+        if metadata.len() != self.size {
+            return None;
+        }
+
+        let mut file = std::fs::File::open(&path).ok()?;
+        let mut bytes = vec![0; SNIFF_LEN.min(self.size as usize)];
+        file.read_exact(&mut bytes).ok()?;
+        if bytes.contains(&0) {
+            return Some(bytes);
+        }
+        file.read_to_end(&mut bytes).ok()?;
+        Some(bytes)
+    }
+
+    /// Read the whole object, however large. For callers that need the
+    /// actual bytes of a binary asset rather than a preview.
+    pub fn read_full_object(&self, common_dir: &Path) -> Option<Vec<u8>> {
+        let bytes = std::fs::read(self.object_path(common_dir)).ok()?;
+        (bytes.len() as u64 == self.size).then_some(bytes)
+    }
+
+    /// Fix up a file built from an object returned by `read_object`. Binary
+    /// objects were only partly read, so their size comes from the pointer
+    /// and their blob id is hashed from the object in the store. If that
+    /// fails, the object is reported as missing.
+    pub fn fix_binary_info(&self, common_dir: &Path, mut file: File) -> File {
+        if let FileContent::Binary(info) = &mut file.content {
+            let path = self.object_path(common_dir);
+            match git2::Oid::hash_file(git2::ObjectType::Blob, path) {
+                Ok(oid) => {
+                    info.size = self.size;
+                    info.hash = oid.to_string();
+                }
+                Err(_) => return File::new(file.path, self.clone().into_content()),
+            }
+        }
+        file
+    }
+
+    /// Content to report when the object isn't available locally.
+    pub fn into_content(self) -> FileContent {
+        FileContent::LfsPointer {
+            oid: self.oid,
+            size: self.size,
+        }
+    }
+}
+
+/// Build a File from blob or working-tree bytes, resolving Git LFS pointers
+/// from the local LFS store and detecting encoding and line endings. Files
+/// with the `-diff` (or `binary`) attribute are never decoded as text.
+///
+/// Also returns whether the content was read from the LFS store.
+pub(super) fn load_file(
+    repo: &Repository,
+    path: &Path,
+    bytes: &[u8],
+    declared: Option<&str>,
+) -> (File, bool) {
+    let force_binary = attributes::diff_attributes(repo, path).binary;
+    let path = path.to_string_lossy().to_string();
+    let decode = |path: String, bytes: &[u8]| {
+        if force_binary {
+            let info = binary::binary_info(&path, bytes);
+            File::new(path, FileContent::Binary(info))
+        } else {
+            encoding::decode_file(path, bytes, declared)
+        }
+    };
+
+    let Some(pointer) = LfsPointer::parse(bytes) else {
+        return (decode(path, bytes), false);
+    };
+    let common_dir = common_dir(repo.path());
+    match pointer.read_object(&common_dir) {
+        Some(object) => (
+            pointer.fix_binary_info(&common_dir, decode(path, &object)),
+            true,
+        ),
+        None => (File::new(path, pointer.into_content()), false),
+    }
+}
+
+/// The common git dir for `git_dir`, where the LFS store lives.
+///
+/// Linked worktrees have their own git dir with a `commondir` file pointing
+/// back at the main repository's.
+pub(super) fn common_dir(git_dir: &Path) -> PathBuf {
+    match std::fs::read_to_string(git_dir.join("commondir")) {
+        Ok(relative) => git_dir.join(relative.trim()),
+        Err(_) => git_dir.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::binary::binary_info;
+    use super::*;
+
+    const OID: &str = "4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393";
+
+    fn pointer(size: u64) -> String {
+        format!("{POINTER_VERSION}\noid sha256:{OID}\nsize {size}\n")
+    }
+
+    #[test]
+    fn test_parse_pointer() {
+        let parsed = LfsPointer::parse(pointer(12345).as_bytes()).unwrap();
+        assert_eq!(parsed.oid, OID);
+        assert_eq!(parsed.size, 12345);
+
+        assert!(LfsPointer::parse(b"just some text\n").is_none());
+        assert!(LfsPointer::parse(format!("{POINTER_VERSION}\nsize 1\n").as_bytes()).is_none());
+    }
+
+    #[test]
+    fn test_read_object() {
+        let dir = tempfile::tempdir().unwrap();
+        let parsed = LfsPointer::parse(pointer(6).as_bytes()).unwrap();
+        assert!(parsed.read_object(dir.path()).is_none());
+
+        let path = parsed.object_path(dir.path());
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "hello\n").unwrap();
+        assert_eq!(parsed.read_object(dir.path()).unwrap(), b"hello\n");
+
+        // Size mismatch means a partial download
+        let wrong_size = LfsPointer::parse(pointer(7).as_bytes()).unwrap();
+        assert!(wrong_size.read_object(dir.path()).is_none());
+    }
+
+    #[test]
+    fn test_binary_object_hash_is_blob_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut bytes = vec![0u8; SNIFF_LEN * 2];
+        bytes[SNIFF_LEN + 1] = 1;
+        let parsed = LfsPointer::parse(pointer(bytes.len() as u64).as_bytes()).unwrap();
+        let path = parsed.object_path(dir.path());
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, &bytes).unwrap();
+
+        let object = parsed.read_object(dir.path()).unwrap();
+        assert_eq!(object.len(), SNIFF_LEN);
+        let file = File::new(
+            "asset.bin",
+            FileContent::Binary(binary_info("asset.bin", &object)),
+        );
+        let FileContent::Binary(info) = parsed.fix_binary_info(dir.path(), file).content else {
+            panic!("expected binary");
+        };
+        // Same id as the plain (non-LFS) blob of the whole object
+        let blob_id = git2::Oid::hash_object(git2::ObjectType::Blob, &bytes).unwrap();
+        assert_eq!(info.hash, blob_id.to_string());
+        assert_eq!(info.size, bytes.len() as u64);
+    }
+}
@@ -0,0 +1,548 @@
+//! Dependency-level summaries of lockfile changes.
+//!
+//! Lockfile diffs are thousands of lines of noise. For Cargo.lock,
+//! package-lock.json, yarn.lock and poetry.lock, both versions are parsed
+//! into package lists and compared, and the result is attached to the file's
+//! `FileDiffSummary`. Summaries are cached by the two sides' blob ids, so
+//! refreshing the file list doesn't re-parse unchanged lockfiles.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::RwLock;
+
+use git2::{ObjectType, Oid, Repository};
+use serde_json::Value;
+
+use super::cli::GitError;
+use super::files::get_file_at_ref;
+use super::types::{
+    DependencyChange, DependencyChangeKind, FileContent, FileDiffSummary, GitRef, LockfileKind,
+    LockfileSummary, WORKDIR,
+};
+
+/// Locked packages: name -> set of (version, source).
+type Packages = BTreeMap<String, BTreeSet<(String, Option<String>)>>;
+
+/// Blob ids of a lockfile's two sides (None for a missing side).
+type SummaryKey = (LockfileKind, Option<Oid>, Option<Oid>);
+
+/// Summaries kept before the cache is emptied and starts over.
+const MAX_CACHED_SUMMARIES: usize = 64;
+
+/// Global cache of lockfile summaries, keyed by the blob ids compared.
+static SUMMARY_CACHE: RwLock<Option<HashMap<SummaryKey, LockfileSummary>>> = RwLock::new(None);
+
+impl LockfileKind {
+    /// Recognize a lockfile by its file name.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.file_name()?.to_str()? {
+            "Cargo.lock" => Some(Self::Cargo),
+            "package-lock.json" => Some(Self::Npm),
+            "yarn.lock" => Some(Self::Yarn),
+            "poetry.lock" => Some(Self::Poetry),
+            _ => None,
+        }
+    }
+
+    fn parse(self, text: &str) -> Result<Packages, String> {
+        match self {
+            Self::Cargo | Self::Poetry => parse_toml_packages(text),
+            Self::Npm => parse_package_lock(text),
+            Self::Yarn => Ok(parse_yarn_lock(text)),
+        }
+    }
+}
+
+/// Attach a `LockfileSummary` to each lockfile in `files`.
+/// `base` and `head` must already be resolved (no merge-base or stash refs).
+///
+/// Best-effort per file: a lockfile that can't be loaded or parsed (e.g. one
+/// with merge conflict markers) gets no summary.
+pub(super) fn attach_lockfile_summaries(
+    repo: &Path,
+    base: &GitRef,
+    head: &GitRef,
+    files: &mut [FileDiffSummary],
+) {
+    let git_repo = Repository::discover(repo).ok();
+    for file in files.iter_mut() {
+        let Some(kind) = LockfileKind::from_path(file.path()) else {
+            continue;
+        };
+        let key = git_repo.as_ref().and_then(|git_repo| {
+            let before = blob_id(git_repo, base, file.before.as_deref())?;
+            let after = blob_id(git_repo, head, file.after.as_deref())?;
+            Some((kind, before, after))
+        });
+        let cached = key.as_ref().and_then(|key| {
+            let cache = SUMMARY_CACHE.read().ok()?;
+            cache.as_ref()?.get(key).cloned()
+        });
+        if let Some(summary) = cached {
+            file.lockfile = Some(summary);
+            continue;
+        }
+
+        match summarize(repo, kind, base, head, file) {
+            Ok(summary) => {
+                if let (Some(key), Ok(mut cache)) = (key, SUMMARY_CACHE.write()) {
+                    let cache = cache.get_or_insert_with(HashMap::new);
+                    if cache.len() >= MAX_CACHED_SUMMARIES {
+                        cache.clear();
+                    }
+                    cache.insert(key, summary.clone());
+                }
+                file.lockfile = Some(summary);
+            }
+            Err(e) => log::warn!("Cannot summarize lockfile {}: {e}", file.path().display()),
+        }
+    }
+}
+
+/// The blob id of `path` at `git_ref`: Some(None) for a missing side, None
+/// if it can't be determined (so the summary isn't cached). Working tree
+/// files are hashed as they are on disk.
+fn blob_id(repo: &Repository, git_ref: &GitRef, path: Option<&Path>) -> Option<Option<Oid>> {
+    let Some(path) = path else {
+        return Some(None);
+    };
+    let id = match git_ref {
+        GitRef::WorkingTree => Oid::hash_file(ObjectType::Blob, repo.workdir()?.join(path)).ok()?,
+        GitRef::Index => repo.index().ok()?.get_path(path, 0)?.id,
+        GitRef::Rev(rev) => {
+            let spec = format!("{rev}:{}", path.to_str()?);
+            repo.revparse_single(&spec).ok()?.id()
+        }
+        _ => return None,
+    };
+    Some(Some(id))
+}
+
+fn summarize(
+    repo: &Path,
+    kind: LockfileKind,
+    base: &GitRef,
+    head: &GitRef,
+    file: &FileDiffSummary,
+) -> Result<LockfileSummary, GitError> {
+    let load = |git_ref: &GitRef, path: Option<&Path>| -> Result<Packages, GitError> {
+        let Some(path) = path else {
+            return Ok(Packages::new());
+        };
+        // get_file_at_ref reads `<ref>:<path>`, so an empty ref means the index
+        let ref_name = match git_ref {
+            GitRef::WorkingTree => WORKDIR,
+            GitRef::Index => "",
+            GitRef::Rev(rev) => rev,
+            GitRef::Path(_) => unreachable!("Path specs aren't listed through git"),
+            GitRef::MergeBase
+            | GitRef::MergeBaseOf(_)
+            | GitRef::Stash(_)
+            | GitRef::Patch(_)
+            | GitRef::Rebased(_) => {
+                unreachable!("MergeBase/MergeBaseOf/Stash/Patch/Rebased should have been resolved")
+            }
+        };
+        let path = path.to_string_lossy();
+        let loaded = get_file_at_ref(repo, ref_name, &path)?;
+        let FileContent::Text { lines } = loaded.content else {
+            return Err(GitError::CommandFailed(format!("{path} is not text")));
+        };
+        kind.parse(&lines.join("\n"))
+            .map_err(|e| GitError::CommandFailed(format!("Cannot parse {path}: {e}")))
+    };
+
+    let old = load(base, file.before.as_deref())?;
+    let new = load(head, file.after.as_deref())?;
+    Ok(LockfileSummary {
+        kind,
+        changes: compare(&old, &new),
+    })
+}
+
+/// Compare two package lists. A package with exactly one version on each side
+/// is an upgrade/downgrade; otherwise each version is added or removed on its own.
+fn compare(old: &Packages, new: &Packages) -> Vec<DependencyChange> {
+    let empty = BTreeSet::new();
+    let names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    let mut changes = Vec::new();
+
+    for name in names {
+        let old_entries = old.get(name).unwrap_or(&empty);
+        let new_entries = new.get(name).unwrap_or(&empty);
+        let removed: Vec<_> = old_entries.difference(new_entries).collect();
+        let added: Vec<_> = new_entries.difference(old_entries).collect();
+
+        let entry = |kind,
+                     old: Option<&(String, Option<String>)>,
+                     new: Option<&(String, Option<String>)>| {
+            DependencyChange {
+                name: name.clone(),
+                kind,
+                old_version: old.map(|(v, _)| v.clone()),
+                new_version: new.map(|(v, _)| v.clone()),
+                old_source: old.and_then(|(_, s)| s.clone()),
+                new_source: new.and_then(|(_, s)| s.clone()),
+            }
+        };
+
+        if let ([from], [to]) = (removed.as_slice(), added.as_slice()) {
+            let kind = match compare_versions(&from.0, &to.0) {
+                Ordering::Less => DependencyChangeKind::Upgraded,
+                Ordering::Greater => DependencyChangeKind::Downgraded,
+                Ordering::Equal => DependencyChangeKind::SourceChanged,
+            };
+            changes.push(entry(kind, Some(from), Some(to)));
+            continue;
+        }
+        for from in removed {
+            changes.push(entry(DependencyChangeKind::Removed, Some(from), None));
+        }
+        for to in added {
+            changes.push(entry(DependencyChangeKind::Added, None, Some(to)));
+        }
+    }
+    changes
+}
+
+/// Compare dotted versions component by component, numerically where
+/// possible ("1.10.0" > "1.9.2"). As in semver, a pre-release sorts before
+/// its release ("1.0.0-beta.1" < "1.0.0") and build metadata is ignored.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (a_release, a_pre) = split_pre_release(a);
+    let (b_release, b_pre) = split_pre_release(b);
+
+    compare_dotted(a_release, b_release).then_with(|| match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a_pre), Some(b_pre)) => compare_dotted(a_pre, b_pre),
+    })
+}
+
+/// Split "1.0.0-beta.1+build" into ("1.0.0", Some("beta.1")).
+fn split_pre_release(version: &str) -> (&str, Option<&str>) {
+    let version = version.split_once('+').map_or(version, |(v, _)| v);
+    match version.split_once('-') {
+        Some((release, pre)) => (release, Some(pre)),
+        None => (version, None),
+    }
+}
+
+/// Compare dot-separated fields: numbers numerically and before words,
+/// and a prefix before anything longer.
+fn compare_dotted(a: &str, b: &str) -> Ordering {
+    let mut a_parts = a.split('.');
+    let mut b_parts = b.split('.');
+    loop {
+        match (a_parts.next(), b_parts.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => {
+                let ordering = match (x.parse::<u64>(), y.parse::<u64>()) {
+                    (Ok(x), Ok(y)) => x.cmp(&y),
+                    (Ok(_), Err(_)) => Ordering::Less,
+                    (Err(_), Ok(_)) => Ordering::Greater,
+                    _ => x.cmp(y),
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+        }
+    }
+}
+
+/// Cargo.lock and poetry.lock: `[[package]]` tables with name, version and source.
+fn parse_toml_packages(text: &str) -> Result<Packages, String> {
+    let table: toml::Table = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+    let mut packages = Packages::new();
+    let Some(toml::Value::Array(entries)) = table.get("package") else {
+        return Ok(packages);
+    };
+
+    for entry in entries {
+        let (Some(name), Some(version)) = (
+            entry.get("name").and_then(|v| v.as_str()),
+            entry.get("version").and_then(|v| v.as_str()),
+        ) else {
+            continue;
+        };
+        // Cargo: source = "registry+..." / "git+..."; Poetry: [package.source] url = ...
+        let source = match entry.get("source") {
+            Some(toml::Value::String(s)) => Some(s.clone()),
+            Some(toml::Value::Table(t)) => t.get("url").and_then(|u| u.as_str()).map(String::from),
+            _ => None,
+        };
+        packages
+            .entry(name.to_string())
+            .or_default()
+            .insert((version.to_string(), source));
+    }
+    Ok(packages)
+}
+
+/// package-lock.json: `packages` keyed by install path (v2/v3), or nested
+/// `dependencies` (v1).
+fn parse_package_lock(text: &str) -> Result<Packages, String> {
+    let json: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let mut packages = Packages::new();
+
+    if let Some(Value::Object(entries)) = json.get("packages") {
+        for (install_path, entry) in entries {
+            // "" is the root project; names follow the last node_modules/
+            let Some(name) = install_path.rsplit("node_modules/").next() else {
+                continue;
+            };
+            if install_path.is_empty() || entry.get("link").and_then(Value::as_bool) == Some(true) {
+                continue;
+            }
+            add_npm_entry(&mut packages, name, entry);
+        }
+    } else if let Some(Value::Object(deps)) = json.get("dependencies") {
+        add_npm_v1_dependencies(&mut packages, deps);
+    }
+    Ok(packages)
+}
+
+fn add_npm_v1_dependencies(packages: &mut Packages, deps: &serde_json::Map<String, Value>) {
+    for (name, entry) in deps {
+        add_npm_entry(packages, name, entry);
+        if let Some(Value::Object(nested)) = entry.get("dependencies") {
+            add_npm_v1_dependencies(packages, nested);
+        }
+    }
+}
+
+fn add_npm_entry(packages: &mut Packages, name: &str, entry: &Value) {
+    let Some(version) = entry.get("version").and_then(Value::as_str) else {
+        return;
+    };
+    let source = entry
+        .get("resolved")
+        .and_then(Value::as_str)
+        .map(String::from);
+    packages
+        .entry(name.to_string())
+        .or_default()
+        .insert((version.to_string(), source));
+}
+
+/// yarn.lock, both the classic format (`version "1.2.3"`) and berry's
+/// YAML (`version: 1.2.3`). Entries start with an unindented line of
+/// comma-separated descriptors such as `"@scope/pkg@^1.0.0", pkg@~1.1:`.
+fn parse_yarn_lock(text: &str) -> Packages {
+    let mut packages = Packages::new();
+    let mut name: Option<String> = None;
+    let mut version: Option<String> = None;
+    let mut source: Option<String> = None;
+
+    let mut flush =
+        |name: &mut Option<String>, version: &mut Option<String>, source: &mut Option<String>| {
+            if let (Some(name), Some(version)) = (name.take(), version.take()) {
+                packages
+                    .entry(name)
+                    .or_default()
+                    .insert((version, source.take()));
+            }
+            *source = None;
+        };
+
+    for line in text.lines() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if !line.starts_with(' ') {
+            flush(&mut name, &mut version, &mut source);
+            let descriptor = line
+                .trim_end_matches(':')
+                .split(", ")
+                .next()
+                .unwrap_or("")
+                .trim_matches('"');
+            name = descriptor_name(descriptor);
+            continue;
+        }
+
+        let trimmed = line.trim();
+        let (key, value) = match trimmed.split_once(':') {
+            // Berry: `key: value`
+            Some((key, value)) if !key.contains(' ') => (key, value.trim()),
+            // Classic: `key "value"` or `key value`
+            _ => trimmed.split_once(' ').unwrap_or((trimmed, "")),
+        };
+        let value = value.trim().trim_matches('"');
+        match key {
+            "version" => version = Some(value.to_string()),
+            "resolved" | "resolution" => source = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    flush(&mut name, &mut version, &mut source);
+    packages
+}
+
+/// Package name of a yarn descriptor: everything before the version's `@`
+/// (scoped names start with `@` themselves).
+fn descriptor_name(descriptor: &str) -> Option<String> {
+    if descriptor.is_empty() || descriptor == "__metadata" {
+        return None;
+    }
+    let at = descriptor[1..].find('@').map(|i| i + 1)?;
+    Some(descriptor[..at].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    fn versions(
+        changes: &[DependencyChange],
+    ) -> Vec<(&str, DependencyChangeKind, Option<&str>, Option<&str>)> {
+        changes
+            .iter()
+            .map(|c| {
+                (
+                    c.name.as_str(),
+                    c.kind,
+                    c.old_version.as_deref(),
+                    c.new_version.as_deref(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("1.10.0", "1.9.2"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0.0", "1.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("0.9", "0.9.1"), Ordering::Less);
+        assert_eq!(compare_versions("1.0.0-beta.1", "1.0.0"), Ordering::Less);
+        assert_eq!(
+            compare_versions("1.0.0-beta.2", "1.0.0-beta.11"),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_versions("1.0.0-rc.1", "1.0.0-beta"),
+            Ordering::Greater
+        );
+        assert_eq!(compare_versions("1.0.0+abc", "1.0.0"), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_parse_yarn_lock() {
+        let classic = "# yarn lockfile v1\n\n\"@babel/core@^7.0.0\", \"@babel/core@^7.1.0\":\n  version \"7.2.0\"\n  resolved \"https://registry.yarnpkg.com/@babel/core.tgz\"\n\nleft-pad@^1.0.0:\n  version \"1.3.0\"\n";
+        let packages = parse_yarn_lock(classic);
+        assert_eq!(packages.len(), 2);
+        let (version, source) = packages["@babel/core"].iter().next().unwrap();
+        assert_eq!(version, "7.2.0");
+        assert!(source.as_deref().unwrap().starts_with("https://"));
+
+        let berry = "__metadata:\n  version: 6\n\n\"left-pad@npm:^1.0.0\":\n  version: 1.3.0\n  resolution: \"left-pad@npm:1.3.0\"\n";
+        let packages = parse_yarn_lock(berry);
+        assert_eq!(packages.keys().collect::<Vec<_>>(), vec!["left-pad"]);
+    }
+
+    #[test]
+    fn test_parse_package_lock() {
+        let lock = r#"{"lockfileVersion": 3, "packages": {
+            "": {"name": "app"},
+            "node_modules/a": {"version": "1.0.0", "resolved": "https://r/a"},
+            "node_modules/a/node_modules/b": {"version": "2.0.0"},
+            "node_modules/b": {"version": "3.0.0"}
+        }}"#;
+        let packages = parse_package_lock(lock).unwrap();
+        assert_eq!(packages["b"].len(), 2);
+        assert!(!packages.contains_key(""));
+    }
+
+    #[test]
+    fn test_cargo_lock_summary() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .args(args)
+                .current_dir(repo)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {args:?} failed");
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "test@test.com"]);
+        git(&["config", "user.name", "Test"]);
+
+        let package = |name: &str, version: &str| {
+            format!("[[package]]\nname = \"{name}\"\nversion = \"{version}\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n\n")
+        };
+        let old =
+            package("serde", "1.0.100") + &package("log", "0.4.20") + &package("old", "1.0.0");
+        std::fs::write(repo.join("Cargo.lock"), format!("version = 4\n\n{old}")).unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-m", "init"]);
+
+        let new =
+            package("serde", "1.0.200") + &package("log", "0.4.19") + &package("new", "0.1.0");
+        std::fs::write(repo.join("Cargo.lock"), format!("version = 4\n\n{new}")).unwrap();
+
+        let files = super::super::diff::list_diff_files(
+            repo,
+            &super::super::types::DiffSpec::uncommitted(),
+            &Default::default(),
+        )
+        .unwrap();
+        let summary = files[0].lockfile.as_ref().unwrap();
+        assert_eq!(summary.kind, LockfileKind::Cargo);
+        assert_eq!(
+            versions(&summary.changes),
+            vec![
+                (
+                    "log",
+                    DependencyChangeKind::Downgraded,
+                    Some("0.4.20"),
+                    Some("0.4.19")
+                ),
+                ("new", DependencyChangeKind::Added, None, Some("0.1.0")),
+                ("old", DependencyChangeKind::Removed, Some("1.0.0"), None),
+                (
+                    "serde",
+                    DependencyChangeKind::Upgraded,
+                    Some("1.0.100"),
+                    Some("1.0.200")
+                ),
+            ]
+        );
+        // Cached by blob ids, so an edit is summarized afresh
+        let repository = Repository::discover(repo).unwrap();
+        let head = blob_id(
+            &repository,
+            &GitRef::Rev("HEAD".into()),
+            files[0].before.as_deref(),
+        );
+        let key = (
+            LockfileKind::Cargo,
+            head.unwrap(),
+            Some(Oid::hash_file(ObjectType::Blob, repo.join("Cargo.lock")).unwrap()),
+        );
+        assert!(SUMMARY_CACHE
+            .read()
+            .unwrap()
+            .as_ref()
+            .unwrap()
+            .contains_key(&key));
+
+        let newer = new + &package("newer", "2.0.0");
+        std::fs::write(repo.join("Cargo.lock"), format!("version = 4\n\n{newer}")).unwrap();
+        let files = super::super::diff::list_diff_files(
+            repo,
+            &super::super::types::DiffSpec::uncommitted(),
+            &Default::default(),
+        )
+        .unwrap();
+        let changes = &files[0].lockfile.as_ref().unwrap().changes;
+        assert!(changes.iter().any(|c| c.name == "newer"));
+    }
+}
@@ -1,30 +1,70 @@
+mod attributes;
+mod binary;
 mod cli;
 mod commit;
 mod diff;
+mod encoding;
 mod files;
+mod filesystem;
 pub mod github;
+mod history;
+mod inline;
+mod lfs;
+mod lockfile;
+mod moves;
+mod notebook;
+mod patch;
+mod range_diff;
+mod reanchor;
 mod refs;
+mod stage;
+mod stash;
+mod structured;
+mod submodule;
 mod types;
+mod window;
 mod worktree;
 
+pub use binary::{
+    get_binary_pair, BinaryData, BinaryPair, BinarySide, BinaryTransport, MAX_BASE64_BYTES,
+};
 pub use cli::GitError;
-pub use commit::commit;
+pub use commit::{commit, commit_selection, CommitFile};
 pub use diff::{get_file_diff, get_unified_diff, list_diff_files};
-pub use files::{get_file_at_ref, search_files};
+pub use files::{
+    get_file_at_ref, search_content, search_files, ContentMatch, ContentSearchOptions,
+};
+pub use filesystem::content_hash;
 pub use github::{
     check_github_auth, create_pull_request, fetch_pr, get_pr_for_branch,
     invalidate_cache as invalidate_pr_cache, list_issues, list_pull_requests, push_branch,
     search_issues, search_pull_requests, sync_review_to_github, update_pull_request,
     CreatePrResult, GitHubAuthStatus, GitHubSyncResult, Issue, PullRequest, PullRequestInfo,
 };
+pub use history::{blame_file, get_file_history, Blame, BlameCommit, BlameLine, FileHistoryEntry};
+pub use moves::{detect_moves, MoveLocation, MoveOptions, MovedBlock};
+pub use notebook::{
+    get_notebook_diff, CellChange, CellKind, NotebookCellDiff, NotebookDiff, OutputSummary,
+};
+pub use range_diff::{
+    range_diff, RangeCommit, RangeDiff, RangeDiffEntry, RangeDiffFile, RangeDiffStatus,
+};
+pub use reanchor::{blob_at, closest_ancestor, line_hunks, map_line, LineHunk};
 pub use refs::{
     detect_default_branch, get_current_branch, get_repo_root, list_branches, list_refs, merge_base,
     resolve_ref, BranchRef,
 };
+pub use stage::{stage_selection, unstage_selection};
+pub use stash::{apply_stash, create_stash, drop_stash, list_stashes, pop_stash, StashEntry};
+pub use structured::{
+    get_structured_diff, StructuredChange, StructuredChangeKind, StructuredDiff, StructuredFormat,
+};
+pub use submodule::{get_submodule_diff, SubmoduleCommit, SubmoduleDiff};
 pub use types::*;
+pub use window::{get_file_diff_window, LARGE_DIFF_LINES};
 pub use worktree::{
     branch_exists, create_worktree, create_worktree_for_existing_branch, create_worktree_from_pr,
-    get_commits_since_base, get_head_sha, get_parent_commit, list_worktrees, remove_worktree,
-    reset_to_commit, switch_branch, update_branch_from_pr, worktree_path_for, CommitInfo,
-    UpdateFromPrResult,
+    get_commits_since_base, get_commits_with_parents, get_head_sha, get_parent_commit,
+    list_worktrees, remove_worktree, reset_to_commit, switch_branch, update_branch_from_pr,
+    worktree_path_for, CommitInfo, UpdateFromPrResult,
 };
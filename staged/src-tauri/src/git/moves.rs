@@ -0,0 +1,264 @@
+//! Moved-code detection across the files of a diff.
+//!
+//! A moved block shows up as a deletion in one place and an identical
+//! addition in another, possibly in a different file. `detect_moves` pairs
+//! these up so the viewer can link both ends instead of showing unrelated
+//! removed and added code.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::cli::GitError;
+use super::diff::{list_diff_files, load_file_diff};
+use super::types::{DiffOptions, DiffSpec, File, FileContent, FileDiff, Span};
+
+/// Lines that occur in more removed places than this don't start a match
+/// (e.g. lone closing braces), keeping the search linear in practice.
+const MAX_CANDIDATES: usize = 64;
+
+/// Options for move detection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MoveOptions {
+    /// Smallest block, in lines, reported as a move
+    pub min_lines: u32,
+    /// Match lines regardless of whitespace (e.g. re-indented code)
+    pub ignore_whitespace: bool,
+}
+
+impl Default for MoveOptions {
+    fn default() -> Self {
+        Self {
+            min_lines: 3,
+            ignore_whitespace: false,
+        }
+    }
+}
+
+/// One end of a moved block: a line range in a file's `FileDiff`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveLocation {
+    /// Path of the file, as in the diff's file list
+    pub path: String,
+    /// Lines in the `before` side (for the source) or `after` side (for the
+    /// destination) of that file's diff
+    pub lines: Span,
+}
+
+/// A block of lines removed in one place and added in another.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MovedBlock {
+    pub from: MoveLocation,
+    pub to: MoveLocation,
+}
+
+/// Find blocks moved within or between the files of a diff.
+/// Files that fail to load are logged and left out.
+pub fn detect_moves(
+    repo: &Path,
+    spec: &DiffSpec,
+    diff_options: &DiffOptions,
+    options: &MoveOptions,
+) -> Result<Vec<MovedBlock>, GitError> {
+    let files = list_diff_files(repo, spec, diff_options)?;
+
+    let mut diffs = Vec::new();
+    for file in files.iter().filter(|f| !f.binary) {
+        let path = file.path().to_string_lossy().to_string();
+        match load_file_diff(repo, spec, Path::new(&path), diff_options) {
+            Ok(diff) => diffs.push((path, diff)),
+            Err(e) => log::warn!("Skipping {path} in move detection: {e}"),
+        }
+    }
+
+    Ok(find_moves(&diffs, options))
+}
+
+/// A contiguous run of removed or added lines.
+struct Region<'a> {
+    file: usize,
+    start: u32,
+    lines: Vec<String>,
+    /// Original lines, for skipping blank anchors
+    raw: &'a [String],
+}
+
+/// Pair removed and added regions line by line, greedily taking the longest
+/// match at each added line.
+fn find_moves(diffs: &[(String, FileDiff)], options: &MoveOptions) -> Vec<MovedBlock> {
+    let normalize = |line: &str| {
+        if options.ignore_whitespace {
+            line.split_whitespace().collect::<String>()
+        } else {
+            line.to_string()
+        }
+    };
+
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    for (file, (_, diff)) in diffs.iter().enumerate() {
+        for alignment in diff.alignments.iter().filter(|a| a.changed) {
+            if let Some(region) = region(file, &diff.before, alignment.before, &normalize) {
+                removed.push(region);
+            }
+            if let Some(region) = region(file, &diff.after, alignment.after, &normalize) {
+                added.push(region);
+            }
+        }
+    }
+
+    // Normalized line -> (region, offset) of each removed occurrence
+    let mut index: HashMap<&str, Vec<(usize, usize)>> = HashMap::new();
+    for (r, region) in removed.iter().enumerate() {
+        for (offset, line) in region.lines.iter().enumerate() {
+            index.entry(line).or_default().push((r, offset));
+        }
+    }
+
+    let min_lines = options.min_lines.max(1) as usize;
+    let mut used: Vec<Vec<bool>> = removed.iter().map(|r| vec![false; r.lines.len()]).collect();
+    let mut moves = Vec::new();
+
+    for region in &added {
+        let mut i = 0;
+        while i < region.lines.len() {
+            let candidates = match index.get(region.lines[i].as_str()) {
+                Some(c) if c.len() <= MAX_CANDIDATES && !region.raw[i].trim().is_empty() => c,
+                _ => {
+                    i += 1;
+                    continue;
+                }
+            };
+
+            // Longest run of unused removed lines matching from here
+            let mut best: Option<(usize, usize, usize)> = None;
+            for &(r, offset) in candidates {
+                let source = &removed[r];
+                let len = (0..)
+                    .take_while(|&k| {
+                        i + k < region.lines.len()
+                            && offset + k < source.lines.len()
+                            && !used[r][offset + k]
+                            && region.lines[i + k] == source.lines[offset + k]
+                    })
+                    .count();
+                match best {
+                    Some((_, _, best_len)) if best_len >= len => {}
+                    _ => best = Some((r, offset, len)),
+                }
+            }
+
+            match best {
+                Some((r, offset, len)) if len >= min_lines => {
+                    used[r][offset..offset + len].fill(true);
+                    let source = &removed[r];
+                    moves.push(MovedBlock {
+                        from: MoveLocation {
+                            path: diffs[source.file].0.clone(),
+                            lines: Span::new(
+                                source.start + offset as u32,
+                                source.start + (offset + len) as u32,
+                            ),
+                        },
+                        to: MoveLocation {
+                            path: diffs[region.file].0.clone(),
+                            lines: Span::new(
+                                region.start + i as u32,
+                                region.start + (i + len) as u32,
+                            ),
+                        },
+                    });
+                    i += len;
+                }
+                _ => i += 1,
+            }
+        }
+    }
+
+    moves
+}
+
+/// The lines of `file` within `span`, normalized; None if empty or not text.
+fn region<'a>(
+    file_index: usize,
+    file: &'a Option<File>,
+    span: Span,
+    normalize: &impl Fn(&str) -> String,
+) -> Option<Region<'a>> {
+    let Some(File {
+        content: FileContent::Text { lines },
+        ..
+    }) = file
+    else {
+        return None;
+    };
+    let raw = lines.get(span.start as usize..span.end as usize)?;
+    if raw.is_empty() {
+        return None;
+    }
+    Some(Region {
+        file: file_index,
+        start: span.start,
+        lines: raw.iter().map(|l| normalize(l)).collect(),
+        raw,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    fn git(repo: &Path, args: &[&str]) {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(repo)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?} failed");
+    }
+
+    #[test]
+    fn test_detect_moves_across_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        git(repo, &["init"]);
+        git(repo, &["config", "user.email", "test@test.com"]);
+        git(repo, &["config", "user.name", "Test"]);
+
+        let helper = "fn helper() {\n    let x = 1;\n    x + 1\n}\n";
+        std::fs::write(repo.join("a.rs"), format!("fn main() {{}}\n\n{helper}")).unwrap();
+        std::fs::write(repo.join("b.rs"), "// b\n").unwrap();
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-m", "init"]);
+
+        // Move helper into b.rs, re-indented
+        std::fs::write(repo.join("a.rs"), "fn main() {}\n").unwrap();
+        let indented: String = helper.lines().map(|l| format!("  {l}\n")).collect();
+        std::fs::write(
+            repo.join("b.rs"),
+            format!("// b\nmod inner {{\n{indented}}}\n"),
+        )
+        .unwrap();
+
+        let spec = DiffSpec::uncommitted();
+        let options = DiffOptions::default();
+        let exact = detect_moves(repo, &spec, &options, &MoveOptions::default()).unwrap();
+        assert!(exact.is_empty());
+
+        let loose = MoveOptions {
+            ignore_whitespace: true,
+            ..Default::default()
+        };
+        let moves = detect_moves(repo, &spec, &options, &loose).unwrap();
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].from.path, "a.rs");
+        assert_eq!(moves[0].from.lines, Span::new(2, 6));
+        assert_eq!(moves[0].to.path, "b.rs");
+        assert_eq!(moves[0].to.lines, Span::new(2, 6));
+    }
+}
@@ -0,0 +1,563 @@
+//! Cell-aware diffs of Jupyter notebooks.
+//!
+//! Notebooks are JSON, so a line diff is mostly execution counts, output
+//! blobs and metadata. `get_notebook_diff` parses both sides into cells,
+//! aligns them, diffs each cell's source like a file, and summarizes outputs
+//! (text excerpts, and size/hash/dimensions for images and other data)
+//! instead of showing them inline.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use base64::Engine;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::binary::binary_info;
+use super::cli::GitError;
+use super::diff::{align_texts, load_file_diff};
+use super::inline;
+use super::types::{BinaryInfo, DiffOptions, DiffSpec, File, FileContent, FileDiff};
+
+/// Output text beyond this many characters is truncated in summaries.
+const MAX_OUTPUT_TEXT: usize = 2000;
+
+/// Cell type, as in the notebook's `cell_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CellKind {
+    Code,
+    Markdown,
+    Raw,
+}
+
+/// How a cell changed between the two sides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CellChange {
+    Added,
+    Removed,
+    /// The cell's source changed
+    Modified,
+    /// Same source; outputs, metadata or execution count may still differ
+    Unchanged,
+}
+
+/// A cell output, summarized.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputSummary {
+    /// `stream`, `execute_result`, `display_data` or `error`
+    pub output_type: String,
+    /// Stream text, `text/plain` data, or `ename: evalue` for errors
+    /// (truncated to a couple of thousand characters)
+    pub text: Option<String>,
+    /// Other data (images, HTML, ...), by MIME type, without the content
+    pub data: Vec<BinaryInfo>,
+}
+
+/// One aligned pair of cells.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotebookCellDiff {
+    pub kind: CellKind,
+    pub change: CellChange,
+    /// Cell id (nbformat 4.5+), from the new side if present
+    pub id: Option<String>,
+    /// Position in the old notebook (None if added)
+    pub before_index: Option<u32>,
+    /// Position in the new notebook (None if removed)
+    pub after_index: Option<u32>,
+    /// The cell source, diffed as a file
+    pub source: FileDiff,
+    pub before_execution_count: Option<u64>,
+    pub after_execution_count: Option<u64>,
+    pub before_outputs: Vec<OutputSummary>,
+    pub after_outputs: Vec<OutputSummary>,
+    pub outputs_changed: bool,
+    pub metadata_changed: bool,
+}
+
+/// Cell-by-cell diff of a notebook, as an alternative to its `FileDiff`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotebookDiff {
+    /// Kernel language (e.g. `python`), from the new side if present
+    pub language: Option<String>,
+    /// Notebook-level metadata (kernel, language info, ...) changed
+    pub metadata_changed: bool,
+    pub cells: Vec<NotebookCellDiff>,
+}
+
+/// Diff a `.ipynb` file cell by cell.
+///
+/// Returns None for other files, and an error if either side isn't an
+/// nbformat 4 notebook.
+pub fn get_notebook_diff(
+    repo: &Path,
+    spec: &DiffSpec,
+    path: &Path,
+    options: &DiffOptions,
+) -> Result<Option<NotebookDiff>, GitError> {
+    let is_notebook = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("ipynb"));
+    if !is_notebook {
+        return Ok(None);
+    }
+
+    let diff = load_file_diff(repo, spec, path, &DiffOptions::default())?;
+    let before = parse_side(&diff.before, "old")?;
+    let after = parse_side(&diff.after, "new")?;
+    let path = path.to_string_lossy();
+
+    let empty = Notebook::default();
+    let (old, new) = (
+        before.as_ref().unwrap_or(&empty),
+        after.as_ref().unwrap_or(&empty),
+    );
+
+    let mut cells = Vec::new();
+    for (before_index, after_index) in align_cells(&old.cells, &new.cells) {
+        let old_cell = before_index.map(|i| &old.cells[i]);
+        let new_cell = after_index.map(|i| &new.cells[i]);
+        cells.push(diff_cell(
+            &path,
+            (before_index, old_cell),
+            (after_index, new_cell),
+            options,
+        )?);
+    }
+
+    Ok(Some(NotebookDiff {
+        language: new.language.clone().or_else(|| old.language.clone()),
+        metadata_changed: before.is_some() && after.is_some() && old.metadata != new.metadata,
+        cells,
+    }))
+}
+
+#[derive(Default)]
+struct Notebook {
+    language: Option<String>,
+    metadata: Value,
+    cells: Vec<Cell>,
+}
+
+struct Cell {
+    kind: CellKind,
+    id: Option<String>,
+    source: Vec<String>,
+    execution_count: Option<u64>,
+    outputs: Vec<OutputSummary>,
+    metadata: Value,
+}
+
+fn parse_side(file: &Option<File>, side: &str) -> Result<Option<Notebook>, GitError> {
+    match file {
+        None => Ok(None),
+        Some(File {
+            content: FileContent::Text { lines },
+            ..
+        }) => parse_notebook(&lines.join("\n"))
+            .map(Some)
+            .map_err(|e| GitError::CommandFailed(format!("Cannot parse {side} notebook: {e}"))),
+        Some(_) => Err(GitError::CommandFailed(format!(
+            "The {side} notebook is not text"
+        ))),
+    }
+}
+
+fn parse_notebook(text: &str) -> Result<Notebook, String> {
+    let json: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    if json.get("nbformat").and_then(Value::as_u64) != Some(4) {
+        return Err("only nbformat 4 notebooks are supported".to_string());
+    }
+
+    let metadata = json.get("metadata").cloned().unwrap_or(Value::Null);
+    let language = metadata
+        .pointer("/kernelspec/language")
+        .or_else(|| metadata.pointer("/language_info/name"))
+        .and_then(Value::as_str)
+        .map(String::from);
+
+    let cells = json
+        .get("cells")
+        .and_then(Value::as_array)
+        .ok_or("missing cells")?
+        .iter()
+        .map(parse_cell)
+        .collect();
+
+    Ok(Notebook {
+        language,
+        metadata,
+        cells,
+    })
+}
+
+fn parse_cell(cell: &Value) -> Cell {
+    let kind = match cell.get("cell_type").and_then(Value::as_str) {
+        Some("code") => CellKind::Code,
+        Some("markdown") => CellKind::Markdown,
+        _ => CellKind::Raw,
+    };
+    let outputs = cell
+        .get("outputs")
+        .and_then(Value::as_array)
+        .map(|outputs| outputs.iter().map(summarize_output).collect())
+        .unwrap_or_default();
+
+    Cell {
+        kind,
+        id: cell.get("id").and_then(Value::as_str).map(String::from),
+        source: multiline(cell.get("source"))
+            .lines()
+            .map(String::from)
+            .collect(),
+        execution_count: cell.get("execution_count").and_then(Value::as_u64),
+        outputs,
+        metadata: cell.get("metadata").cloned().unwrap_or(Value::Null),
+    }
+}
+
+/// nbformat stores text as a string or a list of lines.
+fn multiline(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(parts)) => parts.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+fn summarize_output(output: &Value) -> OutputSummary {
+    let output_type = output
+        .get("output_type")
+        .and_then(Value::as_str)
+        .unwrap_or("unknown")
+        .to_string();
+
+    let mut text = None;
+    let mut data = Vec::new();
+    match output_type.as_str() {
+        "stream" => text = Some(multiline(output.get("text"))),
+        "error" => {
+            let field = |key| output.get(key).and_then(Value::as_str).unwrap_or("");
+            text = Some(format!("{}: {}", field("ename"), field("evalue")));
+        }
+        _ => {
+            let bundle = output.get("data").and_then(Value::as_object);
+            for (mime, value) in bundle.into_iter().flatten() {
+                if mime == "text/plain" {
+                    text = Some(multiline(Some(value)));
+                    continue;
+                }
+                // Binary data is base64 (possibly wrapped); JSON data is a value
+                let content = match value {
+                    Value::String(_) | Value::Array(_) => multiline(Some(value)),
+                    other => other.to_string(),
+                };
+                let bytes = if mime.starts_with("image/") && mime != "image/svg+xml" {
+                    let packed: String = content.split_whitespace().collect();
+                    base64::engine::general_purpose::STANDARD
+                        .decode(packed)
+                        .unwrap_or_else(|_| content.into_bytes())
+                } else {
+                    content.into_bytes()
+                };
+                let mut info = binary_info("", &bytes);
+                info.mime = mime.clone();
+                data.push(info);
+            }
+        }
+    }
+
+    OutputSummary {
+        output_type,
+        text: text.map(|t| truncate(&t)),
+        data,
+    }
+}
+
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(MAX_OUTPUT_TEXT) {
+        Some((at, _)) => format!("{}…", &text[..at]),
+        None => text.to_string(),
+    }
+}
+
+/// Pair up cells: the longest common subsequence of matching cells (same
+/// id when both sides have ids, else same type and source), then, without
+/// ids, cells of the same type and similar source left between matches, in
+/// order. Other cells are removed or added. Returns (before, after) indices
+/// in display order.
+fn align_cells(old: &[Cell], new: &[Cell]) -> Vec<(Option<usize>, Option<usize>)> {
+    let by_id = old.iter().chain(new).all(|c| c.id.is_some());
+    let same = |a: &Cell, b: &Cell| {
+        if by_id {
+            a.id == b.id
+        } else {
+            a.kind == b.kind && a.source == b.source
+        }
+    };
+    // Cells with different ids are different cells, however similar
+    let edited =
+        |a: &Cell, b: &Cell| !by_id && a.kind == b.kind && similarity(&a.source, &b.source) >= 0.5;
+
+    // lcs[i][j]: LCS length of old[i..] and new[j..]
+    let mut lcs = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if same(&old[i], &new[j]) {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut anchors = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if same(&old[i], &new[j]) {
+            anchors.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    anchors.push((old.len(), new.len()));
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for (anchor_i, anchor_j) in anchors {
+        // Gap: pair edited cells positionally, the rest stand alone
+        while i < anchor_i || j < anchor_j {
+            if i < anchor_i && j < anchor_j && edited(&old[i], &new[j]) {
+                pairs.push((Some(i), Some(j)));
+                i += 1;
+                j += 1;
+            } else if i < anchor_i
+                && (j == anchor_j || !new[j..anchor_j].iter().any(|c| edited(&old[i], c)))
+            {
+                pairs.push((Some(i), None));
+                i += 1;
+            } else {
+                pairs.push((None, Some(j)));
+                j += 1;
+            }
+        }
+        if anchor_i < old.len() {
+            pairs.push((Some(anchor_i), Some(anchor_j)));
+            i = anchor_i + 1;
+            j = anchor_j + 1;
+        }
+    }
+    pairs
+}
+
+/// Fraction of whitespace-separated tokens two sources have in common.
+fn similarity(a: &[String], b: &[String]) -> f64 {
+    let tokens = |source: &[String]| {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for token in source.iter().flat_map(|line| line.split_whitespace()) {
+            *counts.entry(token.to_string()).or_default() += 1;
+        }
+        counts
+    };
+    let (a, b) = (tokens(a), tokens(b));
+    let total = a.values().sum::<usize>().max(b.values().sum::<usize>());
+    if total == 0 {
+        return 1.0;
+    }
+    let common: usize = a
+        .iter()
+        .map(|(token, n)| (*n).min(b.get(token).copied().unwrap_or(0)))
+        .sum();
+    common as f64 / total as f64
+}
+
+fn diff_cell(
+    path: &str,
+    (before_index, old): (Option<usize>, Option<&Cell>),
+    (after_index, new): (Option<usize>, Option<&Cell>),
+    options: &DiffOptions,
+) -> Result<NotebookCellDiff, GitError> {
+    let source_file = |cell: Option<&Cell>| {
+        cell.map(|c| {
+            File::new(
+                path,
+                FileContent::Text {
+                    lines: c.source.clone(),
+                },
+            )
+        })
+    };
+    let before = source_file(old);
+    let after = source_file(new);
+    let alignments = align_texts(&before, &after, options)?;
+    let inline_changes =
+        inline::compute_inline_changes(&before, &after, &alignments, options.inline_tokenizer);
+
+    let change = match (old, new) {
+        (None, _) => CellChange::Added,
+        (_, None) => CellChange::Removed,
+        (Some(a), Some(b)) if a.source != b.source => CellChange::Modified,
+        _ => CellChange::Unchanged,
+    };
+    let outputs = |cell: Option<&Cell>| cell.map(|c| c.outputs.clone()).unwrap_or_default();
+    let (before_outputs, after_outputs) = (outputs(old), outputs(new));
+    let both = old.zip(new);
+
+    Ok(NotebookCellDiff {
+        kind: new.or(old).map_or(CellKind::Raw, |c| c.kind),
+        change,
+        id: new.or(old).and_then(|c| c.id.clone()),
+        before_index: before_index.map(|i| i as u32),
+        after_index: after_index.map(|i| i as u32),
+        source: FileDiff {
+            before,
+            after,
+            alignments,
+            inline_changes,
+            highlights: None,
+        },
+        before_execution_count: old.and_then(|c| c.execution_count),
+        after_execution_count: new.and_then(|c| c.execution_count),
+        outputs_changed: both.is_some() && before_outputs != after_outputs,
+        before_outputs,
+        after_outputs,
+        metadata_changed: both.is_some_and(|(a, b)| a.metadata != b.metadata),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::process::Command;
+
+    fn notebook(cells: Value) -> String {
+        serde_json::to_string_pretty(&json!({
+            "nbformat": 4,
+            "nbformat_minor": 4,
+            "metadata": {"kernelspec": {"language": "python", "name": "python3"}},
+            "cells": cells,
+        }))
+        .unwrap()
+    }
+
+    fn code(source: &str, count: u64, output: &str) -> Value {
+        json!({
+            "cell_type": "code",
+            "source": source,
+            "execution_count": count,
+            "metadata": {},
+            "outputs": [{"output_type": "stream", "name": "stdout", "text": [output]}],
+        })
+    }
+
+    #[test]
+    fn test_notebook_diff() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .args(args)
+                .current_dir(repo)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {args:?} failed");
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "test@test.com"]);
+        git(&["config", "user.name", "Test"]);
+
+        let title = json!({"cell_type": "markdown", "source": "# Title", "metadata": {}});
+        std::fs::write(
+            repo.join("a.ipynb"),
+            notebook(json!([
+                title,
+                code("x = 1\nprint(x)", 1, "1\n"),
+                code("y = 2", 2, ""),
+            ])),
+        )
+        .unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-m", "init"]);
+
+        // Rerun (new counts), edit the second cell, drop the third, add a plot
+        let png = base64::engine::general_purpose::STANDARD
+            .encode(b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR\x00\x00\x00\x10\x00\x00\x00\x08");
+        let plot = json!({
+            "cell_type": "code",
+            "source": "plot()",
+            "execution_count": 3,
+            "metadata": {},
+            "outputs": [{"output_type": "display_data", "metadata": {},
+                "data": {"image/png": png, "text/plain": "<Figure>"}}],
+        });
+        std::fs::write(
+            repo.join("a.ipynb"),
+            notebook(json!([title, code("x = 10\nprint(x)", 5, "10\n"), plot])),
+        )
+        .unwrap();
+
+        let diff = get_notebook_diff(
+            repo,
+            &DiffSpec::uncommitted(),
+            Path::new("a.ipynb"),
+            &DiffOptions::default(),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(diff.language.as_deref(), Some("python"));
+        assert!(!diff.metadata_changed);
+
+        let changes: Vec<_> = diff.cells.iter().map(|c| (c.kind, c.change)).collect();
+        assert_eq!(
+            changes,
+            vec![
+                (CellKind::Markdown, CellChange::Unchanged),
+                (CellKind::Code, CellChange::Modified),
+                (CellKind::Code, CellChange::Removed),
+                (CellKind::Code, CellChange::Added),
+            ]
+        );
+
+        let edited = &diff.cells[1];
+        assert_eq!(edited.before_execution_count, Some(1));
+        assert_eq!(edited.after_execution_count, Some(5));
+        assert!(edited.outputs_changed);
+        assert_eq!(edited.after_outputs[0].text.as_deref(), Some("10\n"));
+        // Only the first source line changed
+        let changed: Vec<_> = edited
+            .source
+            .alignments
+            .iter()
+            .filter(|a| a.changed)
+            .collect();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].after.len(), 1);
+
+        let plot = &diff.cells[3];
+        let output = &plot.after_outputs[0];
+        assert_eq!(output.text.as_deref(), Some("<Figure>"));
+        assert_eq!(output.data[0].mime, "image/png");
+        assert_eq!(
+            output.data[0].image.map(|i| (i.width, i.height)),
+            Some((16, 8))
+        );
+
+        let other = get_notebook_diff(
+            repo,
+            &DiffSpec::uncommitted(),
+            Path::new("a.json"),
+            &DiffOptions::default(),
+        )
+        .unwrap();
+        assert!(other.is_none());
+    }
+}
@@ -0,0 +1,580 @@
+//! Patch, diff and mbox files viewed as a changeset.
+//!
+//! A `DiffSpec` whose head is `GitRef::Patch` shows the changes in a patch
+//! file. When the patch's base is available, it's applied to real trees and
+//! the result is diffed like any commit range, with full file context:
+//! - base `Rev`: applied on top of that commit's tree
+//! - base the same `Patch`: applied to a tree of the pre-image blobs named in
+//!   its `index` lines, when they're all in the repository
+//!
+//! Otherwise (no repository, unknown blobs, or a patch git can't parse),
+//! only the hunks are known: `list_files`/`load_file_diff` build summaries
+//! and diffs from the hunks alone, with `hunks_only` set.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use git2::{Oid, Repository};
+
+use super::binary::binary_info;
+use super::cli::GitError;
+use super::diff::{align_hunks, parse_hunk_header};
+use super::types::{DiffSpec, File, FileContent, FileDiff, FileDiffSummary, GitRef};
+
+/// Default mode for pre-image blobs whose `index` line doesn't give one.
+const REGULAR_FILE_MODE: u32 = 0o100644;
+
+/// Hunk-only sides are blank-padded up to their last hunk; patches whose
+/// hunks claim to reach further than this are refused rather than padded.
+const MAX_HUNKS_ONLY_LINES: u32 = 1_000_000;
+
+/// How a patch (or, from `diff::resolve_view`, any spec) can be shown.
+pub(super) enum PatchView {
+    /// Applied, or not a patch: diff these resolved refs
+    Trees(DiffSpec),
+    /// Not applicable: just the hunks, one entry per file
+    HunksOnly(Vec<FilePatch>),
+}
+
+/// One file's section of a patch.
+#[derive(Debug, Clone, Default)]
+pub(super) struct FilePatch {
+    /// None if the patch creates the file
+    old_path: Option<String>,
+    /// None if the patch deletes the file
+    new_path: Option<String>,
+    /// Abbreviated pre-image blob id, from the `index` line
+    old_blob: Option<String>,
+    old_mode: Option<u32>,
+    binary: bool,
+    hunks: Vec<PatchHunk>,
+    /// The section's raw text, for handing to git
+    text: String,
+}
+
+#[derive(Debug, Clone)]
+struct PatchHunk {
+    /// 1-indexed start and line count, as in the hunk header
+    old_start: u32,
+    old_lines: u32,
+    new_start: u32,
+    new_lines: u32,
+    /// Lines with their ` `/`-`/`+` prefix
+    lines: Vec<String>,
+}
+
+impl FilePatch {
+    fn path(&self) -> &str {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or_default()
+    }
+}
+
+/// Parse `file` and work out how to show it against `base` (already
+/// resolved: a `Rev`, or a `Patch` for "the patch's own base").
+pub(super) fn open(repo_path: &Path, base: &GitRef, file: &Path) -> Result<PatchView, GitError> {
+    let bytes = std::fs::read(file).map_err(|e| {
+        GitError::CommandFailed(format!("Cannot read patch {}: {e}", file.display()))
+    })?;
+    let messages = parse(&String::from_utf8_lossy(&bytes))?;
+    if messages.iter().all(|m| m.is_empty()) {
+        return Err(GitError::CommandFailed(format!(
+            "{} contains no diffs",
+            file.display()
+        )));
+    }
+
+    let base_tree = match base {
+        GitRef::Rev(rev) => {
+            let repo =
+                Repository::discover(repo_path).map_err(|e| GitError::NotARepo(e.to_string()))?;
+            let tree = repo
+                .revparse_single(rev)
+                .and_then(|obj| obj.peel_to_tree())
+                .map_err(|e| GitError::CommandFailed(format!("Cannot resolve '{rev}': {e}")))?;
+            let head = apply(&repo, tree.id(), &messages)?;
+            return Ok(PatchView::Trees(DiffSpec::custom(
+                GitRef::Rev(rev.clone()),
+                GitRef::Rev(head.to_string()),
+            )));
+        }
+        GitRef::Patch(own) if Path::new(own) == file => Repository::discover(repo_path)
+            .ok()
+            .and_then(|repo| Some((pre_image_tree(&repo, &messages)?, repo))),
+        _ => {
+            return Err(GitError::CommandFailed(
+                "A patch can only be diffed against itself or a base rev".to_string(),
+            ))
+        }
+    };
+
+    // Applying to the pre-image blobs is best-effort: fall back to the hunks
+    if let Some((tree, repo)) = base_tree {
+        if let Ok(head) = apply(&repo, tree, &messages) {
+            return Ok(PatchView::Trees(DiffSpec::custom(
+                GitRef::Rev(tree.to_string()),
+                GitRef::Rev(head.to_string()),
+            )));
+        }
+    }
+    Ok(PatchView::HunksOnly(latest_per_file(messages)))
+}
+
+/// Apply each message's diff in turn, starting from `tree`.
+fn apply(repo: &Repository, tree: Oid, messages: &[Vec<FilePatch>]) -> Result<Oid, GitError> {
+    let apply_err = |e: git2::Error| GitError::CommandFailed(format!("Patch does not apply: {e}"));
+
+    let mut tree = tree;
+    for files in messages.iter().filter(|files| !files.is_empty()) {
+        let text: String = files.iter().map(|f| f.text.as_str()).collect();
+        let diff = git2::Diff::from_buffer(text.as_bytes()).map_err(apply_err)?;
+        let base = repo.find_tree(tree).map_err(apply_err)?;
+        let mut index = repo.apply_to_tree(&base, &diff, None).map_err(apply_err)?;
+        tree = index.write_tree_to(repo).map_err(apply_err)?;
+    }
+    Ok(tree)
+}
+
+/// A tree holding the pre-image of every file the patch series touches,
+/// or None if any pre-image blob isn't in the repository.
+fn pre_image_tree(repo: &Repository, messages: &[Vec<FilePatch>]) -> Option<Oid> {
+    let mut index = git2::Index::new().ok()?;
+    let mut seen = std::collections::HashSet::new();
+
+    for file in messages.iter().flatten() {
+        // Only a path's first appearance says what the base must contain
+        let earlier = file.old_path.as_ref().is_some_and(|p| seen.contains(p));
+        seen.extend(file.old_path.clone());
+        seen.extend(file.new_path.clone());
+        let Some(old_path) = file.old_path.as_ref().filter(|_| !earlier) else {
+            continue;
+        };
+        let blob = repo
+            .revparse_single(file.old_blob.as_deref()?)
+            .ok()?
+            .peel_to_blob()
+            .ok()?;
+        index
+            .add(&git2::IndexEntry {
+                ctime: git2::IndexTime::new(0, 0),
+                mtime: git2::IndexTime::new(0, 0),
+                dev: 0,
+                ino: 0,
+                mode: file.old_mode.unwrap_or(REGULAR_FILE_MODE),
+                uid: 0,
+                gid: 0,
+                file_size: 0,
+                id: blob.id(),
+                flags: 0,
+                flags_extended: 0,
+                path: old_path.as_bytes().to_vec(),
+            })
+            .ok()?;
+    }
+    index.write_tree_to(repo).ok()
+}
+
+/// For hunk-only views, one patch per file: a file changed by several
+/// messages of a series shows its last change.
+fn latest_per_file(messages: Vec<Vec<FilePatch>>) -> Vec<FilePatch> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for file in messages.into_iter().flatten() {
+        match positions.get(file.path()) {
+            Some(&i) => files[i] = file,
+            None => {
+                positions.insert(file.path().to_string(), files.len());
+                files.push(file);
+            }
+        }
+    }
+    files
+}
+
+/// File summaries for a hunk-only patch.
+pub(super) fn list_files(patches: &[FilePatch]) -> Vec<FileDiffSummary> {
+    patches
+        .iter()
+        .map(|patch| {
+            let mut summary = FileDiffSummary::new(
+                patch.old_path.as_ref().map(PathBuf::from),
+                patch.new_path.as_ref().map(PathBuf::from),
+            );
+            summary.hunks_only = true;
+            summary.binary = patch.binary;
+            if !patch.binary {
+                let lines = patch.hunks.iter().flat_map(|h| &h.lines);
+                let count = |prefix| lines.clone().filter(|l| l.starts_with(prefix)).count();
+                summary.additions = Some(count('+') as u32);
+                summary.deletions = Some(count('-') as u32);
+            }
+            summary
+        })
+        .collect()
+}
+
+/// A file's diff built from its hunks alone. Sides span up to the end of
+/// the last hunk; lines outside the hunks are unknown and left blank, so
+/// line numbers (and comments on them) match the real files.
+pub(super) fn load_file_diff(patches: &[FilePatch], path: &Path) -> Result<FileDiff, GitError> {
+    let path = path.to_string_lossy();
+    let patch = patches
+        .iter()
+        .find(|p| p.path() == path || p.old_path.as_deref() == Some(&*path))
+        .ok_or_else(|| GitError::CommandFailed(format!("{path} is not in the patch")))?;
+
+    if patch.binary {
+        let side = |present: bool| {
+            present.then(|| File::new(&*path, FileContent::Binary(binary_info(&path, &[]))))
+        };
+        return Ok(FileDiff {
+            before: side(patch.old_path.is_some()),
+            after: side(patch.new_path.is_some()),
+            alignments: vec![],
+            inline_changes: vec![],
+            highlights: None,
+        });
+    }
+
+    let malformed = || GitError::CommandFailed(format!("Malformed hunk for {path} in patch"));
+    // 0-indexed start of a hunk side; empty sides start after the given line
+    let start = |line: u32, count: u32| match count {
+        0 => Ok(line),
+        _ => line.checked_sub(1).ok_or_else(malformed),
+    };
+    let (mut before_len, mut after_len) = (0u32, 0u32);
+    for hunk in &patch.hunks {
+        let old_end = start(hunk.old_start, hunk.old_lines)?.checked_add(hunk.old_lines);
+        let new_end = start(hunk.new_start, hunk.new_lines)?.checked_add(hunk.new_lines);
+        before_len = before_len.max(old_end.ok_or_else(malformed)?);
+        after_len = after_len.max(new_end.ok_or_else(malformed)?);
+    }
+    if before_len.max(after_len) > MAX_HUNKS_ONLY_LINES {
+        return Err(GitError::CommandFailed(format!(
+            "Hunks for {path} reach past line {MAX_HUNKS_ONLY_LINES}"
+        )));
+    }
+    let mut before = vec![String::new(); before_len as usize];
+    let mut after = vec![String::new(); after_len as usize];
+    let set = |lines: &mut Vec<String>, index: u32, text: &str| {
+        *lines.get_mut(index as usize).ok_or_else(malformed)? = text.to_string();
+        Ok::<_, GitError>(())
+    };
+
+    // Fill in the known lines and split hunks into runs of changes
+    let mut changes = Vec::new();
+    for hunk in &patch.hunks {
+        let mut old = start(hunk.old_start, hunk.old_lines)?;
+        let mut new = start(hunk.new_start, hunk.new_lines)?;
+        let mut run: Option<(u32, u32)> = None;
+        for line in &hunk.lines {
+            let mut chars = line.chars();
+            let prefix = chars.next();
+            let text = chars.as_str();
+            match prefix {
+                Some('-') => {
+                    run.get_or_insert((old, new));
+                    set(&mut before, old, text)?;
+                    old += 1;
+                }
+                Some('+') => {
+                    run.get_or_insert((old, new));
+                    set(&mut after, new, text)?;
+                    new += 1;
+                }
+                _ => {
+                    if let Some((run_old, run_new)) = run.take() {
+                        changes.push((run_old, old - run_old, run_new, new - run_new));
+                    }
+                    set(&mut before, old, text)?;
+                    set(&mut after, new, text)?;
+                    old += 1;
+                    new += 1;
+                }
+            }
+        }
+        if let Some((run_old, run_new)) = run {
+            changes.push((run_old, old - run_old, run_new, new - run_new));
+        }
+    }
+
+    let side = |present: bool, lines: Vec<String>| {
+        present.then(|| File::new(&*path, FileContent::Text { lines }))
+    };
+    let before = side(patch.old_path.is_some(), before);
+    let after = side(patch.new_path.is_some(), after);
+    let alignments = align_hunks(&changes, &before, &after);
+    Ok(FileDiff {
+        before,
+        after,
+        alignments,
+        inline_changes: vec![],
+        highlights: None,
+    })
+}
+
+/// Split a patch into messages (mbox `From ` separators) of file sections.
+/// Text outside file sections (headers, commit messages, signatures) is
+/// skipped. Hunks whose lines don't match their header are an error.
+fn parse(text: &str) -> Result<Vec<Vec<FilePatch>>, GitError> {
+    let mut messages: Vec<Vec<FilePatch>> = vec![vec![]];
+    let mut current: Option<FilePatch> = None;
+    // Lines left in the current hunk: (old, new)
+    let mut remaining = (0u32, 0u32);
+
+    let finish = |messages: &mut Vec<Vec<FilePatch>>, current: &mut Option<FilePatch>| {
+        if let Some(file) = current.take() {
+            messages.last_mut().unwrap().push(file);
+        }
+    };
+    let malformed =
+        |file: &FilePatch| GitError::CommandFailed(format!("Malformed hunk for {}", file.path()));
+
+    for raw in text.split_inclusive('\n') {
+        let line = raw.trim_end_matches(['\n', '\r']);
+
+        if remaining != (0, 0) {
+            if let Some(file) = current.as_mut() {
+                file.text.push_str(raw);
+                let (old, new) = remaining;
+                let left = match line.chars().next() {
+                    Some('-') => old.checked_sub(1).map(|old| (old, new)),
+                    Some('+') => new.checked_sub(1).map(|new| (old, new)),
+                    Some('\\') => continue,
+                    // Context lines of empty content may have lost their space
+                    None | Some(' ') => old.checked_sub(1).zip(new.checked_sub(1)),
+                    _ => None,
+                };
+                remaining = left.ok_or_else(|| malformed(file))?;
+                let line = if line.is_empty() { " " } else { line };
+                file.hunks.last_mut().unwrap().lines.push(line.to_string());
+            }
+            continue;
+        }
+
+        if line.starts_with("From ") {
+            finish(&mut messages, &mut current);
+            messages.push(vec![]);
+        } else if let Some(paths) = line.strip_prefix("diff --git ") {
+            finish(&mut messages, &mut current);
+            let (old, new) = split_git_paths(paths);
+            current = Some(FilePatch {
+                old_path: old,
+                new_path: new,
+                text: raw.to_string(),
+                ..Default::default()
+            });
+        } else if line.starts_with("--- ") && !current.as_ref().is_some_and(|f| f.hunks.is_empty())
+        {
+            // A plain unified diff without a `diff --git` header
+            finish(&mut messages, &mut current);
+            current = Some(FilePatch {
+                old_path: strip_prefix(&line[4..]),
+                text: raw.to_string(),
+                ..Default::default()
+            });
+        } else if let Some(file) = current.as_mut() {
+            if line == "-- " {
+                // format-patch signature: the message's diff is over
+                finish(&mut messages, &mut current);
+                continue;
+            }
+            file.text.push_str(raw);
+            if let Some((old_start, old_lines, new_start, new_lines)) = parse_hunk_header(line) {
+                if (old_start == 0 && old_lines > 0) || (new_start == 0 && new_lines > 0) {
+                    return Err(malformed(file));
+                }
+                file.hunks.push(PatchHunk {
+                    old_start,
+                    old_lines,
+                    new_start,
+                    new_lines,
+                    lines: vec![],
+                });
+                remaining = (old_lines, new_lines);
+            } else if line.starts_with("new file mode") {
+                file.old_path = None;
+            } else if line.starts_with("deleted file mode") {
+                file.new_path = None;
+            } else if let Some(mode) = line.strip_prefix("old mode ") {
+                file.old_mode = u32::from_str_radix(mode.trim(), 8).ok();
+            } else if let Some(index) = line.strip_prefix("index ") {
+                let (range, mode) = index.split_once(' ').unwrap_or((index, ""));
+                file.old_blob = range.split_once("..").map(|(old, _)| old.to_string());
+                if let Ok(mode) = u32::from_str_radix(mode.trim(), 8) {
+                    file.old_mode.get_or_insert(mode);
+                }
+            } else if let Some(path) = line.strip_prefix("rename from ") {
+                file.old_path = Some(path.to_string());
+            } else if let Some(path) = line.strip_prefix("rename to ") {
+                file.new_path = Some(path.to_string());
+            } else if let Some(path) = line.strip_prefix("--- ") {
+                file.old_path = strip_prefix(path);
+            } else if let Some(path) = line.strip_prefix("+++ ") {
+                file.new_path = strip_prefix(path);
+            } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+                file.binary = true;
+            }
+        }
+    }
+    if remaining != (0, 0) {
+        return Err(malformed(current.as_ref().unwrap()));
+    }
+    finish(&mut messages, &mut current);
+    Ok(messages)
+}
+
+/// `a/x b/y` from a `diff --git` line. Ambiguous when paths contain
+/// spaces; the `---`/`+++` or rename lines that follow take precedence.
+fn split_git_paths(paths: &str) -> (Option<String>, Option<String>) {
+    match paths.split_once(" b/") {
+        Some((old, new)) => (
+            Some(old.strip_prefix("a/").unwrap_or(old).to_string()),
+            Some(new.to_string()),
+        ),
+        None => (None, None),
+    }
+}
+
+/// The path in a `---`/`+++` line: None for /dev/null, else without the
+/// `a/`/`b/` prefix and any trailing timestamp.
+fn strip_prefix(path: &str) -> Option<String> {
+    let path = path.split('\t').next().unwrap_or(path).trim_end();
+    if path == "/dev/null" {
+        return None;
+    }
+    let path = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+    Some(path.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::diff::{get_file_diff, list_diff_files};
+    use super::super::types::DiffOptions;
+    use super::*;
+    use std::process::Command;
+
+    fn git(repo: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(repo)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?} failed");
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn test_open_patch() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        git(repo, &["init"]);
+        git(repo, &["config", "user.email", "test@test.com"]);
+        git(repo, &["config", "user.name", "Test"]);
+        let lines: Vec<String> = (1..=20).map(|i| format!("line {i}")).collect();
+        std::fs::write(repo.join("a.txt"), lines.join("\n") + "\n").unwrap();
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-m", "init"]);
+
+        // A two-patch series: edit a.txt, then add b.txt
+        let mut edited = lines.clone();
+        edited[9] = "line ten".to_string();
+        std::fs::write(repo.join("a.txt"), edited.join("\n") + "\n").unwrap();
+        git(repo, &["commit", "-am", "edit a"]);
+        std::fs::write(repo.join("b.txt"), "new\n").unwrap();
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-m", "add b"]);
+        let mbox = git(repo, &["format-patch", "--stdout", "HEAD~2"]);
+        git(repo, &["reset", "--hard", "HEAD~2"]);
+
+        let patch_file = dir.path().join("series.mbox");
+        std::fs::write(&patch_file, &mbox).unwrap();
+        let patch_path = patch_file.to_string_lossy().to_string();
+        let options = DiffOptions::default();
+
+        // In the repo: applied to the pre-image blobs, with full context
+        let spec = DiffSpec::patch(&patch_path);
+        let files = list_diff_files(repo, &spec, &options).unwrap();
+        let paths: Vec<_> = files.iter().map(|f| f.path().to_path_buf()).collect();
+        assert_eq!(paths, vec![PathBuf::from("a.txt"), PathBuf::from("b.txt")]);
+        assert!(!files[0].hunks_only);
+        let diff = get_file_diff(repo, &spec, Path::new("a.txt"), &options).unwrap();
+        let FileContent::Text { lines: after } = diff.after.unwrap().content else {
+            panic!("expected text");
+        };
+        assert_eq!(after.len(), 20);
+        assert_eq!(after[9], "line ten");
+
+        // Against an explicit base rev
+        let spec = DiffSpec::custom(
+            GitRef::Rev("HEAD".into()),
+            GitRef::Patch(patch_path.clone()),
+        );
+        assert_eq!(list_diff_files(repo, &spec, &options).unwrap().len(), 2);
+
+        // Outside any repository: hunks only, at their real line numbers
+        let elsewhere = tempfile::tempdir().unwrap();
+        let spec = DiffSpec::patch(&patch_path);
+        let files = list_diff_files(elsewhere.path(), &spec, &options).unwrap();
+        assert!(files[0].hunks_only);
+        assert_eq!((files[0].additions, files[0].deletions), (Some(1), Some(1)));
+        assert!(files[1].is_added());
+        let diff = get_file_diff(elsewhere.path(), &spec, Path::new("a.txt"), &options).unwrap();
+        let FileContent::Text { lines: after } = diff.after.unwrap().content else {
+            panic!("expected text");
+        };
+        assert_eq!(after.len(), 13);
+        assert_eq!(after[9], "line ten");
+        assert_eq!(after[0], "");
+        let changed: Vec<_> = diff.alignments.iter().filter(|a| a.changed).collect();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].after.start, 9);
+    }
+
+    #[test]
+    fn test_malformed_hunks_are_rejected() {
+        let header = "--- a/a.txt\n+++ b/a.txt\n";
+        // Context line without its space, starting with a multibyte char
+        assert!(parse(&format!("{header}@@ -1,2 +1,2 @@\nécole\n-a\n+b\n")).is_err());
+        // More lines than the header says
+        assert!(parse(&format!("{header}@@ -1,1 +1,1 @@\n-a\n-b\n+c\n")).is_err());
+        // Fewer lines than the header says
+        assert!(parse(&format!("{header}@@ -1,3 +1,3 @@\n x\n-a\n+b\n")).is_err());
+        // A non-empty side starting at line 0
+        assert!(parse(&format!("{header}@@ -0,1 +0,1 @@\n-a\n+b\n")).is_err());
+
+        let messages = parse(&format!("{header}@@ -2,2 +2,2 @@\n é\n-a\n+b\n")).unwrap();
+        let diff = load_file_diff(&latest_per_file(messages), Path::new("a.txt")).unwrap();
+        let FileContent::Text { lines: after } = diff.after.unwrap().content else {
+            panic!("expected text");
+        };
+        assert_eq!(after, vec!["", "é", "b"]);
+
+        // Hunks built by hand are checked when loaded too
+        let patch = FilePatch {
+            old_path: Some("a.txt".into()),
+            new_path: Some("a.txt".into()),
+            hunks: vec![PatchHunk {
+                old_start: 1,
+                old_lines: 1,
+                new_start: 1,
+                new_lines: 1,
+                lines: vec!["-a".into(), "-b".into()],
+            }],
+            ..Default::default()
+        };
+        assert!(load_file_diff(&[patch], Path::new("a.txt")).is_err());
+
+        // Far-off hunks aren't padded out to their claimed position
+        let far = parse(&format!(
+            "{header}@@ -4000000000,1 +4000000000,1 @@\n-a\n+b\n"
+        ))
+        .unwrap();
+        assert!(load_file_diff(&latest_per_file(far), Path::new("a.txt")).is_err());
+    }
+}
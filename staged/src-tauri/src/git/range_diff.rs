@@ -0,0 +1,483 @@
+//! Range-diffs: what changed between two versions of a branch.
+//!
+//! When a PR is force-pushed (rebased, amended, reordered), diffing the old
+//! and new tips mixes the author's changes with everything upstream did in
+//! between. `range_diff` pairs up the commits of the two series, like
+//! `git range-diff`, and shows how each commit's patch changed.
+//! `GitRef::Rebased` gives the whole-branch view: the old tip replayed onto
+//! the new base, to diff against the new tip (see `DiffSpec::interdiff`).
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+use git2::{Commit, DiffFormat, Oid, Repository, Sort};
+use serde::{Deserialize, Serialize};
+
+use super::cli::GitError;
+use super::diff::align_texts;
+use super::types::{DiffOptions, File, FileContent, FileDiff};
+
+/// Stage bits of an index entry's flags (nonzero for conflict entries).
+const STAGE_MASK: u16 = 0x3000;
+
+/// How a commit changed between the two series.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RangeDiffStatus {
+    /// Same patch and message
+    Unchanged,
+    /// Paired, but the patch or message differs
+    Modified,
+    /// Only in the new series
+    Added,
+    /// Only in the old series
+    Removed,
+}
+
+/// A commit in either series.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RangeCommit {
+    pub sha: String,
+    pub short_sha: String,
+    pub subject: String,
+    pub author: String,
+    pub timestamp: i64,
+}
+
+/// How one file's patch changed within a commit pair. `diff.before` is the
+/// old commit's patch for the file and `diff.after` the new one's (hunk
+/// headers without line numbers, so moved hunks compare equal); either is
+/// None when that commit doesn't touch the file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RangeDiffFile {
+    pub path: String,
+    pub diff: FileDiff,
+}
+
+/// One row of a range-diff: a commit pair, or a commit only in one series.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RangeDiffEntry {
+    pub status: RangeDiffStatus,
+    pub old: Option<RangeCommit>,
+    pub new: Option<RangeCommit>,
+    pub message_changed: bool,
+    /// Files whose patch differs; for added/removed commits, all of them
+    pub files: Vec<RangeDiffFile>,
+}
+
+/// Commits of the old and new series, paired up.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RangeDiff {
+    /// Where the old series branched from `upstream`
+    pub old_base: String,
+    /// Where the new series branched from `upstream`
+    pub new_base: String,
+    /// In new-series order, with removed commits near their old position
+    pub commits: Vec<RangeDiffEntry>,
+}
+
+/// A non-merge commit of a series, with its patch split by file.
+struct SeriesCommit {
+    info: RangeCommit,
+    message: String,
+    patch_id: Option<Oid>,
+    /// Patch lines per path, in path order
+    files: Vec<(String, Vec<String>)>,
+}
+
+/// Compare two versions of a branch: the commits `upstream..old` against
+/// `upstream..new`.
+///
+/// Commits are paired by identical patch, then by identical subject, then
+/// by changed-line similarity (at least half the +/- lines in common).
+/// Merge commits are skipped, like `git range-diff`.
+pub fn range_diff(
+    repo_path: &Path,
+    old: &str,
+    new: &str,
+    upstream: &str,
+    options: &DiffOptions,
+) -> Result<RangeDiff, GitError> {
+    let repo = Repository::discover(repo_path).map_err(|e| GitError::NotARepo(e.to_string()))?;
+    let old_tip = find_commit(&repo, old)?;
+    let new_tip = find_commit(&repo, new)?;
+    let upstream = find_commit(&repo, upstream)?;
+    let old_base = merge_base(&repo, &old_tip, &upstream)?;
+    let new_base = merge_base(&repo, &new_tip, &upstream)?;
+
+    let old_series = series(&repo, old_base, old_tip.id())?;
+    let new_series = series(&repo, new_base, new_tip.id())?;
+    let pairs = pair(&old_series, &new_series);
+
+    let mut commits = Vec::new();
+    let mut emitted = vec![false; old_series.len()];
+    let mut emit_removed_before = |limit: usize, commits: &mut Vec<RangeDiffEntry>| {
+        for i in 0..limit {
+            if !emitted[i] && !pairs.contains(&Some(i)) {
+                emitted[i] = true;
+                commits.push(entry(Some(&old_series[i]), None, options)?);
+            }
+        }
+        Ok::<_, GitError>(())
+    };
+    for (j, (new_commit, old_index)) in new_series.iter().zip(&pairs).enumerate() {
+        // Removed commits go before the next commit that pairs after them
+        let next_paired = pairs[j..].iter().flatten().next();
+        emit_removed_before(
+            next_paired.copied().unwrap_or(old_series.len()),
+            &mut commits,
+        )?;
+        let old_commit = old_index.map(|i| &old_series[i]);
+        commits.push(entry(old_commit, Some(new_commit), options)?);
+    }
+    emit_removed_before(old_series.len(), &mut commits)?;
+
+    Ok(RangeDiff {
+        old_base: old_base.to_string(),
+        new_base: new_base.to_string(),
+        commits,
+    })
+}
+
+/// The tree of `rev` rebased onto where `onto` branched from `upstream`:
+/// its changes since its own merge-base with `upstream`, replayed with a
+/// three-way tree merge. Files that conflict keep `rev`'s version.
+///
+/// Returns the tree SHA (unreferenced, like stash trees).
+pub(super) fn rebased_tree(
+    repo_path: &Path,
+    rev: &str,
+    onto: &str,
+    upstream: &str,
+) -> Result<String, GitError> {
+    let repo = Repository::discover(repo_path).map_err(|e| GitError::NotARepo(e.to_string()))?;
+    let commit = find_commit(&repo, rev)?;
+    let upstream = find_commit(&repo, upstream)?;
+    let old_base = merge_base(&repo, &commit, &upstream)?;
+    let new_base = merge_base(&repo, &find_commit(&repo, onto)?, &upstream)?;
+
+    let tree_err =
+        |e: git2::Error| GitError::CommandFailed(format!("Cannot rebase '{rev}' for diffing: {e}"));
+    let tree = commit.tree().map_err(tree_err)?;
+    if old_base == new_base {
+        return Ok(tree.id().to_string());
+    }
+
+    let base_tree = |oid| repo.find_commit(oid).and_then(|c| c.tree());
+    let ancestor = base_tree(old_base).map_err(tree_err)?;
+    let ours = base_tree(new_base).map_err(tree_err)?;
+    let mut index = repo
+        .merge_trees(&ancestor, &ours, &tree, None)
+        .map_err(tree_err)?;
+
+    if index.has_conflicts() {
+        let conflicts: Vec<git2::IndexConflict> = index
+            .conflicts()
+            .map_err(tree_err)?
+            .collect::<Result<_, _>>()
+            .map_err(tree_err)?;
+        for conflict in conflicts {
+            let Some(path) = [&conflict.their, &conflict.our, &conflict.ancestor]
+                .into_iter()
+                .flatten()
+                .map(|entry| String::from_utf8_lossy(&entry.path).to_string())
+                .next()
+            else {
+                continue;
+            };
+            // Removing the path clears all its conflict stages
+            index.remove_path(Path::new(&path)).map_err(tree_err)?;
+            if let Some(mut theirs) = conflict.their {
+                theirs.flags &= !STAGE_MASK;
+                index.add(&theirs).map_err(tree_err)?;
+            }
+        }
+    }
+
+    let oid = index.write_tree_to(&repo).map_err(tree_err)?;
+    Ok(oid.to_string())
+}
+
+fn find_commit<'r>(repo: &'r Repository, rev: &str) -> Result<Commit<'r>, GitError> {
+    repo.revparse_single(rev)
+        .and_then(|obj| obj.peel_to_commit())
+        .map_err(|e| GitError::CommandFailed(format!("Cannot resolve '{rev}': {e}")))
+}
+
+fn merge_base(repo: &Repository, a: &Commit, b: &Commit) -> Result<Oid, GitError> {
+    repo.merge_base(a.id(), b.id()).map_err(|e| {
+        GitError::CommandFailed(format!(
+            "No common ancestor for {} and {}: {e}",
+            a.id(),
+            b.id()
+        ))
+    })
+}
+
+/// Non-merge commits in `base..tip`, oldest first.
+fn series(repo: &Repository, base: Oid, tip: Oid) -> Result<Vec<SeriesCommit>, GitError> {
+    let walk_err = |e: git2::Error| GitError::CommandFailed(format!("Cannot list commits: {e}"));
+    let mut walk = repo.revwalk().map_err(walk_err)?;
+    walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)
+        .map_err(walk_err)?;
+    walk.push(tip).map_err(walk_err)?;
+    walk.hide(base).map_err(walk_err)?;
+
+    let mut commits = Vec::new();
+    for oid in walk {
+        let commit = repo.find_commit(oid.map_err(walk_err)?).map_err(walk_err)?;
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree().map_err(walk_err)?),
+            Err(_) => None,
+        };
+        let tree = commit.tree().map_err(walk_err)?;
+        let mut diff = repo
+            .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+            .map_err(walk_err)?;
+        diff.find_similar(None).map_err(walk_err)?;
+
+        let sha = commit.id().to_string();
+        commits.push(SeriesCommit {
+            info: RangeCommit {
+                short_sha: sha[..7].to_string(),
+                sha,
+                subject: commit.summary().unwrap_or_default().to_string(),
+                author: commit.author().name().unwrap_or_default().to_string(),
+                timestamp: commit.time().seconds(),
+            },
+            message: commit.message().unwrap_or_default().trim_end().to_string(),
+            patch_id: diff.patchid(None).ok(),
+            files: patch_lines(&diff).map_err(walk_err)?,
+        });
+    }
+    Ok(commits)
+}
+
+/// A diff's patch lines grouped by file. Hunk headers keep only their
+/// function context, since line numbers shift whenever upstream changes.
+fn patch_lines(diff: &git2::Diff) -> Result<Vec<(String, Vec<String>)>, git2::Error> {
+    let mut files: Vec<(String, Vec<String>)> = Vec::new();
+    diff.print(DiffFormat::Patch, |delta, _hunk, line| {
+        let path = delta
+            .new_file()
+            .path()
+            .or(delta.old_file().path())
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if files.last().map(|(last, _)| last) != Some(&path) {
+            let mut header = Vec::new();
+            if let (Some(old), Some(new)) = (delta.old_file().path(), delta.new_file().path()) {
+                if old != new {
+                    header.push(format!("rename from {}", old.display()));
+                }
+            }
+            files.push((path, header));
+        }
+        let lines = &mut files.last_mut().unwrap().1;
+
+        let content = String::from_utf8_lossy(line.content());
+        let content = content.trim_end_matches(['\n', '\r']);
+        match line.origin() {
+            '+' | '-' | ' ' => lines.push(format!("{}{content}", line.origin())),
+            'H' => {
+                let context = content.rsplit_once("@@").map_or("", |(_, c)| c);
+                lines.push(format!("@@{}", context.trim_end()));
+            }
+            'B' => lines.push("Binary files differ".to_string()),
+            _ => {}
+        }
+        true
+    })?;
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(files)
+}
+
+/// For each new commit, the index of the old commit it pairs with.
+fn pair(old: &[SeriesCommit], new: &[SeriesCommit]) -> Vec<Option<usize>> {
+    let mut pairs: Vec<Option<usize>> = vec![None; new.len()];
+    let mut used = vec![false; old.len()];
+
+    let mut match_by = |matches: &dyn Fn(&SeriesCommit, &SeriesCommit) -> bool| {
+        for (j, new_commit) in new.iter().enumerate() {
+            if pairs[j].is_some() {
+                continue;
+            }
+            if let Some(i) = (0..old.len()).find(|&i| !used[i] && matches(&old[i], new_commit)) {
+                used[i] = true;
+                pairs[j] = Some(i);
+            }
+        }
+    };
+    match_by(&|a, b| a.patch_id.is_some() && a.patch_id == b.patch_id);
+    match_by(&|a, b| a.info.subject == b.info.subject);
+    match_by(&|a, b| similarity(a, b) >= 0.5);
+    pairs
+}
+
+/// Fraction of changed (+/-) lines two commits have in common.
+fn similarity(a: &SeriesCommit, b: &SeriesCommit) -> f64 {
+    let changed = |commit: &SeriesCommit| {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for line in commit.files.iter().flat_map(|(_, lines)| lines) {
+            if line.starts_with('+') || line.starts_with('-') {
+                *counts.entry(line.clone()).or_default() += 1;
+            }
+        }
+        counts
+    };
+    let (a, b) = (changed(a), changed(b));
+    let total = a.values().sum::<usize>().max(b.values().sum::<usize>());
+    if total == 0 {
+        return 0.0;
+    }
+    let common: usize = a
+        .iter()
+        .map(|(line, n)| (*n).min(b.get(line).copied().unwrap_or(0)))
+        .sum();
+    common as f64 / total as f64
+}
+
+fn entry(
+    old: Option<&SeriesCommit>,
+    new: Option<&SeriesCommit>,
+    options: &DiffOptions,
+) -> Result<RangeDiffEntry, GitError> {
+    let lines_for = |commit: Option<&SeriesCommit>, path: &str| {
+        commit.and_then(|c| {
+            c.files
+                .iter()
+                .find(|(p, _)| p == path)
+                .map(|(_, l)| l.clone())
+        })
+    };
+    let paths: BTreeSet<&str> = old
+        .into_iter()
+        .chain(new)
+        .flat_map(|c| c.files.iter().map(|(path, _)| path.as_str()))
+        .collect();
+
+    let mut files = Vec::new();
+    for path in paths {
+        let (before, after) = (lines_for(old, path), lines_for(new, path));
+        if before == after {
+            continue;
+        }
+        let file = |lines: Option<Vec<String>>| {
+            lines.map(|lines| File::new(path, FileContent::Text { lines }))
+        };
+        let (before, after) = (file(before), file(after));
+        let alignments = align_texts(&before, &after, options)?;
+        files.push(RangeDiffFile {
+            path: path.to_string(),
+            diff: FileDiff {
+                before,
+                after,
+                alignments,
+                inline_changes: vec![],
+                highlights: None,
+            },
+        });
+    }
+
+    let message_changed = matches!((old, new), (Some(a), Some(b)) if a.message != b.message);
+    let status = match (old, new) {
+        (Some(_), Some(_)) if files.is_empty() && !message_changed => RangeDiffStatus::Unchanged,
+        (Some(_), Some(_)) => RangeDiffStatus::Modified,
+        (None, _) => RangeDiffStatus::Added,
+        (_, None) => RangeDiffStatus::Removed,
+    };
+    Ok(RangeDiffEntry {
+        status,
+        old: old.map(|c| c.info.clone()),
+        new: new.map(|c| c.info.clone()),
+        message_changed,
+        files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::diff::list_diff_files;
+    use super::super::types::DiffSpec;
+    use super::*;
+    use std::process::Command;
+
+    fn git(repo: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(repo)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?} failed");
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    fn commit_file(repo: &Path, path: &str, content: &str, message: &str) {
+        std::fs::write(repo.join(path), content).unwrap();
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-m", message]);
+    }
+
+    #[test]
+    fn test_range_diff_after_rebase() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        git(repo, &["init", "-b", "main"]);
+        git(repo, &["config", "user.email", "test@test.com"]);
+        git(repo, &["config", "user.name", "Test"]);
+        commit_file(repo, "base.txt", "base\n", "init");
+
+        // v1 of the branch: three commits
+        git(repo, &["checkout", "-b", "feature"]);
+        commit_file(repo, "a.txt", "a\n", "add a");
+        commit_file(repo, "b.txt", "b\n", "add b");
+        commit_file(repo, "c.txt", "c\n", "add c");
+        let old = git(repo, &["rev-parse", "HEAD"]);
+
+        // Upstream moves on; v2 is rebased, edits "add b" and drops "add c"
+        git(repo, &["checkout", "main"]);
+        commit_file(repo, "base.txt", "base\nupstream\n", "upstream");
+        git(repo, &["checkout", "-b", "feature-v2"]);
+        commit_file(repo, "a.txt", "a\n", "add a");
+        commit_file(repo, "b.txt", "b, better\n", "add b");
+        commit_file(repo, "d.txt", "d\n", "add d");
+        let new = git(repo, &["rev-parse", "HEAD"]);
+
+        let options = DiffOptions::default();
+        let result = range_diff(repo, &old, &new, "main", &options).unwrap();
+        let statuses: Vec<_> = result
+            .commits
+            .iter()
+            .map(|c| {
+                let commit = c.new.as_ref().or(c.old.as_ref()).unwrap();
+                (commit.subject.as_str(), c.status)
+            })
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("add a", RangeDiffStatus::Unchanged),
+                ("add b", RangeDiffStatus::Modified),
+                ("add c", RangeDiffStatus::Removed),
+                ("add d", RangeDiffStatus::Added),
+            ]
+        );
+        let modified = &result.commits[1];
+        assert_eq!(modified.files.len(), 1);
+        assert_eq!(modified.files[0].path, "b.txt");
+        assert!(!modified.message_changed);
+
+        // The interdiff leaves out upstream's change to base.txt
+        let spec = DiffSpec::interdiff(&old, &new, "main");
+        let files = list_diff_files(repo, &spec, &options).unwrap();
+        let paths: Vec<_> = files.iter().map(|f| f.path().to_string_lossy()).collect();
+        assert_eq!(paths, vec!["b.txt", "c.txt", "d.txt"]);
+    }
+}
@@ -0,0 +1,218 @@
+//! Git lookups for carrying reviews forward to new commits.
+//!
+//! Reviews are keyed by resolved `DiffId` sides (a SHA, or the `WORKDIR`/
+//! `INDEX` sentinels). These helpers answer, for such sides, which earlier
+//! review is closest, whether a file's content changed, and where lines
+//! moved, so `review::carry_forward` can re-anchor marks and comments.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use git2::{Oid, Repository, Sort};
+
+use super::cli::{self, GitError};
+use super::diff::{get_unified_diff, parse_hunk_header};
+use super::types::{DiffOptions, DiffSpec, GitRef, INDEX, WORKDIR};
+
+/// A changed region as 0-indexed (old_start, old_lines, new_start, new_lines).
+pub type LineHunk = (u32, u32, u32, u32);
+
+/// The blob SHA of `path` at a `DiffId` side, or None if it doesn't exist
+/// there. Working-tree files are hashed as `git add` would store them.
+pub fn blob_at(repo: &Path, side: &str, path: &str) -> Result<Option<String>, GitError> {
+    if is_working_tree(side) {
+        if !repo.join(path).is_file() {
+            return Ok(None);
+        }
+        let output = cli::run(repo, &["hash-object", "--", path])?;
+        return Ok(Some(output.trim().to_string()));
+    }
+
+    let spec = if side == INDEX {
+        format!(":{path}")
+    } else {
+        format!("{side}:{path}")
+    };
+    // A missing path is the expected failure here
+    Ok(cli::run(repo, &["rev-parse", "--verify", "--quiet", &spec])
+        .ok()
+        .map(|output| output.trim().to_string()))
+}
+
+/// Of `candidates` (commit SHAs), the closest ancestor of `target`: the
+/// first one reached walking back from `target` in topological order, so
+/// no other candidate lies between it and `target`. None if none of them
+/// is an ancestor.
+pub fn closest_ancestor(
+    repo: &Path,
+    candidates: &[String],
+    target: &str,
+) -> Result<Option<String>, GitError> {
+    let is_sha = |s: &str| s.len() == 40 && s.chars().all(|c| c.is_ascii_hexdigit());
+    if !is_sha(target) {
+        return Ok(None);
+    }
+    let candidates: HashSet<Oid> = candidates
+        .iter()
+        .filter(|c| is_sha(c) && *c != target)
+        .filter_map(|c| Oid::from_str(c).ok())
+        .collect();
+    if candidates.is_empty() {
+        return Ok(None);
+    }
+
+    let Ok(target) = Oid::from_str(target) else {
+        return Ok(None);
+    };
+
+    let repo = Repository::discover(repo).map_err(|e| GitError::NotARepo(e.to_string()))?;
+    let walk_err = |e: git2::Error| GitError::CommandFailed(format!("Cannot walk history: {e}"));
+    let mut walk = repo.revwalk().map_err(walk_err)?;
+    walk.set_sorting(Sort::TOPOLOGICAL).map_err(walk_err)?;
+    // An unknown target has no ancestors to carry from
+    if walk.push(target).is_err() {
+        return Ok(None);
+    }
+    for oid in walk {
+        let oid = oid.map_err(walk_err)?;
+        if candidates.contains(&oid) {
+            return Ok(Some(oid.to_string()));
+        }
+    }
+    Ok(None)
+}
+
+/// The changed regions of `path` between two `DiffId` sides.
+pub fn line_hunks(
+    repo: &Path,
+    from: &str,
+    to: &str,
+    path: &str,
+) -> Result<Vec<LineHunk>, GitError> {
+    let spec = DiffSpec::custom(side_ref(from), side_ref(to));
+    let options = DiffOptions {
+        context_lines: Some(0),
+        ..DiffOptions::default()
+    };
+    let output = get_unified_diff(repo, &spec, Path::new(path), &options)?;
+
+    // An empty side's start is the line before it, so it's already 0-indexed
+    let start = |line: u32, count: u32| if count == 0 { line } else { line - 1 };
+    Ok(output
+        .lines()
+        .filter_map(parse_hunk_header)
+        .map(|(old_start, old_lines, new_start, new_lines)| {
+            (
+                start(old_start, old_lines),
+                old_lines,
+                start(new_start, new_lines),
+                new_lines,
+            )
+        })
+        .collect())
+}
+
+/// Where a 0-indexed line ends up after the changes in `hunks`. Lines
+/// inside a changed region map into its replacement (or just past it, if
+/// it was deleted).
+pub fn map_line(hunks: &[LineHunk], line: u32) -> u32 {
+    let mut shift: i64 = 0;
+    for &(old_start, old_lines, new_start, new_lines) in hunks {
+        if line < old_start {
+            break;
+        }
+        if line < old_start + old_lines {
+            return new_start + (line - old_start).min(new_lines.saturating_sub(1));
+        }
+        shift = (new_start + new_lines) as i64 - (old_start + old_lines) as i64;
+    }
+    (line as i64 + shift).max(0) as u32
+}
+
+fn is_working_tree(side: &str) -> bool {
+    side == WORKDIR || side == "@"
+}
+
+fn side_ref(side: &str) -> GitRef {
+    if is_working_tree(side) {
+        GitRef::WorkingTree
+    } else if side == INDEX {
+        GitRef::Index
+    } else {
+        GitRef::Rev(side.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    fn git(repo: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(repo)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?} failed");
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    #[test]
+    fn test_map_line() {
+        // Two lines inserted after old line 3, old lines 8-9 deleted
+        let hunks = [(3, 0, 3, 2), (7, 2, 9, 0)];
+        assert_eq!(map_line(&hunks, 0), 0);
+        assert_eq!(map_line(&hunks, 3), 5);
+        assert_eq!(map_line(&hunks, 7), 9);
+        assert_eq!(map_line(&hunks, 9), 9);
+    }
+
+    #[test]
+    fn test_reanchor_lookups() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        git(repo, &["init"]);
+        git(repo, &["config", "user.email", "test@test.com"]);
+        git(repo, &["config", "user.name", "Test"]);
+        std::fs::write(repo.join("a.txt"), "1\n2\n3\n").unwrap();
+        std::fs::write(repo.join("b.txt"), "b\n").unwrap();
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-m", "one"]);
+        let first = git(repo, &["rev-parse", "HEAD"]);
+        std::fs::write(repo.join("a.txt"), "0\n1\n2\n3\n").unwrap();
+        git(repo, &["commit", "-am", "two"]);
+        let second = git(repo, &["rev-parse", "HEAD"]);
+        std::fs::write(repo.join("b.txt"), "b2\n").unwrap();
+        git(repo, &["commit", "-am", "three"]);
+        let third = git(repo, &["rev-parse", "HEAD"]);
+
+        let candidates = vec![first.clone(), second.clone()];
+        assert_eq!(
+            closest_ancestor(repo, &candidates, &third).unwrap(),
+            Some(second.clone())
+        );
+        assert_eq!(
+            closest_ancestor(repo, std::slice::from_ref(&third), &first).unwrap(),
+            None
+        );
+
+        assert_eq!(
+            blob_at(repo, &second, "b.txt").unwrap(),
+            blob_at(repo, &first, "b.txt").unwrap()
+        );
+        assert_ne!(
+            blob_at(repo, &third, "b.txt").unwrap(),
+            blob_at(repo, &second, "b.txt").unwrap()
+        );
+        assert_eq!(
+            blob_at(repo, WORKDIR, "b.txt").unwrap(),
+            blob_at(repo, &third, "b.txt").unwrap()
+        );
+        assert_eq!(blob_at(repo, &third, "missing.txt").unwrap(), None);
+
+        let hunks = line_hunks(repo, &first, &third, "a.txt").unwrap();
+        assert_eq!(hunks, vec![(0, 0, 0, 1)]);
+        assert_eq!(map_line(&hunks, 2), 3);
+    }
+}
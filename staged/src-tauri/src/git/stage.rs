@@ -0,0 +1,452 @@
+//! Partial (hunk- and line-level) staging.
+//!
+//! Builds a patch containing only the selected lines of a file's diff and
+//! applies it to the index with `git apply --cached`, the same way `git add -p`
+//! does. Unstaging applies the filtered staged diff in reverse.
+
+use super::cli::{self, GitError};
+use super::diff::{parse_hunk_header, resolve_spec};
+use super::types::{DiffSpec, GitRef, LineSelection};
+use std::path::Path;
+
+/// Stage the selected lines of a file's diff.
+///
+/// Line numbers in `selection` refer to the sides of `spec`'s diff. The patch is
+/// applied on top of the current index, so the spec's base must match what is
+/// staged for this file: `index..@` always does, `HEAD..@` does when nothing is
+/// staged yet.
+pub fn stage_selection(
+    repo: &Path,
+    spec: &DiffSpec,
+    path: &Path,
+    selection: &LineSelection,
+) -> Result<(), GitError> {
+    let spec = resolve_spec(repo, spec)?;
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| GitError::InvalidPath(path.display().to_string()))?;
+
+    let diff = match (&spec.base, &spec.head) {
+        (GitRef::Rev(_), GitRef::WorkingTree) if is_untracked(repo, path_str)? => {
+            untracked_file_patch(repo, path_str)?
+        }
+        (GitRef::Rev(base), GitRef::WorkingTree) => {
+            cli::run(repo, &["diff", "--no-color", "-U0", base, "--", path_str])?
+        }
+        (GitRef::Rev(base), GitRef::Rev(head)) => cli::run(
+            repo,
+            &["diff", "--no-color", "-U0", base, head, "--", path_str],
+        )?,
+        (GitRef::Index, GitRef::WorkingTree) if is_untracked(repo, path_str)? => {
+            untracked_file_patch(repo, path_str)?
+        }
+        (GitRef::Index, GitRef::WorkingTree) => {
+            cli::run(repo, &["diff", "--no-color", "-U0", "--", path_str])?
+        }
+        (GitRef::Index, _) | (_, GitRef::Index) => {
+            return Err(GitError::CommandFailed(
+                "Can only stage from a diff against the working tree or a commit".to_string(),
+            ))
+        }
+        (GitRef::WorkingTree, _) => {
+            return Err(GitError::CommandFailed(
+                "Cannot use working tree as base".to_string(),
+            ))
+        }
+        (GitRef::Path(_), _) | (_, GitRef::Path(_)) => {
+            return Err(GitError::CommandFailed(
+                "Cannot stage from a diff of filesystem paths".to_string(),
+            ))
+        }
+        (
+            GitRef::MergeBase
+            | GitRef::MergeBaseOf(_)
+            | GitRef::Stash(_)
+            | GitRef::Patch(_)
+            | GitRef::Rebased(_),
+            _,
+        )
+        | (
+            _,
+            GitRef::MergeBase
+            | GitRef::MergeBaseOf(_)
+            | GitRef::Stash(_)
+            | GitRef::Patch(_)
+            | GitRef::Rebased(_),
+        ) => {
+            unreachable!("MergeBase/MergeBaseOf/Stash/Patch/Rebased should have been resolved")
+        }
+    };
+
+    apply_to_index(repo, &diff, selection, false)
+}
+
+/// Unstage the selected lines of a file's staged changes.
+///
+/// Line numbers in `selection` refer to the staged diff (HEAD on the before side,
+/// the index on the after side).
+pub fn unstage_selection(
+    repo: &Path,
+    path: &Path,
+    selection: &LineSelection,
+) -> Result<(), GitError> {
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| GitError::InvalidPath(path.display().to_string()))?;
+
+    let diff = cli::run(
+        repo,
+        &["diff", "--cached", "--no-color", "-U0", "--", path_str],
+    )?;
+
+    apply_to_index(repo, &diff, selection, true)
+}
+
+/// Filter `diff` down to the selection and apply it to the index.
+fn apply_to_index(
+    repo: &Path,
+    diff: &str,
+    selection: &LineSelection,
+    reverse: bool,
+) -> Result<(), GitError> {
+    if diff.contains("\nBinary files ") || diff.starts_with("Binary files ") {
+        return Err(GitError::CommandFailed(
+            "Cannot partially stage a binary file".to_string(),
+        ));
+    }
+
+    let patch = match filter_patch(diff, selection, reverse) {
+        Some(p) => p,
+        None => return Ok(()), // Nothing selected
+    };
+
+    let mut args = vec!["apply", "--cached", "--unidiff-zero", "--whitespace=nowarn"];
+    if reverse {
+        args.push("--reverse");
+    }
+    args.push("-");
+
+    cli::run_with_input(repo, &args, &patch)?;
+    Ok(())
+}
+
+/// Check whether a path is unknown to the index.
+fn is_untracked(repo: &Path, path: &str) -> Result<bool, GitError> {
+    let output = cli::run(repo, &["ls-files", "--", path])?;
+    Ok(output.trim().is_empty())
+}
+
+/// Build a "new file" patch for an untracked file.
+/// `git diff` doesn't report untracked files, so we synthesize the patch ourselves.
+fn untracked_file_patch(repo: &Path, path: &str) -> Result<String, GitError> {
+    let full_path = repo.join(path);
+    let bytes = std::fs::read(&full_path)
+        .map_err(|e| GitError::CommandFailed(format!("Cannot read file: {e}")))?;
+    let text = String::from_utf8(bytes).map_err(|_| GitError::InvalidUtf8)?;
+
+    let mode = if is_executable(&full_path) {
+        "100755"
+    } else {
+        "100644"
+    };
+
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let mut patch = format!(
+        "diff --git a/{path} b/{path}\nnew file mode {mode}\n--- /dev/null\n+++ b/{path}\n"
+    );
+    if lines.is_empty() {
+        return Ok(patch);
+    }
+
+    patch.push_str(&format!("@@ -0,0 +1,{} @@\n", lines.len()));
+    for line in lines {
+        patch.push('+');
+        patch.push_str(line);
+        if !line.ends_with('\n') {
+            patch.push_str("\n\\ No newline at end of file\n");
+        }
+    }
+    Ok(patch)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .map(|m| m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    false
+}
+
+/// Convert a 1-indexed hunk start to the 0-indexed line the hunk begins at.
+/// Empty ranges point at the line *before* the change, so they're already 0-indexed.
+fn start_to_index(start: u32, count: u32) -> u32 {
+    if count == 0 {
+        start
+    } else {
+        start.saturating_sub(1)
+    }
+}
+
+/// Convert a 0-indexed position back to a hunk header start.
+fn index_to_start(index: u32, count: u32) -> u32 {
+    if count == 0 {
+        index
+    } else {
+        index + 1
+    }
+}
+
+/// Reduce a zero-context unified diff to the selected lines.
+///
+/// Applied forward, unselected removals become context and unselected additions
+/// are dropped. Applied in `reverse`, the roles flip: unselected additions become
+/// context and unselected removals are dropped. Hunk headers are recomputed so the
+/// patch applies with `--unidiff-zero`.
+///
+/// Returns None if no changed lines remain.
+fn filter_patch(diff: &str, selection: &LineSelection, reverse: bool) -> Option<String> {
+    let mut lines = diff.split_inclusive('\n').peekable();
+
+    // File header: everything up to the first hunk
+    let mut header: Vec<&str> = Vec::new();
+    while let Some(line) = lines.peek() {
+        if line.starts_with("@@") {
+            break;
+        }
+        header.push(line);
+        lines.next();
+    }
+
+    let mut hunks = String::new();
+    let mut delta: i64 = 0;
+    let mut all_selected = true;
+
+    while let Some(line) = lines.next() {
+        let Some((old_start, old_count, new_start, new_count)) = parse_hunk_header(line) else {
+            continue;
+        };
+        let mut old_idx = start_to_index(old_start, old_count);
+        let mut new_idx = start_to_index(new_start, new_count);
+        let (orig_old_pos, orig_new_pos) = (old_idx, new_idx);
+
+        let mut body = String::new();
+        let mut removed = 0u32;
+        let mut added = 0u32;
+        let mut context = 0u32;
+        let mut last_kept = false;
+
+        while let Some(line) = lines.peek() {
+            if line.starts_with("@@") {
+                break;
+            }
+            let line = lines.next().unwrap();
+            let rest = &line[1..];
+
+            match line.as_bytes()[0] {
+                b'-' => {
+                    if selection.includes_before(old_idx) {
+                        body.push_str(line);
+                        removed += 1;
+                        last_kept = true;
+                    } else if reverse {
+                        // Not restoring this line: drop it
+                        all_selected = false;
+                        last_kept = false;
+                    } else {
+                        // Not removing this line: keep it as context
+                        body.push(' ');
+                        body.push_str(rest);
+                        context += 1;
+                        all_selected = false;
+                        last_kept = true;
+                    }
+                    old_idx += 1;
+                }
+                b'+' => {
+                    if selection.includes_after(new_idx) {
+                        body.push_str(line);
+                        added += 1;
+                        last_kept = true;
+                    } else if reverse {
+                        // Not unstaging this line: keep it as context
+                        body.push(' ');
+                        body.push_str(rest);
+                        context += 1;
+                        all_selected = false;
+                        last_kept = true;
+                    } else {
+                        // Not adding this line: drop it
+                        all_selected = false;
+                        last_kept = false;
+                    }
+                    new_idx += 1;
+                }
+                b' ' => {
+                    body.push_str(line);
+                    context += 1;
+                    old_idx += 1;
+                    new_idx += 1;
+                    last_kept = true;
+                }
+                // "\ No newline at end of file" belongs to the preceding line
+                b'\\' if last_kept => body.push_str(line),
+                _ => {}
+            }
+        }
+
+        if removed == 0 && added == 0 {
+            continue;
+        }
+
+        let out_old_count = removed + context;
+        let out_new_count = added + context;
+        let (old_pos, new_pos) = if reverse {
+            ((orig_new_pos as i64 - delta).max(0) as u32, orig_new_pos)
+        } else {
+            (orig_old_pos, (orig_old_pos as i64 + delta).max(0) as u32)
+        };
+
+        hunks.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            index_to_start(old_pos, out_old_count),
+            out_old_count,
+            index_to_start(new_pos, out_new_count),
+            out_new_count
+        ));
+        hunks.push_str(&body);
+        delta += out_new_count as i64 - out_old_count as i64;
+    }
+
+    if hunks.is_empty() {
+        return None;
+    }
+
+    let mut patch = String::new();
+    for line in header {
+        // A partially staged deletion leaves the file in place
+        if !all_selected && !reverse && line.starts_with("deleted file mode") {
+            continue;
+        }
+        if !all_selected && !reverse && line.starts_with("+++ /dev/null") {
+            if let Some(path) = header_path(diff) {
+                patch.push_str(&format!("+++ b/{path}\n"));
+                continue;
+            }
+        }
+        patch.push_str(line);
+    }
+    patch.push_str(&hunks);
+    Some(patch)
+}
+
+/// Extract the old path from a `--- a/<path>` header line.
+fn header_path(diff: &str) -> Option<&str> {
+    diff.lines()
+        .find_map(|l| l.strip_prefix("--- a/"))
+        .map(|p| p.trim_end_matches('\r'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::Span;
+    use std::process::Command;
+
+    const DIFF: &str = "diff --git a/f.txt b/f.txt\n\
+index 1111111..2222222 100644\n\
+--- a/f.txt\n\
++++ b/f.txt\n\
+@@ -2 +2 @@\n\
+-two\n\
++TWO\n\
+@@ -5,0 +6,2 @@\n\
++six\n\
++seven\n";
+
+    #[test]
+    fn test_filter_patch_second_hunk_only() {
+        let selection = LineSelection {
+            before: vec![],
+            after: vec![Span::new(5, 7)],
+        };
+        let patch = filter_patch(DIFF, &selection, false).unwrap();
+        assert!(patch.contains("@@ -5,0 +6,2 @@\n+six\n+seven\n"));
+        assert!(!patch.contains("TWO"));
+    }
+
+    #[test]
+    fn test_filter_patch_removal_without_addition() {
+        let selection = LineSelection {
+            before: vec![Span::new(1, 2)],
+            after: vec![],
+        };
+        let patch = filter_patch(DIFF, &selection, false).unwrap();
+        assert!(patch.contains("@@ -2,1 +1,0 @@\n-two\n"));
+        assert!(!patch.contains("six"));
+    }
+
+    #[test]
+    fn test_filter_patch_nothing_selected() {
+        assert!(filter_patch(DIFF, &LineSelection::default(), false).is_none());
+    }
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn test_stage_and_unstage_selection() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        git(repo, &["init"]);
+        git(repo, &["config", "user.email", "test@example.com"]);
+        git(repo, &["config", "user.name", "Test"]);
+        std::fs::write(repo.join("f.txt"), "one\ntwo\nthree\nfour\nfive\n").unwrap();
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-m", "init"]);
+
+        std::fs::write(
+            repo.join("f.txt"),
+            "one\nTWO\nthree\nfour\nfive\nsix\nseven\n",
+        )
+        .unwrap();
+
+        // Stage only the appended lines
+        let selection = LineSelection {
+            before: vec![],
+            after: vec![Span::new(5, 7)],
+        };
+        stage_selection(
+            repo,
+            &DiffSpec::uncommitted(),
+            Path::new("f.txt"),
+            &selection,
+        )
+        .unwrap();
+        assert_eq!(
+            git(repo, &["show", ":f.txt"]),
+            "one\ntwo\nthree\nfour\nfive\nsix\nseven\n"
+        );
+
+        // Unstage "seven" again
+        let selection = LineSelection {
+            before: vec![],
+            after: vec![Span::new(6, 7)],
+        };
+        unstage_selection(repo, Path::new("f.txt"), &selection).unwrap();
+        assert_eq!(
+            git(repo, &["show", ":f.txt"]),
+            "one\ntwo\nthree\nfour\nfive\nsix\n"
+        );
+    }
+}
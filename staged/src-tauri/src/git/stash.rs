@@ -0,0 +1,291 @@
+//! Stash operations.
+//!
+//! Stashes are addressed by their commit SHA where possible, since
+//! `stash@{n}` indices shift whenever a stash is pushed or dropped.
+//! Both forms are accepted by every function here.
+
+use super::cli::{self, GitError};
+use git2::{ObjectType, Oid, Repository, TreeWalkMode, TreeWalkResult};
+use serde::Serialize;
+use std::path::Path;
+
+/// A stash entry.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StashEntry {
+    /// Position in the stash list (the `n` in `stash@{n}`)
+    pub index: usize,
+    /// SHA of the stash commit. Stable across pushes and drops.
+    pub sha: String,
+    /// Stash message, e.g. "WIP on main: abc1234 Fix parser"
+    pub message: String,
+    /// Unix timestamp when the stash was created
+    pub timestamp: i64,
+    /// Whether untracked files were stashed too (`git stash -u`)
+    pub has_untracked: bool,
+}
+
+impl StashEntry {
+    /// The `stash@{n}` reference for this entry.
+    pub fn reflog_ref(&self) -> String {
+        format!("stash@{{{}}}", self.index)
+    }
+}
+
+/// List stashes, newest first.
+pub fn list_stashes(repo: &Path) -> Result<Vec<StashEntry>, GitError> {
+    // Format: sha|parents|timestamp|message
+    let output = cli::run(repo, &["stash", "list", "--format=%H|%P|%ct|%gs"])?;
+
+    let mut stashes = Vec::new();
+    for (index, line) in output.lines().filter(|l| !l.is_empty()).enumerate() {
+        let parts: Vec<&str> = line.splitn(4, '|').collect();
+        if parts.len() < 4 {
+            continue;
+        }
+        stashes.push(StashEntry {
+            index,
+            sha: parts[0].to_string(),
+            // Parents are [HEAD, index] plus the untracked-files commit if any
+            has_untracked: parts[1].split_whitespace().count() > 2,
+            timestamp: parts[2].parse().unwrap_or(0),
+            message: parts[3].to_string(),
+        });
+    }
+
+    Ok(stashes)
+}
+
+/// Stash local changes.
+///
+/// Returns the new stash, or None if there was nothing to stash.
+pub fn create_stash(
+    repo: &Path,
+    message: Option<&str>,
+    include_untracked: bool,
+) -> Result<Option<StashEntry>, GitError> {
+    let before = top_stash_sha(repo);
+
+    let mut args = vec!["stash", "push"];
+    if include_untracked {
+        args.push("--include-untracked");
+    }
+    if let Some(message) = message {
+        args.extend(["--message", message]);
+    }
+    cli::run(repo, &args)?;
+
+    // `git stash push` succeeds without creating anything when the tree is clean
+    if top_stash_sha(repo) == before {
+        return Ok(None);
+    }
+    Ok(list_stashes(repo)?.into_iter().next())
+}
+
+/// Apply a stash to the working tree, keeping it in the stash list.
+/// Staged changes are restored to the index too; if they no longer apply
+/// cleanly, this fails and nothing is changed.
+pub fn apply_stash(repo: &Path, stash: &str) -> Result<(), GitError> {
+    let stash_ref = find_stash_ref(repo, stash)?;
+    cli::run(repo, &["stash", "apply", "--index", &stash_ref])?;
+    Ok(())
+}
+
+/// Apply a stash and drop it. The stash is kept if applying fails.
+pub fn pop_stash(repo: &Path, stash: &str) -> Result<(), GitError> {
+    let stash_ref = find_stash_ref(repo, stash)?;
+    cli::run(repo, &["stash", "pop", "--index", &stash_ref])?;
+    Ok(())
+}
+
+/// Remove a stash from the stash list.
+pub fn drop_stash(repo: &Path, stash: &str) -> Result<(), GitError> {
+    let stash_ref = find_stash_ref(repo, stash)?;
+    cli::run(repo, &["stash", "drop", &stash_ref])?;
+    Ok(())
+}
+
+/// Build the tree a stash would leave in the working tree: its tracked
+/// changes overlaid with its untracked files.
+///
+/// Returns the tree SHA. For stashes without untracked files this is just
+/// the stash commit's tree; otherwise a merged tree is written to the
+/// object database (unreferenced, so `git gc` eventually cleans it up).
+pub(super) fn stash_tree(repo_path: &Path, stash: &str) -> Result<String, GitError> {
+    let repo = Repository::discover(repo_path).map_err(|e| GitError::NotARepo(e.to_string()))?;
+
+    let commit = repo
+        .revparse_single(stash)
+        .and_then(|obj| obj.peel_to_commit())
+        .map_err(|e| GitError::CommandFailed(format!("Cannot resolve stash '{stash}': {e}")))?;
+    let tree = commit
+        .tree()
+        .map_err(|e| GitError::CommandFailed(format!("Cannot get tree for '{stash}': {e}")))?;
+
+    let untracked = match commit.parent(2) {
+        Ok(parent) => parent.tree().map_err(|e| {
+            GitError::CommandFailed(format!("Cannot get untracked tree for '{stash}': {e}"))
+        })?,
+        Err(_) => return Ok(tree.id().to_string()),
+    };
+
+    let tree_err =
+        |e: git2::Error| GitError::CommandFailed(format!("Cannot build stash tree: {e}"));
+
+    // Overlay the untracked blobs onto the stashed tree in an in-memory index
+    let mut index = git2::Index::new().map_err(tree_err)?;
+    index.read_tree(&tree).map_err(tree_err)?;
+
+    let mut blobs: Vec<(Vec<u8>, Oid, u32)> = Vec::new();
+    untracked
+        .walk(TreeWalkMode::PreOrder, |dir, entry| {
+            if entry.kind() == Some(ObjectType::Blob) {
+                let mut path = dir.as_bytes().to_vec();
+                path.extend_from_slice(entry.name_bytes());
+                blobs.push((path, entry.id(), entry.filemode() as u32));
+            }
+            TreeWalkResult::Ok
+        })
+        .map_err(tree_err)?;
+
+    for (path, id, mode) in blobs {
+        index
+            .add(&git2::IndexEntry {
+                ctime: git2::IndexTime::new(0, 0),
+                mtime: git2::IndexTime::new(0, 0),
+                dev: 0,
+                ino: 0,
+                mode,
+                uid: 0,
+                gid: 0,
+                file_size: 0,
+                id,
+                flags: 0,
+                flags_extended: 0,
+                path,
+            })
+            .map_err(tree_err)?;
+    }
+
+    let oid = index.write_tree_to(&repo).map_err(tree_err)?;
+    Ok(oid.to_string())
+}
+
+/// SHA of `stash@{0}`, if any stash exists.
+fn top_stash_sha(repo: &Path) -> Option<String> {
+    cli::run(repo, &["rev-parse", "--verify", "--quiet", "refs/stash"])
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+/// Map a stash SHA (or `stash@{n}`) to its current `stash@{n}` reference.
+/// `git stash drop`/`pop` only accept reflog references.
+///
+/// Abbreviated SHAs must be unambiguous; they're resolved by git and
+/// compared in full, so a short or empty prefix can't pick the wrong stash.
+fn find_stash_ref(repo: &Path, stash: &str) -> Result<String, GitError> {
+    if stash.starts_with("stash@{") {
+        return Ok(stash.to_string());
+    }
+    let not_found = || GitError::CommandFailed(format!("No stash found for '{stash}'"));
+    if stash.is_empty() || stash.starts_with('-') {
+        return Err(not_found());
+    }
+
+    let spec = format!("{stash}^{{commit}}");
+    let sha =
+        cli::run(repo, &["rev-parse", "--verify", "--quiet", &spec]).map_err(|_| not_found())?;
+    let sha = sha.trim();
+    list_stashes(repo)?
+        .into_iter()
+        .find(|entry| entry.sha == sha)
+        .map(|entry| entry.reflog_ref())
+        .ok_or_else(not_found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::{get_file_diff, list_diff_files, DiffOptions, DiffSpec};
+    use std::process::Command;
+
+    fn git(repo: &Path, args: &[&str]) {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(repo)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?} failed");
+    }
+
+    fn init_repo(repo: &Path) {
+        git(repo, &["init"]);
+        git(repo, &["config", "user.email", "test@test.com"]);
+        git(repo, &["config", "user.name", "Test"]);
+        std::fs::write(repo.join("a.txt"), "one\n").unwrap();
+        git(repo, &["add", "a.txt"]);
+        git(repo, &["commit", "-m", "initial"]);
+    }
+
+    #[test]
+    fn test_stash_roundtrip_with_untracked() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        init_repo(repo);
+
+        assert!(create_stash(repo, None, false).unwrap().is_none());
+
+        std::fs::write(repo.join("a.txt"), "two\n").unwrap();
+        std::fs::write(repo.join("new.txt"), "fresh\n").unwrap();
+        let entry = create_stash(repo, Some("parked"), true)
+            .unwrap()
+            .expect("stash created");
+        assert!(entry.has_untracked);
+        assert!(entry.message.contains("parked"));
+        assert!(!repo.join("new.txt").exists());
+
+        // The stash diff includes both the tracked edit and the untracked file
+        let spec = DiffSpec::stash(&entry.sha);
+        let options = DiffOptions::default();
+        let mut files: Vec<String> = list_diff_files(repo, &spec, &options)
+            .unwrap()
+            .iter()
+            .map(|f| f.path().to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["a.txt", "new.txt"]);
+        let diff = get_file_diff(repo, &spec, Path::new("new.txt"), &options).unwrap();
+        assert!(diff.before.is_none() && diff.after.is_some());
+
+        pop_stash(repo, &entry.sha).unwrap();
+        assert!(list_stashes(repo).unwrap().is_empty());
+        assert_eq!(
+            std::fs::read_to_string(repo.join("a.txt")).unwrap(),
+            "two\n"
+        );
+        assert!(repo.join("new.txt").exists());
+    }
+
+    #[test]
+    fn test_drop_by_sha_after_index_shift() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        init_repo(repo);
+
+        std::fs::write(repo.join("a.txt"), "first\n").unwrap();
+        let first = create_stash(repo, None, false).unwrap().unwrap();
+        std::fs::write(repo.join("a.txt"), "second\n").unwrap();
+        let second = create_stash(repo, None, false).unwrap().unwrap();
+
+        // Prefixes that don't name exactly one stash are rejected
+        assert!(drop_stash(repo, "").is_err());
+        assert!(drop_stash(repo, "HEAD").is_err());
+        assert_eq!(list_stashes(repo).unwrap().len(), 2);
+
+        // `first` is now stash@{1}
+        drop_stash(repo, &first.sha[..12]).unwrap();
+        let remaining = list_stashes(repo).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].sha, second.sha);
+    }
+}
@@ -0,0 +1,434 @@
+//! Structural diffs of JSON, YAML and TOML files.
+//!
+//! Both sides are parsed into a common value tree and compared by key path,
+//! so reordered keys and reformatting don't show up as changes. The result is
+//! a flat change list meant as an alternative to the line-based view.
+
+use std::path::Path;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::cli::GitError;
+use super::diff::load_file_diff;
+use super::types::{DiffOptions, DiffSpec, File, FileContent};
+
+/// Arrays whose LCS table would exceed this many cells are compared by index.
+const MAX_LCS_CELLS: usize = 1_000_000;
+
+/// A config format that can be diffed structurally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StructuredFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl StructuredFormat {
+    /// Pick a format from a file's extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "json" => Some(Self::Json),
+            "yaml" | "yml" => Some(Self::Yaml),
+            "toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+
+    fn parse(self, text: &str) -> Result<Value, String> {
+        match self {
+            Self::Json => serde_json::from_str(text).map_err(|e| e.to_string()),
+            Self::Toml => text
+                .parse::<toml::Table>()
+                .map(|table| toml_to_json(toml::Value::Table(table)))
+                .map_err(|e| e.to_string()),
+            Self::Yaml => {
+                let mut docs =
+                    yaml_rust::YamlLoader::load_from_str(text).map_err(|e| e.to_string())?;
+                Ok(match docs.len() {
+                    0 => Value::Null,
+                    1 => yaml_to_json(docs.remove(0)),
+                    // Multi-document files compare as a list of documents
+                    _ => Value::Array(docs.into_iter().map(yaml_to_json).collect()),
+                })
+            }
+        }
+    }
+}
+
+/// Kind of a structural change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StructuredChangeKind {
+    Added,
+    Removed,
+    /// The value at a path changed (including changing type)
+    Changed,
+    /// An identical value was removed at `old_path` and added at `path`
+    Moved,
+}
+
+/// A change at a single key path.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructuredChange {
+    pub kind: StructuredChangeKind,
+    /// Key path, e.g. `dependencies.serde` or `services[2].image`
+    /// (for removals, the path in the old document)
+    pub path: String,
+    /// Previous path of a moved value
+    pub old_path: Option<String>,
+    pub old_value: Option<Value>,
+    pub new_value: Option<Value>,
+}
+
+/// Key-path-level changes between the two sides of a config file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructuredDiff {
+    pub format: StructuredFormat,
+    pub changes: Vec<StructuredChange>,
+}
+
+/// Diff a JSON/YAML/TOML file structurally.
+///
+/// Returns None if the file's extension isn't a supported format, and an
+/// error if either side doesn't parse (e.g. a half-edited working tree file).
+pub fn get_structured_diff(
+    repo: &Path,
+    spec: &DiffSpec,
+    path: &Path,
+) -> Result<Option<StructuredDiff>, GitError> {
+    let Some(format) = StructuredFormat::from_path(path) else {
+        return Ok(None);
+    };
+
+    let diff = load_file_diff(repo, spec, path, &DiffOptions::default())?;
+    let parse = |file: &Option<File>, side: &str| -> Result<Option<Value>, GitError> {
+        match file {
+            None => Ok(None),
+            Some(File {
+                content: FileContent::Text { lines },
+                ..
+            }) => format
+                .parse(&lines.join("\n"))
+                .map(Some)
+                .map_err(|e| GitError::CommandFailed(format!("Cannot parse {side} version: {e}"))),
+            Some(_) => Err(GitError::CommandFailed(format!(
+                "The {side} version is not text"
+            ))),
+        }
+    };
+    let before = parse(&diff.before, "old")?;
+    let after = parse(&diff.after, "new")?;
+
+    Ok(Some(StructuredDiff {
+        format,
+        changes: diff_values(before.as_ref(), after.as_ref()),
+    }))
+}
+
+/// Compare two documents (None for a missing side).
+fn diff_values(before: Option<&Value>, after: Option<&Value>) -> Vec<StructuredChange> {
+    let mut changes = Vec::new();
+    match (before, after) {
+        (Some(old), Some(new)) => diff_at(&mut changes, "", old, new),
+        (None, Some(new)) => changes.push(change(StructuredChangeKind::Added, "", None, Some(new))),
+        (Some(old), None) => {
+            changes.push(change(StructuredChangeKind::Removed, "", Some(old), None))
+        }
+        (None, None) => {}
+    }
+    detect_moves(changes)
+}
+
+fn change(
+    kind: StructuredChangeKind,
+    path: &str,
+    old: Option<&Value>,
+    new: Option<&Value>,
+) -> StructuredChange {
+    StructuredChange {
+        kind,
+        path: path.to_string(),
+        old_path: None,
+        old_value: old.cloned(),
+        new_value: new.cloned(),
+    }
+}
+
+fn diff_at(changes: &mut Vec<StructuredChange>, path: &str, old: &Value, new: &Value) {
+    if old == new {
+        return;
+    }
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => diff_objects(changes, path, old, new),
+        (Value::Array(old), Value::Array(new)) => diff_arrays(changes, path, old, new),
+        _ => changes.push(change(
+            StructuredChangeKind::Changed,
+            path,
+            Some(old),
+            Some(new),
+        )),
+    }
+}
+
+fn diff_objects(
+    changes: &mut Vec<StructuredChange>,
+    path: &str,
+    old: &Map<String, Value>,
+    new: &Map<String, Value>,
+) {
+    for (key, old_value) in old {
+        let key_path = key_path(path, key);
+        match new.get(key) {
+            Some(new_value) => diff_at(changes, &key_path, old_value, new_value),
+            None => changes.push(change(
+                StructuredChangeKind::Removed,
+                &key_path,
+                Some(old_value),
+                None,
+            )),
+        }
+    }
+    for (key, new_value) in new.iter().filter(|(key, _)| !old.contains_key(*key)) {
+        changes.push(change(
+            StructuredChangeKind::Added,
+            &key_path(path, key),
+            None,
+            Some(new_value),
+        ));
+    }
+}
+
+/// Compare arrays by their longest common subsequence of equal elements.
+/// Between matches, elements are paired by position and compared recursively;
+/// the rest are additions and removals.
+fn diff_arrays(changes: &mut Vec<StructuredChange>, path: &str, old: &[Value], new: &[Value]) {
+    let matches = if old.len() * new.len() <= MAX_LCS_CELLS {
+        lcs(old, new)
+    } else {
+        vec![]
+    };
+
+    let (mut i, mut j) = (0, 0);
+    for (mi, mj) in matches.into_iter().chain([(old.len(), new.len())]) {
+        let paired = (mi - i).min(mj - j);
+        for k in 0..paired {
+            diff_at(changes, &index_path(path, j + k), &old[i + k], &new[j + k]);
+        }
+        for (k, value) in old[i + paired..mi].iter().enumerate() {
+            changes.push(change(
+                StructuredChangeKind::Removed,
+                &index_path(path, i + paired + k),
+                Some(value),
+                None,
+            ));
+        }
+        for (k, value) in new[j + paired..mj].iter().enumerate() {
+            changes.push(change(
+                StructuredChangeKind::Added,
+                &index_path(path, j + paired + k),
+                None,
+                Some(value),
+            ));
+        }
+        (i, j) = (mi + 1, mj + 1);
+    }
+}
+
+/// Index pairs of a longest common subsequence of equal elements.
+fn lcs(old: &[Value], new: &[Value]) -> Vec<(usize, usize)> {
+    let (n, m) = (old.len(), new.len());
+    let mut table = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            table[i][j] = if old[i] == new[j] {
+                table[i + 1][j + 1] + 1
+            } else {
+                table[i + 1][j].max(table[i][j + 1])
+            };
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if old[i] == new[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if table[i + 1][j] >= table[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+/// Turn removed/added pairs with identical values into moves.
+fn detect_moves(changes: Vec<StructuredChange>) -> Vec<StructuredChange> {
+    let mut result: Vec<StructuredChange> = Vec::with_capacity(changes.len());
+    let mut removed: Vec<usize> = Vec::new();
+
+    for change in changes {
+        if change.kind == StructuredChangeKind::Added {
+            let source = removed.iter().position(|&r| {
+                result[r].old_value == change.new_value && is_move(&result[r], &change)
+            });
+            if let Some(pos) = source {
+                let r = removed.remove(pos);
+                let from = &mut result[r];
+                from.kind = StructuredChangeKind::Moved;
+                from.old_path = Some(std::mem::take(&mut from.path));
+                from.path = change.path;
+                from.new_value = change.new_value;
+                continue;
+            }
+        }
+        if change.kind == StructuredChangeKind::Removed {
+            removed.push(result.len());
+        }
+        result.push(change);
+    }
+    result
+}
+
+/// Whether a removal and an identical addition count as a move. Equal
+/// scalars under different keys are usually coincidence (two dependencies on
+/// the same version), so only non-empty containers and array elements move.
+fn is_move(removed: &StructuredChange, added: &StructuredChange) -> bool {
+    let container = match &added.new_value {
+        Some(Value::Object(map)) => !map.is_empty(),
+        Some(Value::Array(items)) => !items.is_empty(),
+        _ => false,
+    };
+    container || (removed.path.ends_with(']') && added.path.ends_with(']'))
+}
+
+fn key_path(parent: &str, key: &str) -> String {
+    let simple = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+    match (parent.is_empty(), simple) {
+        (true, true) => key.to_string(),
+        (false, true) => format!("{parent}.{key}"),
+        (_, false) => format!("{parent}[{}]", Value::String(key.to_string())),
+    }
+}
+
+fn index_path(parent: &str, index: usize) -> String {
+    format!("{parent}[{index}]")
+}
+
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(n) => Value::from(n),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(items) => Value::Array(items.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(k, v)| (k, toml_to_json(v)))
+                .collect(),
+        ),
+    }
+}
+
+fn yaml_to_json(value: yaml_rust::Yaml) -> Value {
+    use yaml_rust::Yaml;
+    match value {
+        Yaml::String(s) => Value::String(s),
+        Yaml::Integer(n) => Value::from(n),
+        Yaml::Real(s) => s
+            .parse::<f64>()
+            .map(Value::from)
+            .unwrap_or(Value::String(s)),
+        Yaml::Boolean(b) => Value::Bool(b),
+        Yaml::Array(items) => Value::Array(items.into_iter().map(yaml_to_json).collect()),
+        Yaml::Hash(hash) => Value::Object(
+            hash.into_iter()
+                .map(|(k, v)| {
+                    let key = match k {
+                        Yaml::String(s) => s,
+                        other => yaml_to_json(other).to_string(),
+                    };
+                    (key, yaml_to_json(v))
+                })
+                .collect(),
+        ),
+        Yaml::Alias(_) | Yaml::Null | Yaml::BadValue => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn kinds(changes: &[StructuredChange]) -> Vec<(StructuredChangeKind, &str)> {
+        changes.iter().map(|c| (c.kind, c.path.as_str())).collect()
+    }
+
+    #[test]
+    fn test_diff_values() {
+        use StructuredChangeKind::*;
+
+        let old = json!({
+            "name": "app",
+            "version": "1.0.0",
+            "dependencies": {"a": "1", "b": "2"},
+            "files": ["x", "y", "z"],
+        });
+        let new = json!({
+            "dependencies": {"b": "3", "c": "1"},
+            "files": ["y", "z", "x", "w"],
+            "version": "1.1.0",
+            "name": "app",
+        });
+        let changes = diff_values(Some(&old), Some(&new));
+        assert_eq!(
+            kinds(&changes),
+            vec![
+                (Removed, "dependencies.a"),
+                (Changed, "dependencies.b"),
+                (Added, "dependencies.c"),
+                (Moved, "files[2]"),
+                (Added, "files[3]"),
+                (Changed, "version"),
+            ]
+        );
+        assert_eq!(changes[3].old_path.as_deref(), Some("files[0]"));
+        assert_eq!(changes[5].new_value, Some(json!("1.1.0")));
+
+        // Reordering keys alone is not a change
+        let reordered = json!({"b": 1, "a": 2});
+        assert!(diff_values(Some(&json!({"a": 2, "b": 1})), Some(&reordered)).is_empty());
+    }
+
+    #[test]
+    fn test_parse_formats() {
+        let toml = StructuredFormat::Toml
+            .parse("[package]\nname = \"x\"\nversion = \"0.1.0\"\n")
+            .unwrap();
+        assert_eq!(toml, json!({"package": {"name": "x", "version": "0.1.0"}}));
+
+        let yaml = StructuredFormat::Yaml
+            .parse("image: app:1\nports:\n  - 80\n  - 443\n")
+            .unwrap();
+        assert_eq!(yaml, json!({"image": "app:1", "ports": [80, 443]}));
+
+        assert_eq!(
+            StructuredFormat::from_path(Path::new("deploy/prod.YML")),
+            Some(StructuredFormat::Yaml)
+        );
+        assert_eq!(key_path("a", "b.c"), "a[\"b.c\"]");
+    }
+}
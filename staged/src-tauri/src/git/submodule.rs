@@ -0,0 +1,252 @@
+//! Expanding submodule bumps into the submodule's own history.
+//!
+//! In a `FileDiff`, a submodule shows up as `FileContent::Submodule` on each
+//! side. `get_submodule_diff` takes the same spec and path and reports the
+//! commits and files that changed inside the submodule between those SHAs.
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::cli::{self, GitError};
+use super::diff::{get_file_diff, list_diff_files};
+use super::types::{DiffOptions, DiffSpec, FileContent, FileDiffSummary, GitRef};
+
+/// Git's well-known empty tree, used as the base when a submodule was added.
+pub(super) const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// Cap on commits listed per direction, so adding a large submodule stays cheap.
+const MAX_COMMITS: usize = 200;
+
+/// A commit inside a submodule.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmoduleCommit {
+    pub sha: String,
+    pub short_sha: String,
+    pub subject: String,
+    pub author: String,
+    pub timestamp: i64,
+}
+
+/// What changed inside a submodule between the two sides of a diff.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmoduleDiff {
+    pub path: String,
+    /// Pinned commit before (None if the submodule was added)
+    pub old_commit: Option<String>,
+    /// Pinned (or checked-out) commit after (None if the submodule was removed)
+    pub new_commit: Option<String>,
+    /// Whether the submodule's working tree has uncommitted changes
+    pub dirty: bool,
+    /// Commits in new but not old, newest first
+    pub commits: Vec<SubmoduleCommit>,
+    /// Commits in old but not new (the pin moved backwards or sideways)
+    pub removed_commits: Vec<SubmoduleCommit>,
+    /// Files changed in the submodule, including uncommitted changes when dirty
+    pub files: Vec<FileDiffSummary>,
+}
+
+/// Expand a submodule entry of a diff into its commit log and file list.
+///
+/// The submodule must be checked out, and both pinned commits must be
+/// present in its repository (i.e. fetched).
+pub fn get_submodule_diff(
+    repo: &Path,
+    spec: &DiffSpec,
+    path: &Path,
+) -> Result<SubmoduleDiff, GitError> {
+    let diff = get_file_diff(repo, spec, path, &DiffOptions::default())?;
+
+    let pinned = |content: Option<&FileContent>| match content {
+        Some(FileContent::Submodule { commit, dirty }) => Some((commit.clone(), *dirty)),
+        _ => None,
+    };
+    let old = pinned(diff.before.as_ref().map(|f| &f.content));
+    let new = pinned(diff.after.as_ref().map(|f| &f.content));
+    if old.is_none() && new.is_none() {
+        return Err(GitError::CommandFailed(format!(
+            "Not a submodule: {}",
+            path.display()
+        )));
+    }
+
+    let old_commit = old.map(|(commit, _)| commit);
+    let (new_commit, dirty) = match new {
+        Some((commit, dirty)) => (Some(commit), dirty),
+        None => (None, false),
+    };
+
+    let toplevel = cli::run(repo, &["rev-parse", "--show-toplevel"])?;
+    let sub_repo = Path::new(toplevel.trim()).join(path);
+    if !sub_repo.join(".git").exists() {
+        return Err(GitError::CommandFailed(format!(
+            "Submodule not checked out: {}",
+            path.display()
+        )));
+    }
+
+    let (commits, removed_commits, files) = match (&old_commit, &new_commit) {
+        (Some(old), Some(new)) => {
+            let head = if dirty {
+                GitRef::WorkingTree
+            } else {
+                GitRef::Rev(new.clone())
+            };
+            (
+                log_range(&sub_repo, &format!("{old}..{new}"))?,
+                log_range(&sub_repo, &format!("{new}..{old}"))?,
+                list_diff_files(
+                    &sub_repo,
+                    &DiffSpec::custom(GitRef::Rev(old.clone()), head),
+                    &DiffOptions::default(),
+                )?,
+            )
+        }
+        (None, Some(new)) => (
+            log_range(&sub_repo, new)?,
+            vec![],
+            list_diff_files(
+                &sub_repo,
+                &DiffSpec::custom(
+                    GitRef::Rev(EMPTY_TREE.to_string()),
+                    GitRef::Rev(new.clone()),
+                ),
+                &DiffOptions::default(),
+            )?,
+        ),
+        // Removed: the submodule's checkout (if any) no longer belongs to this diff
+        _ => (vec![], vec![], vec![]),
+    };
+
+    Ok(SubmoduleDiff {
+        path: path.to_string_lossy().to_string(),
+        old_commit,
+        new_commit,
+        dirty,
+        commits,
+        removed_commits,
+        files,
+    })
+}
+
+/// `git log` for a revision range in the submodule, newest first.
+fn log_range(sub_repo: &Path, range: &str) -> Result<Vec<SubmoduleCommit>, GitError> {
+    let max_count = format!("--max-count={MAX_COMMITS}");
+    // Format: sha|short_sha|author|timestamp|subject
+    let output = cli::run(
+        sub_repo,
+        &["log", &max_count, "--format=%H|%h|%an|%ct|%s", range],
+    )?;
+
+    Ok(output
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.splitn(5, '|').collect();
+            (parts.len() == 5).then(|| SubmoduleCommit {
+                sha: parts[0].to_string(),
+                short_sha: parts[1].to_string(),
+                author: parts[2].to_string(),
+                timestamp: parts[3].parse().unwrap_or(0),
+                subject: parts[4].to_string(),
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    fn git(repo: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(repo)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?} failed");
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    fn init_repo(repo: &Path) {
+        git(repo, &["init"]);
+        git(repo, &["config", "user.email", "test@test.com"]);
+        git(repo, &["config", "user.name", "Test"]);
+    }
+
+    #[test]
+    fn test_submodule_bump() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir.path().join("lib");
+        let app = dir.path().join("app");
+        std::fs::create_dir_all(&lib).unwrap();
+        std::fs::create_dir_all(&app).unwrap();
+
+        init_repo(&lib);
+        std::fs::write(lib.join("a.txt"), "v1\n").unwrap();
+        git(&lib, &["add", "."]);
+        git(&lib, &["commit", "-m", "v1"]);
+
+        init_repo(&app);
+        git(
+            &app,
+            &[
+                "-c",
+                "protocol.file.allow=always",
+                "submodule",
+                "add",
+                lib.to_str().unwrap(),
+                "vendor/lib",
+            ],
+        );
+        git(&app, &["commit", "-m", "add lib"]);
+
+        // Bump the submodule by one commit, and leave it dirty
+        let sub = app.join("vendor/lib");
+        std::fs::write(sub.join("b.txt"), "new\n").unwrap();
+        git(&sub, &["add", "."]);
+        git(
+            &sub,
+            &[
+                "-c",
+                "user.email=t@t",
+                "-c",
+                "user.name=T",
+                "commit",
+                "-m",
+                "add b",
+            ],
+        );
+        let new_sha = git(&sub, &["rev-parse", "HEAD"]).trim().to_string();
+        std::fs::write(sub.join("a.txt"), "edited\n").unwrap();
+
+        let path = Path::new("vendor/lib");
+        let diff = get_file_diff(
+            &app,
+            &DiffSpec::uncommitted(),
+            path,
+            &DiffOptions::default(),
+        )
+        .unwrap();
+        assert!(matches!(
+            diff.after.unwrap().content,
+            FileContent::Submodule { ref commit, dirty: true } if *commit == new_sha
+        ));
+
+        let sub_diff = get_submodule_diff(&app, &DiffSpec::uncommitted(), path).unwrap();
+        assert!(sub_diff.dirty);
+        assert_eq!(sub_diff.new_commit.as_deref(), Some(new_sha.as_str()));
+        assert_eq!(sub_diff.commits.len(), 1);
+        assert_eq!(sub_diff.commits[0].subject, "add b");
+        assert!(sub_diff.removed_commits.is_empty());
+        let mut files: Vec<String> = sub_diff
+            .files
+            .iter()
+            .map(|f| f.path().to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["a.txt", "b.txt"]);
+    }
+}
@@ -5,8 +5,13 @@ use std::path::PathBuf;
 /// Used for DiffId storage keys.
 pub const WORKDIR: &str = "WORKDIR";
 
+/// Sentinel value representing the index (staging area).
+/// Used for DiffId storage keys.
+pub const INDEX: &str = "INDEX";
+
 /// Identifies a diff between two repository states for storage (reviews).
-/// Uses resolved SHAs or WORKDIR sentinel, not symbolic refs.
+/// Uses resolved SHAs or WORKDIR sentinel, not symbolic refs, and
+/// `fs:<content hash>` for filesystem paths.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct DiffId {
     pub before: String,
@@ -33,6 +38,10 @@ impl DiffId {
 pub enum GitRef {
     /// The working tree (uncommitted changes)
     WorkingTree,
+    /// The index (staging area).
+    /// Valid as head against a commit (staged changes) or as base against
+    /// the working tree (unstaged changes).
+    Index,
     /// Anything that resolves to a commit: SHA, branch, tag, origin/main, HEAD~3, etc.
     Rev(String),
     /// Merge-base between the default branch and HEAD.
@@ -41,18 +50,39 @@ pub enum GitRef {
     /// Merge-base between a specific branch and a head ref.
     /// Format: [base_branch, head_ref] - computes merge-base(base_branch, head_ref)
     MergeBaseOf([String; 2]),
+    /// A stash entry (`stash@{0}` or the stash commit SHA), viewed as the
+    /// stashed working tree plus any untracked files stashed with `-u`.
+    /// Resolved to a synthesized tree at diff-time.
+    Stash(String),
+    /// A file or directory on disk, which needn't be in any repository.
+    /// Only valid paired with another Path.
+    Path(String),
+    /// A patch, diff or mbox file. As head: the files after applying it.
+    /// As base (paired with the same Patch): the pre-image blobs named in
+    /// its `index` lines. Resolved at diff-time.
+    Patch(String),
+    /// `[rev, onto, upstream]`: the tree of `rev` rebased onto the point
+    /// where `onto` branched from `upstream`. Resolved to a synthesized tree
+    /// at diff-time.
+    Rebased([String; 3]),
 }
 
 impl GitRef {
     /// String representation for git commands
     /// WorkingTree is represented as empty string (git uses working tree by default)
+    /// Index has no rev form either; callers select it with `--cached`
     /// MergeBase/MergeBaseOf should be resolved before calling this
     pub fn as_git_arg(&self) -> Option<&str> {
         match self {
             GitRef::WorkingTree => None,
+            GitRef::Index => None,
             GitRef::Rev(s) => Some(s),
             GitRef::MergeBase => panic!("MergeBase must be resolved before use"),
             GitRef::MergeBaseOf(_) => panic!("MergeBaseOf must be resolved before use"),
+            GitRef::Stash(_) => panic!("Stash must be resolved before use"),
+            GitRef::Path(_) => panic!("Path has no git form"),
+            GitRef::Patch(_) => panic!("Patch must be resolved before use"),
+            GitRef::Rebased(_) => panic!("Rebased must be resolved before use"),
         }
     }
 
@@ -60,9 +90,14 @@ impl GitRef {
     pub fn display(&self) -> &str {
         match self {
             GitRef::WorkingTree => "@",
+            GitRef::Index => "index",
             GitRef::Rev(s) => s,
             GitRef::MergeBase => "merge-base",
             GitRef::MergeBaseOf(_) => "merge-base",
+            GitRef::Stash(s) => s,
+            GitRef::Path(p) => p,
+            GitRef::Patch(p) => p,
+            GitRef::Rebased([rev, ..]) => rev,
         }
     }
 }
@@ -83,6 +118,22 @@ impl DiffSpec {
         }
     }
 
+    /// Staged changes: HEAD..index
+    pub fn staged() -> Self {
+        Self {
+            base: GitRef::Rev("HEAD".to_string()),
+            head: GitRef::Index,
+        }
+    }
+
+    /// Unstaged changes: index..@
+    pub fn unstaged() -> Self {
+        Self {
+            base: GitRef::Index,
+            head: GitRef::WorkingTree,
+        }
+    }
+
     /// Last commit: HEAD~1..HEAD
     pub fn last_commit() -> Self {
         Self {
@@ -91,17 +142,109 @@ impl DiffSpec {
         }
     }
 
+    /// A stash: the commit it was taken on..the stashed changes (incl. untracked)
+    pub fn stash(stash: &str) -> Self {
+        Self {
+            base: GitRef::Rev(format!("{stash}^1")),
+            head: GitRef::Stash(stash.to_string()),
+        }
+    }
+
+    /// Two files or directories on disk, outside git
+    pub fn paths(before: impl Into<String>, after: impl Into<String>) -> Self {
+        Self {
+            base: GitRef::Path(before.into()),
+            head: GitRef::Path(after.into()),
+        }
+    }
+
+    /// A patch file against its own base (the blobs in its `index` lines)
+    pub fn patch(file: impl Into<String>) -> Self {
+        let file = file.into();
+        Self {
+            base: GitRef::Patch(file.clone()),
+            head: GitRef::Patch(file),
+        }
+    }
+
+    /// Interdiff between two versions of a branch: the old tip rebased onto
+    /// the new version's base..the new tip, so upstream changes drop out
+    pub fn interdiff(old: &str, new: &str, upstream: &str) -> Self {
+        Self {
+            base: GitRef::Rebased([old.to_string(), new.to_string(), upstream.to_string()]),
+            head: GitRef::Rev(new.to_string()),
+        }
+    }
+
     /// Custom range
     pub fn custom(base: GitRef, head: GitRef) -> Self {
         Self { base, head }
     }
 
+    /// Both paths, if this compares two filesystem paths.
+    pub fn as_paths(&self) -> Option<(&std::path::Path, &std::path::Path)> {
+        match (&self.base, &self.head) {
+            (GitRef::Path(base), GitRef::Path(head)) => {
+                Some((std::path::Path::new(base), std::path::Path::new(head)))
+            }
+            _ => None,
+        }
+    }
+
     /// Display as "base..head"
     pub fn display(&self) -> String {
         format!("{}..{}", self.base.display(), self.head.display())
     }
 }
 
+/// How whitespace differences are treated when diffing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WhitespaceMode {
+    /// Whitespace changes are regular changes
+    #[default]
+    None,
+    /// Ignore all whitespace (`git diff -w`)
+    IgnoreAll,
+    /// Ignore changes in the amount of whitespace (`git diff -b`)
+    IgnoreChange,
+    /// Ignore whitespace at end of line (`git diff --ignore-space-at-eol`)
+    IgnoreEol,
+}
+
+/// Line diff algorithm
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffAlgorithm {
+    /// git's default
+    #[default]
+    Myers,
+    /// Myers, spending extra time to find the smallest diff
+    Minimal,
+    Patience,
+    Histogram,
+}
+
+/// Options controlling how a DiffSpec is diffed.
+/// Defaults match plain `git diff`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiffOptions {
+    pub whitespace: WhitespaceMode,
+    /// Ignore changes whose lines are all blank
+    pub ignore_blank_lines: bool,
+    pub algorithm: DiffAlgorithm,
+    /// Context lines for unified diff output (None = git's default of 3).
+    /// Alignments are always computed without context.
+    pub context_lines: Option<u32>,
+    /// Similarity percentage (0-100) for rename detection (None = git's default of 50)
+    pub rename_threshold: Option<u8>,
+    /// Similarity percentage (0-100) for copy detection (None = don't detect copies)
+    pub copy_threshold: Option<u8>,
+    /// Tokenizer for intra-line changes
+    pub inline_tokenizer: InlineTokenizer,
+}
+
 /// A contiguous range of lines (0-indexed, exclusive end)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Span {
@@ -123,12 +266,48 @@ impl Span {
     }
 }
 
-/// Content of a file - either text lines or binary marker
+/// Content of a file - text lines, binary metadata, or a submodule/LFS pointer
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum FileContent {
-    Text { lines: Vec<String> },
-    Binary,
+    Text {
+        lines: Vec<String>,
+    },
+    Binary(BinaryInfo),
+    /// A submodule (gitlink) pinned at `commit`.
+    /// `dirty` is only ever set for the working tree: the submodule has
+    /// uncommitted changes or untracked files.
+    Submodule {
+        commit: String,
+        dirty: bool,
+    },
+    /// A Git LFS pointer whose object isn't in the local LFS store.
+    /// `oid` is the object's SHA-256; `size` its size in bytes.
+    LfsPointer {
+        oid: String,
+        size: u64,
+    },
+}
+
+/// Size, hash and type of a binary file's content.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BinaryInfo {
+    /// Size in bytes
+    pub size: u64,
+    /// Git blob id of the content; equal hashes mean identical content
+    pub hash: String,
+    /// MIME type, from the content's magic bytes or else the file extension
+    pub mime: String,
+    /// Pixel dimensions, for images in a recognized format
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<ImageSize>,
+}
+
+/// Pixel dimensions of an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageSize {
+    pub width: u32,
+    pub height: u32,
 }
 
 /// A file with its path and content
@@ -136,6 +315,44 @@ pub enum FileContent {
 pub struct File {
     pub path: String,
     pub content: FileContent,
+    /// Detected text encoding (None unless the content is text)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<TextEncoding>,
+    /// Line ending style (None unless the content is text with line breaks)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eol: Option<LineEnding>,
+}
+
+impl File {
+    /// A file without encoding or line-ending information (non-text content).
+    pub fn new(path: impl Into<String>, content: FileContent) -> Self {
+        Self {
+            path: path.into(),
+            content,
+            encoding: None,
+            eol: None,
+        }
+    }
+}
+
+/// Encoding a text file was decoded from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextEncoding {
+    /// WHATWG encoding name, e.g. "UTF-8", "UTF-16LE", "windows-1252"
+    pub name: String,
+    /// Whether the file starts with a byte-order mark
+    pub bom: bool,
+}
+
+/// Line ending style of a text file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    Cr,
+    /// More than one style in the same file
+    Mixed,
 }
 
 /// Summary of a file in the diff (for sidebar)
@@ -145,9 +362,130 @@ pub struct File {
 pub struct FileDiffSummary {
     pub before: Option<PathBuf>,
     pub after: Option<PathBuf>,
+    /// Lines added (None for binary files, or if stats couldn't be computed)
+    #[serde(default)]
+    pub additions: Option<u32>,
+    /// Lines removed (None for binary files, or if stats couldn't be computed)
+    #[serde(default)]
+    pub deletions: Option<u32>,
+    /// Whether git treats the file as binary
+    #[serde(default)]
+    pub binary: bool,
+    /// Mode before the change (None if added or unknown)
+    #[serde(default)]
+    pub old_mode: Option<FileMode>,
+    /// Mode after the change (None if deleted or unknown)
+    #[serde(default)]
+    pub new_mode: Option<FileMode>,
+    /// Marked `linguist-generated` in .gitattributes (collapsed by default)
+    #[serde(default)]
+    pub generated: bool,
+    /// Marked `linguist-vendored` in .gitattributes
+    #[serde(default)]
+    pub vendored: bool,
+    /// Dependency changes, when the file is a recognized lockfile
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lockfile: Option<LockfileSummary>,
+    /// Only a patch's hunks are known: lines outside them are blank
+    #[serde(default)]
+    pub hunks_only: bool,
+}
+
+/// Package manager a lockfile belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LockfileKind {
+    /// Cargo.lock
+    Cargo,
+    /// package-lock.json
+    Npm,
+    /// yarn.lock (classic and berry)
+    Yarn,
+    /// poetry.lock
+    Poetry,
+}
+
+/// How a locked dependency changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyChangeKind {
+    Added,
+    Removed,
+    Upgraded,
+    Downgraded,
+    /// Same version, different source (registry, git revision, ...)
+    SourceChanged,
+}
+
+/// A dependency whose locked version or source changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencyChange {
+    pub name: String,
+    pub kind: DependencyChangeKind,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+    pub old_source: Option<String>,
+    pub new_source: Option<String>,
+}
+
+/// Dependency-level summary of a lockfile change.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockfileSummary {
+    pub kind: LockfileKind,
+    /// Sorted by package name
+    pub changes: Vec<DependencyChange>,
+}
+
+/// Kind of a tree entry, from its git file mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileMode {
+    /// 100644
+    Regular,
+    /// 100755
+    Executable,
+    /// 120000
+    Symlink,
+    /// 160000 (gitlink)
+    Submodule,
+}
+
+impl FileMode {
+    /// Parse an octal git mode such as "100755". Returns None for "000000" (absent).
+    pub fn from_octal(mode: &str) -> Option<Self> {
+        match mode {
+            "100644" | "100664" => Some(FileMode::Regular),
+            "100755" => Some(FileMode::Executable),
+            "120000" => Some(FileMode::Symlink),
+            "160000" => Some(FileMode::Submodule),
+            _ => None,
+        }
+    }
 }
 
 impl FileDiffSummary {
+    /// A summary with paths only; stats are filled in separately.
+    pub fn new(before: Option<PathBuf>, after: Option<PathBuf>) -> Self {
+        Self {
+            before,
+            after,
+            additions: None,
+            deletions: None,
+            binary: false,
+            old_mode: None,
+            new_mode: None,
+            generated: false,
+            vendored: false,
+            lockfile: None,
+            hunks_only: false,
+        }
+    }
+
+    /// Whether the file mode changed (e.g. gained the executable bit)
+    pub fn is_mode_changed(&self) -> bool {
+        matches!((self.old_mode, self.new_mode), (Some(old), Some(new)) if old != new)
+    }
+
     /// The primary path to use for this file (after if exists, else before)
     pub fn path(&self) -> &PathBuf {
         self.after.as_ref().or(self.before.as_ref()).unwrap()
@@ -178,6 +516,78 @@ pub struct Alignment {
     pub changed: bool,
 }
 
+/// Lines picked out of a file's diff for partial staging or committing.
+/// Spans index into the before/after sides of the diff (0-indexed, exclusive end).
+/// Removed lines outside `before` are kept; added lines outside `after` are dropped.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineSelection {
+    /// Removed lines to include (before side)
+    #[serde(default)]
+    pub before: Vec<Span>,
+    /// Added lines to include (after side)
+    #[serde(default)]
+    pub after: Vec<Span>,
+}
+
+impl LineSelection {
+    /// Select every line of the given changed alignments (whole-hunk selection).
+    pub fn from_alignments(alignments: &[Alignment]) -> Self {
+        let changed = alignments.iter().filter(|a| a.changed);
+        Self {
+            before: changed.clone().map(|a| a.before).collect(),
+            after: changed.map(|a| a.after).collect(),
+        }
+    }
+
+    /// True if the before-side line is selected.
+    pub fn includes_before(&self, line: u32) -> bool {
+        self.before.iter().any(|s| s.start <= line && line < s.end)
+    }
+
+    /// True if the after-side line is selected.
+    pub fn includes_after(&self, line: u32) -> bool {
+        self.after.iter().any(|s| s.start <= line && line < s.end)
+    }
+}
+
+/// How lines are split into tokens for intra-line diffing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InlineTokenizer {
+    /// Identifier runs and whitespace runs are single tokens; punctuation is per-char
+    #[default]
+    Word,
+    /// Every character is a token
+    Char,
+}
+
+/// A range of characters within a line (0-indexed, exclusive end).
+/// Offsets count UTF-16 code units, matching JavaScript string indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CharRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl CharRange {
+    pub fn new(start: u32, end: u32) -> Self {
+        Self { start, end }
+    }
+}
+
+/// Intra-line changes for a pair of lines in a changed region
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InlineChange {
+    /// Line index in the before file
+    pub before_line: u32,
+    /// Line index in the after file
+    pub after_line: u32,
+    /// Changed ranges within the before line
+    pub before: Vec<CharRange>,
+    /// Changed ranges within the after line
+    pub after: Vec<CharRange>,
+}
+
 /// Full diff content for rendering a single file
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FileDiff {
@@ -187,4 +597,104 @@ pub struct FileDiff {
     pub after: Option<File>,
     /// How lines map between before/after
     pub alignments: Vec<Alignment>,
+    /// Intra-line changes for paired lines in changed alignments
+    #[serde(default)]
+    pub inline_changes: Vec<InlineChange>,
+    /// Syntax highlighting, when requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub highlights: Option<DiffHighlights>,
+}
+
+/// Which lines of each side to load in a windowed diff.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum DiffWindow {
+    /// Explicit line ranges of each side (e.g. the visible viewport)
+    Ranges {
+        #[serde(default)]
+        before: Vec<Span>,
+        #[serde(default)]
+        after: Vec<Span>,
+    },
+    /// Changed regions plus `context` lines around each
+    Hunks { context: u32 },
+}
+
+/// Consecutive lines of one side, starting at line `start` (0-indexed).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineChunk {
+    pub start: u32,
+    pub lines: Vec<String>,
+}
+
+/// One side of a windowed diff: the file's metadata and only the loaded lines.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileWindow {
+    pub path: String,
+    /// Total lines in the file (0 unless the content is text)
+    pub line_count: u32,
+    /// Loaded line ranges, sorted and non-overlapping (text only)
+    pub chunks: Vec<LineChunk>,
+    /// The content when it isn't text (binary, submodule, LFS pointer);
+    /// None for text, whose lines are in `chunks`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<FileContent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<TextEncoding>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eol: Option<LineEnding>,
+}
+
+/// A `FileDiff` with only some lines of each side, for very large files.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileDiffWindow {
+    /// File before the change (None if added)
+    pub before: Option<FileWindow>,
+    /// File after the change (None if deleted)
+    pub after: Option<FileWindow>,
+    /// How lines map between before/after, for the whole file
+    pub alignments: Vec<Alignment>,
+    /// Intra-line changes for paired lines within the loaded chunks
+    #[serde(default)]
+    pub inline_changes: Vec<InlineChange>,
+}
+
+/// A styled run of characters within a line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenSpan {
+    /// Start character (not byte) offset, inclusive
+    pub start: u32,
+    /// End character offset, exclusive
+    pub end: u32,
+    /// Foreground color as #rrggbb or #rrggbbaa
+    pub color: String,
+    #[serde(default)]
+    pub bold: bool,
+    #[serde(default)]
+    pub italic: bool,
+    #[serde(default)]
+    pub underline: bool,
+}
+
+/// Token spans for every line of a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileHighlights {
+    /// Syntax used, e.g. "Rust"
+    pub syntax: String,
+    /// One entry per line of the file
+    pub lines: Vec<Vec<TokenSpan>>,
+}
+
+/// Syntax highlighting for both sides of a diff.
+/// A side is None when it's missing, binary, or in a language we don't recognise.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffHighlights {
+    /// Name of the theme the colors come from
+    pub theme: String,
+    /// Default text color of the theme
+    pub foreground: String,
+    /// Background color of the theme
+    pub background: String,
+    pub before: Option<FileHighlights>,
+    pub after: Option<FileHighlights>,
 }
@@ -0,0 +1,248 @@
+//! Windowed diffs for very large files.
+//!
+//! `get_file_diff` sends both sides in full, which freezes the viewer for
+//! files with hundreds of thousands of lines. `get_file_diff_window` returns
+//! the full alignments but only the requested lines of each side, and by
+//! default falls back to just the changed regions once a file is too large.
+
+use std::path::Path;
+
+use super::cli::GitError;
+use super::diff::load_file_diff;
+use super::inline;
+use super::types::{
+    Alignment, DiffOptions, DiffSpec, DiffWindow, File, FileContent, FileDiffWindow, FileWindow,
+    LineChunk, Span,
+};
+
+/// Above this many lines on either side, diffs without an explicit window
+/// only load the changed regions.
+pub const LARGE_DIFF_LINES: u32 = 20_000;
+
+/// Context around changed regions when the size guard kicks in.
+const GUARD_CONTEXT_LINES: u32 = 3;
+
+/// Get a diff with only some lines of each side loaded.
+///
+/// With `window` None, small files are loaded in full and files over
+/// `LARGE_DIFF_LINES` lines get `DiffWindow::Hunks` with a few lines of context.
+pub fn get_file_diff_window(
+    repo: &Path,
+    spec: &DiffSpec,
+    path: &Path,
+    options: &DiffOptions,
+    window: Option<&DiffWindow>,
+) -> Result<FileDiffWindow, GitError> {
+    let diff = load_file_diff(repo, spec, path, options)?;
+    let before_len = line_count(&diff.before);
+    let after_len = line_count(&diff.after);
+
+    let (before_ranges, after_ranges) = match window {
+        Some(DiffWindow::Ranges { before, after }) => (before.clone(), after.clone()),
+        Some(DiffWindow::Hunks { context }) => hunk_ranges(&diff.alignments, *context),
+        None if before_len.max(after_len) <= LARGE_DIFF_LINES => (
+            vec![Span::new(0, before_len)],
+            vec![Span::new(0, after_len)],
+        ),
+        None => hunk_ranges(&diff.alignments, GUARD_CONTEXT_LINES),
+    };
+    let before_ranges = normalize(before_ranges, before_len);
+    let after_ranges = normalize(after_ranges, after_len);
+
+    // Only tokenize changed regions that are (at least partly) loaded
+    let loaded: Vec<Alignment> = diff
+        .alignments
+        .iter()
+        .filter(|a| {
+            a.changed && (overlaps(&before_ranges, a.before) || overlaps(&after_ranges, a.after))
+        })
+        .cloned()
+        .collect();
+    let inline_changes = inline::compute_inline_changes(
+        &diff.before,
+        &diff.after,
+        &loaded,
+        options.inline_tokenizer,
+    )
+    .into_iter()
+    .filter(|c| contains(&before_ranges, c.before_line) || contains(&after_ranges, c.after_line))
+    .collect();
+
+    Ok(FileDiffWindow {
+        before: diff.before.map(|f| window_file(f, &before_ranges)),
+        after: diff.after.map(|f| window_file(f, &after_ranges)),
+        alignments: diff.alignments,
+        inline_changes,
+    })
+}
+
+fn line_count(file: &Option<File>) -> u32 {
+    match file {
+        Some(File {
+            content: FileContent::Text { lines },
+            ..
+        }) => lines.len() as u32,
+        _ => 0,
+    }
+}
+
+/// Ranges covering each changed alignment plus `context` lines on each side.
+fn hunk_ranges(alignments: &[Alignment], context: u32) -> (Vec<Span>, Vec<Span>) {
+    let expand = |span: Span| {
+        Span::new(
+            span.start.saturating_sub(context),
+            span.end.saturating_add(context),
+        )
+    };
+    alignments
+        .iter()
+        .filter(|a| a.changed)
+        .map(|a| (expand(a.before), expand(a.after)))
+        .unzip()
+}
+
+/// Clamp ranges to the file, drop empty and inverted ones, and merge
+/// overlapping or adjacent ones, in order.
+fn normalize(mut ranges: Vec<Span>, len: u32) -> Vec<Span> {
+    ranges.sort_by_key(|r| r.start);
+    let mut merged: Vec<Span> = Vec::new();
+    for range in ranges {
+        let range = Span::new(range.start.min(len), range.end.min(len));
+        if range.start >= range.end {
+            continue;
+        }
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+fn overlaps(ranges: &[Span], span: Span) -> bool {
+    ranges
+        .iter()
+        .any(|r| r.start < span.end && span.start < r.end)
+}
+
+fn contains(ranges: &[Span], line: u32) -> bool {
+    ranges.iter().any(|r| r.start <= line && line < r.end)
+}
+
+/// Keep only the lines of `file` within `ranges` (already normalized).
+fn window_file(file: File, ranges: &[Span]) -> FileWindow {
+    let (line_count, chunks, content) = match file.content {
+        FileContent::Text { lines } => {
+            let chunks = ranges
+                .iter()
+                .map(|r| LineChunk {
+                    start: r.start,
+                    lines: lines[r.start as usize..r.end as usize].to_vec(),
+                })
+                .collect();
+            (lines.len() as u32, chunks, None)
+        }
+        other => (0, vec![], Some(other)),
+    };
+
+    FileWindow {
+        path: file.path,
+        line_count,
+        chunks,
+        content,
+        encoding: file.encoding,
+        eol: file.eol,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_normalize() {
+        let ranges = vec![
+            Span::new(10, 20),
+            Span::new(0, 5),
+            Span::new(4, 8),
+            Span::new(30, 40),
+        ];
+        assert_eq!(
+            normalize(ranges, 35),
+            vec![Span::new(0, 8), Span::new(10, 20), Span::new(30, 35)]
+        );
+        assert!(normalize(vec![Span::new(50, 60)], 35).is_empty());
+        assert_eq!(
+            normalize(vec![Span::new(10, 5), Span::new(1, 2)], 35),
+            vec![Span::new(1, 2)]
+        );
+    }
+
+    #[test]
+    fn test_diff_window() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        for args in [
+            vec!["init"],
+            vec!["config", "user.email", "test@example.com"],
+            vec!["config", "user.name", "Test"],
+        ] {
+            Command::new("git")
+                .args(&args)
+                .current_dir(repo)
+                .output()
+                .unwrap();
+        }
+
+        let lines: Vec<String> = (0..100).map(|i| format!("line {i}")).collect();
+        std::fs::write(repo.join("big.txt"), lines.join("\n") + "\n").unwrap();
+        for args in [vec!["add", "."], vec!["commit", "-m", "init"]] {
+            Command::new("git")
+                .args(&args)
+                .current_dir(repo)
+                .output()
+                .unwrap();
+        }
+        let mut changed = lines.clone();
+        changed[50] = "line fifty".to_string();
+        std::fs::write(repo.join("big.txt"), changed.join("\n") + "\n").unwrap();
+
+        let path = Path::new("big.txt");
+        let spec = DiffSpec::uncommitted();
+        let options = DiffOptions::default();
+
+        let hunks = DiffWindow::Hunks { context: 2 };
+        let window = get_file_diff_window(repo, &spec, path, &options, Some(&hunks)).unwrap();
+        let after = window.after.unwrap();
+        assert_eq!(after.line_count, 100);
+        assert_eq!(after.chunks.len(), 1);
+        assert_eq!(after.chunks[0].start, 48);
+        assert_eq!(after.chunks[0].lines.len(), 5);
+        assert_eq!(after.chunks[0].lines[2], "line fifty");
+        assert_eq!(window.inline_changes.len(), 1);
+        assert!(window.alignments.iter().any(|a| a.changed));
+
+        // A viewport away from the change loads no inline changes
+        let ranges = DiffWindow::Ranges {
+            before: vec![Span::new(0, 10)],
+            after: vec![Span::new(0, 10)],
+        };
+        let window = get_file_diff_window(repo, &spec, path, &options, Some(&ranges)).unwrap();
+        assert_eq!(window.before.unwrap().chunks[0].lines.len(), 10);
+        assert!(window.inline_changes.is_empty());
+
+        // Inverted ranges (e.g. from the frontend mid-scroll) are dropped
+        let inverted = DiffWindow::Ranges {
+            before: vec![Span::new(10, 5)],
+            after: vec![Span::new(10, 5), Span::new(20, 22)],
+        };
+        let window = get_file_diff_window(repo, &spec, path, &options, Some(&inverted)).unwrap();
+        assert!(window.before.unwrap().chunks.is_empty());
+        assert_eq!(window.after.unwrap().chunks[0].start, 20);
+
+        // Small files load in full without a window
+        let window = get_file_diff_window(repo, &spec, path, &options, None).unwrap();
+        assert_eq!(window.after.unwrap().chunks[0].lines.len(), 100);
+    }
+}
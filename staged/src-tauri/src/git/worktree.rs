@@ -3,6 +3,7 @@
 //! Manages worktrees in a standard location (~/.staged/worktrees/<repo>/<branch>).
 
 use super::cli::{self, GitError};
+use super::submodule::EMPTY_TREE;
 use std::path::{Path, PathBuf};
 
 /// Get the standard worktree base directory.
@@ -270,6 +271,66 @@ pub fn get_commits_since_base(worktree: &Path, base: &str) -> Result<Vec<CommitI
     Ok(commits)
 }
 
+/// Get the first-parent commits in `base..head`, oldest first, each paired
+/// with what it should be diffed against (its first parent, or the empty
+/// tree for a root commit) and the files it changes relative to that.
+pub fn get_commits_with_parents(
+    repo: &Path,
+    base: &str,
+    head: &str,
+) -> Result<Vec<(CommitInfo, String, Vec<String>)>, GitError> {
+    // Records are separated by \x1e; each is a header line followed by the
+    // changed paths. Format: sha|short_sha|parents|author|timestamp|subject
+    // (subject last, as it may contain |)
+    let format = "--format=%x1e%H|%h|%P|%an|%ct|%s";
+    let range = format!("{base}..{head}");
+
+    let output = cli::run(
+        repo,
+        &[
+            "-c",
+            "core.quotePath=false",
+            "log",
+            "--reverse",
+            "--first-parent",
+            // Merges list their changes against the first parent
+            "-m",
+            "--name-only",
+            format,
+            &range,
+        ],
+    )?;
+
+    let mut commits = Vec::new();
+    for record in output.split('\x1e') {
+        let mut lines = record.lines().filter(|l| !l.is_empty());
+        let Some(header) = lines.next() else {
+            continue;
+        };
+        let parts: Vec<&str> = header.splitn(6, '|').collect();
+        if parts.len() < 6 {
+            continue;
+        }
+        let parent = parts[2]
+            .split(' ')
+            .find(|p| !p.is_empty())
+            .unwrap_or(EMPTY_TREE);
+        commits.push((
+            CommitInfo {
+                sha: parts[0].to_string(),
+                short_sha: parts[1].to_string(),
+                subject: parts[5].to_string(),
+                author: parts[3].to_string(),
+                timestamp: parts[4].parse().unwrap_or(0),
+            },
+            parent.to_string(),
+            lines.map(str::to_string).collect(),
+        ));
+    }
+
+    Ok(commits)
+}
+
 /// Check if a branch exists in the repository.
 pub fn branch_exists(repo: &Path, branch_name: &str) -> Result<bool, GitError> {
     let result = cli::run(
@@ -489,4 +550,40 @@ mod tests {
         assert!(path.to_string_lossy().contains("feature-auth-flow"));
         assert!(!path.to_string_lossy().contains("feature/auth-flow"));
     }
+
+    #[test]
+    fn test_commits_with_parents_list_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        let git = |args: &[&str]| cli::run(repo, args).unwrap();
+        git(&["init"]);
+        git(&["config", "user.email", "test@test.com"]);
+        git(&["config", "user.name", "Test"]);
+        git(&["commit", "--allow-empty", "-m", "base"]);
+        std::fs::write(repo.join("a.txt"), "a\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-m", "first | with a pipe"]);
+        std::fs::write(repo.join("a.txt"), "b\n").unwrap();
+        std::fs::write(repo.join("é.txt"), "c\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-m", "second"]);
+        git(&["commit", "--allow-empty", "-m", "empty"]);
+
+        let base = get_parent_commit(repo, "HEAD~2").unwrap().unwrap();
+        let commits = get_commits_with_parents(repo, &base, "HEAD").unwrap();
+        let summary: Vec<_> = commits
+            .iter()
+            .map(|(commit, _, files)| (commit.subject.as_str(), files.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("first | with a pipe", vec!["a.txt".to_string()]),
+                ("second", vec!["a.txt".to_string(), "é.txt".to_string()]),
+                ("empty", vec![]),
+            ]
+        );
+        assert_eq!(commits[0].1, base);
+        assert_eq!(commits[1].1, commits[0].0.sha);
+    }
 }
@@ -0,0 +1,383 @@
+//! Server-side syntax highlighting for diffs.
+//!
+//! Produces per-line token spans for the before/after files of a `FileDiff`
+//! using syntect. Custom VS Code themes (see `themes`) are converted to
+//! syntect themes; otherwise one of syntect's bundled themes is used.
+
+use crate::git::{DiffHighlights, File, FileContent, FileDiff, FileHighlights, TokenSpan};
+use crate::themes;
+use serde::Deserialize;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{
+    Color, FontStyle, ScopeSelectors, Style, StyleModifier, Theme, ThemeItem, ThemeSet,
+    ThemeSettings,
+};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+/// Bundled theme used when no (valid) theme is requested.
+const DEFAULT_THEME: &str = "base16-ocean.dark";
+
+/// Lines longer than this are left unstyled, so minified code doesn't
+/// produce huge span lists. They're still parsed, to keep the state right
+/// for the lines after them.
+const MAX_LINE_LENGTH: usize = 10_000;
+
+/// Options for the highlighting pass.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HighlightOptions {
+    /// Custom theme name (from `themes::discover_custom_themes`) or a
+    /// bundled syntect theme name. Falls back to a bundled dark theme.
+    pub theme: Option<String>,
+}
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme_set() -> &'static ThemeSet {
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+    THEMES.get_or_init(ThemeSet::load_defaults)
+}
+
+/// Fill in `diff.highlights` for both sides of the diff.
+pub fn highlight_file_diff(diff: &mut FileDiff, options: &HighlightOptions) {
+    let (theme_name, theme) = resolve_theme(options.theme.as_deref());
+    let settings = &theme.settings;
+
+    diff.highlights = Some(DiffHighlights {
+        theme: theme_name,
+        foreground: color_to_hex(settings.foreground.unwrap_or(Color::WHITE)),
+        background: color_to_hex(settings.background.unwrap_or(Color::BLACK)),
+        before: diff.before.as_ref().and_then(|f| highlight_file(f, &theme)),
+        after: diff.after.as_ref().and_then(|f| highlight_file(f, &theme)),
+    });
+}
+
+/// Highlight a single file. Returns None for binary files and unknown languages.
+fn highlight_file(file: &File, theme: &Theme) -> Option<FileHighlights> {
+    let FileContent::Text { lines } = &file.content else {
+        return None;
+    };
+    let syntax = find_syntax(&file.path, lines.first().map(String::as_str))?;
+
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let mut highlighted = Vec::with_capacity(lines.len());
+
+    for line in lines {
+        let too_long = line.len() > MAX_LINE_LENGTH;
+        // The newline-terminated syntaxes need the line ending to track state
+        let line = format!("{line}\n");
+        let spans = match highlighter.highlight_line(&line, syntax_set()) {
+            Ok(_) if too_long => Vec::new(),
+            Ok(regions) => regions_to_spans(&regions),
+            Err(e) => {
+                log::warn!("Highlighting {} failed: {e}", file.path);
+                return None;
+            }
+        };
+        highlighted.push(spans);
+    }
+
+    Some(FileHighlights {
+        syntax: syntax.name.clone(),
+        lines: highlighted,
+    })
+}
+
+/// Pick a syntax by extension (or full filename, e.g. "Makefile"), then by
+/// the first line (shebangs, modelines). Plain text counts as unknown.
+fn find_syntax(path: &str, first_line: Option<&str>) -> Option<&'static SyntaxReference> {
+    let syntaxes = syntax_set();
+    let path = Path::new(path);
+
+    let by_name = [path.extension(), path.file_name()]
+        .into_iter()
+        .flatten()
+        .filter_map(|s| s.to_str())
+        .find_map(|token| syntaxes.find_syntax_by_extension(token));
+
+    by_name
+        .or_else(|| first_line.and_then(|line| syntaxes.find_syntax_by_first_line(line)))
+        .filter(|syntax| syntax.name != "Plain Text")
+}
+
+/// Convert syntect regions to character-offset spans, merging neighbours
+/// with identical styles.
+fn regions_to_spans(regions: &[(Style, &str)]) -> Vec<TokenSpan> {
+    let mut spans: Vec<TokenSpan> = Vec::new();
+    let mut offset = 0u32;
+
+    for (style, text) in regions {
+        let text = text.trim_end_matches('\n');
+        let len = text.chars().count() as u32;
+        if len == 0 {
+            continue;
+        }
+
+        let span = TokenSpan {
+            start: offset,
+            end: offset + len,
+            color: color_to_hex(style.foreground),
+            bold: style.font_style.contains(FontStyle::BOLD),
+            italic: style.font_style.contains(FontStyle::ITALIC),
+            underline: style.font_style.contains(FontStyle::UNDERLINE),
+        };
+        offset += len;
+
+        match spans.last_mut() {
+            Some(last)
+                if last.end == span.start
+                    && last.color == span.color
+                    && (last.bold, last.italic, last.underline)
+                        == (span.bold, span.italic, span.underline) =>
+            {
+                last.end = span.end
+            }
+            _ => spans.push(span),
+        }
+    }
+
+    spans
+}
+
+/// Resolve a theme name to a syntect theme: custom themes first, then
+/// bundled ones, then the default.
+fn resolve_theme(name: Option<&str>) -> (String, Theme) {
+    if let Some(name) = name {
+        if let Some(custom) = themes::discover_custom_themes()
+            .into_iter()
+            .find(|t| t.name == name)
+        {
+            match std::fs::read_to_string(&custom.path)
+                .map_err(|e| e.to_string())
+                .and_then(|json| vscode_theme_to_syntect(&json))
+            {
+                Ok(theme) => return (custom.name, theme),
+                Err(e) => log::warn!("Cannot load theme '{name}': {e}"),
+            }
+        }
+        if let Some(theme) = theme_set().themes.get(name) {
+            return (name.to_string(), theme.clone());
+        }
+    }
+    (
+        DEFAULT_THEME.to_string(),
+        theme_set().themes[DEFAULT_THEME].clone(),
+    )
+}
+
+/// The parts of a VS Code theme that matter for token colors.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VsCodeTheme {
+    name: Option<String>,
+    #[serde(default)]
+    colors: std::collections::HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    token_colors: Vec<VsCodeTokenColor>,
+}
+
+#[derive(Deserialize)]
+struct VsCodeTokenColor {
+    scope: Option<VsCodeScope>,
+    #[serde(default)]
+    settings: VsCodeTokenSettings,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum VsCodeScope {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VsCodeTokenSettings {
+    foreground: Option<String>,
+    background: Option<String>,
+    font_style: Option<String>,
+}
+
+/// Convert a VS Code theme (JSON) to a syntect theme.
+///
+/// Token colors map directly: VS Code and syntect both use TextMate scope
+/// selectors. Rules without a scope set the default colors.
+fn vscode_theme_to_syntect(json: &str) -> Result<Theme, String> {
+    let parsed: VsCodeTheme = serde_json::from_str(json).map_err(|e| e.to_string())?;
+
+    let mut settings = ThemeSettings {
+        foreground: ui_color(&parsed, "editor.foreground"),
+        background: ui_color(&parsed, "editor.background"),
+        ..Default::default()
+    };
+
+    let mut scopes = Vec::new();
+    for rule in parsed.token_colors {
+        let style = StyleModifier {
+            foreground: rule.settings.foreground.as_deref().and_then(parse_color),
+            background: rule.settings.background.as_deref().and_then(parse_color),
+            font_style: rule.settings.font_style.as_deref().map(parse_font_style),
+        };
+
+        let selector = match rule.scope {
+            None => {
+                // Global rule: default text colors
+                settings.foreground = style.foreground.or(settings.foreground);
+                settings.background = style.background.or(settings.background);
+                continue;
+            }
+            Some(VsCodeScope::One(scope)) => scope,
+            Some(VsCodeScope::Many(scopes)) => scopes.join(", "),
+        };
+        // Skip selectors syntect can't parse rather than failing the whole theme
+        if let Ok(scope) = ScopeSelectors::from_str(&selector) {
+            scopes.push(ThemeItem { scope, style });
+        }
+    }
+
+    Ok(Theme {
+        name: parsed.name,
+        author: None,
+        settings,
+        scopes,
+    })
+}
+
+/// Look up a workbench color such as "editor.background".
+fn ui_color(theme: &VsCodeTheme, key: &str) -> Option<Color> {
+    theme.colors.get(key)?.as_str().and_then(parse_color)
+}
+
+/// Parse #rgb, #rgba, #rrggbb or #rrggbbaa.
+fn parse_color(s: &str) -> Option<Color> {
+    let hex = s.strip_prefix('#')?;
+    let digits: Vec<u8> = hex
+        .chars()
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<_>>()?;
+
+    let channels: Vec<u8> = match digits.len() {
+        3 | 4 => digits.iter().map(|d| d * 17).collect(),
+        6 | 8 => digits.chunks(2).map(|p| p[0] * 16 + p[1]).collect(),
+        _ => return None,
+    };
+
+    Some(Color {
+        r: channels[0],
+        g: channels[1],
+        b: channels[2],
+        a: channels.get(3).copied().unwrap_or(0xff),
+    })
+}
+
+fn parse_font_style(s: &str) -> FontStyle {
+    s.split_whitespace()
+        .fold(FontStyle::empty(), |style, word| match word {
+            "bold" => style | FontStyle::BOLD,
+            "italic" => style | FontStyle::ITALIC,
+            "underline" => style | FontStyle::UNDERLINE,
+            _ => style,
+        })
+}
+
+fn color_to_hex(color: Color) -> String {
+    if color.a == 0xff {
+        format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+    } else {
+        format!(
+            "#{:02x}{:02x}{:02x}{:02x}",
+            color.r, color.g, color.b, color.a
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_file(path: &str, lines: &[&str]) -> File {
+        File::new(
+            path,
+            FileContent::Text {
+                lines: lines.iter().map(|s| s.to_string()).collect(),
+            },
+        )
+    }
+
+    #[test]
+    fn test_parse_color() {
+        let c = parse_color("#ff8000").unwrap();
+        assert_eq!((c.r, c.g, c.b, c.a), (255, 128, 0, 255));
+        let c = parse_color("#fff8").unwrap();
+        assert_eq!((c.r, c.a), (255, 0x88));
+        assert!(parse_color("red").is_none());
+        assert!(parse_color("#12345").is_none());
+    }
+
+    #[test]
+    fn test_vscode_theme_conversion() {
+        let theme = vscode_theme_to_syntect(
+            r##"{
+                "name": "Test",
+                "colors": { "editor.background": "#101010", "editor.foreground": "#eeeeee" },
+                "tokenColors": [
+                    { "scope": ["keyword", "storage.type"], "settings": { "foreground": "#ff0000", "fontStyle": "bold" } },
+                    { "scope": "comment", "settings": { "foreground": "#00ff00" } }
+                ]
+            }"##,
+        )
+        .unwrap();
+        assert_eq!(theme.scopes.len(), 2);
+        assert_eq!(color_to_hex(theme.settings.background.unwrap()), "#101010");
+
+        let file = text_file("main.rs", &["fn main() {}  // hi"]);
+        let spans = &highlight_file(&file, &theme).unwrap().lines[0];
+        let fn_span = &spans[0];
+        assert_eq!((fn_span.start, fn_span.end), (0, 2));
+        assert_eq!(fn_span.color, "#ff0000");
+        assert!(fn_span.bold);
+        assert!(spans.iter().any(|s| s.color == "#00ff00"));
+        assert_eq!(spans.last().unwrap().end, 19);
+    }
+
+    #[test]
+    fn test_highlight_file_diff_sides() {
+        let mut diff = FileDiff {
+            before: None,
+            after: Some(text_file("script", &["#!/bin/bash", "echo hi"])),
+            alignments: vec![],
+            inline_changes: vec![],
+            highlights: None,
+        };
+        highlight_file_diff(&mut diff, &HighlightOptions::default());
+
+        let highlights = diff.highlights.unwrap();
+        assert_eq!(highlights.theme, DEFAULT_THEME);
+        assert!(highlights.before.is_none());
+        let after = highlights.after.unwrap();
+        assert_eq!(after.lines.len(), 2);
+        assert!(after.syntax.to_lowercase().contains("bash"));
+
+        let unknown = text_file("notes.unknownext", &["hello"]);
+        assert!(highlight_file(&unknown, &theme_set().themes[DEFAULT_THEME]).is_none());
+    }
+
+    #[test]
+    fn test_overlong_lines_keep_parse_state() {
+        let theme = &theme_set().themes[DEFAULT_THEME];
+        let long = format!("/* {}", "x".repeat(MAX_LINE_LENGTH));
+        let short = text_file("lib.rs", &["/* x", "still a comment */ fn f() {}"]);
+        let minified = text_file("lib.rs", &[&long, "still a comment */ fn f() {}"]);
+
+        let short = highlight_file(&short, theme).unwrap();
+        let minified = highlight_file(&minified, theme).unwrap();
+        assert!(minified.lines[0].is_empty());
+        assert_eq!(minified.lines[1], short.lines[1]);
+    }
+}
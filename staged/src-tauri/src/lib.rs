@@ -6,6 +6,7 @@
 pub mod actions;
 pub mod ai;
 pub mod git;
+mod highlight;
 pub mod project;
 mod recent_repos;
 pub mod review;
@@ -16,10 +17,12 @@ mod watcher;
 use ai::analysis::ChangesetAnalysis;
 use ai::{SessionManager, SessionStatus};
 use git::{
-    CreatePrResult, DiffId, DiffSpec, File, FileDiff, FileDiffSummary, GitHubAuthStatus,
-    GitHubSyncResult, GitRef, PullRequest, PullRequestInfo,
+    CommitFile, CreatePrResult, DiffId, DiffOptions, DiffSpec, File, FileDiff, FileDiffSummary,
+    GitHubAuthStatus, GitHubSyncResult, GitRef, LineSelection, PullRequest, PullRequestInfo,
+};
+use review::{
+    Comment, CommentAuthor, Edit, NewComment, NewEdit, Review, StepCommit, SteppedReview,
 };
-use review::{Comment, Edit, NewComment, NewEdit, Review};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use store::{now_timestamp, ContentSegment, SessionFull, Store};
@@ -42,6 +45,7 @@ fn make_diff_id(repo: &Path, spec: &DiffSpec) -> Result<DiffId, String> {
     let resolve = |r: &GitRef| -> Result<String, String> {
         match r {
             GitRef::WorkingTree => Ok("@".to_string()),
+            GitRef::Index => Ok(git::INDEX.to_string()),
             GitRef::Rev(rev) => git::resolve_ref(repo, rev).map_err(|e| e.to_string()),
             GitRef::MergeBase => {
                 // Resolve merge-base to a concrete SHA for stable storage key
@@ -52,6 +56,18 @@ fn make_diff_id(repo: &Path, spec: &DiffSpec) -> Result<DiffId, String> {
                 // Resolve merge-base between specific refs
                 git::merge_base(repo, base_branch, head_ref).map_err(|e| e.to_string())
             }
+            // Key by the stash commit, which stays stable as stash@{n} indices shift
+            GitRef::Stash(stash) => git::resolve_ref(repo, stash).map_err(|e| e.to_string()),
+            // Key by content, so a review follows identical copies
+            GitRef::Path(path) => git::content_hash(Path::new(path)).map_err(|e| e.to_string()),
+            // Keyed by the patch's content, so renaming or moving it keeps its reviews
+            GitRef::Patch(file) => git::content_hash(Path::new(file)).map_err(|e| e.to_string()),
+            // Key by both versions; the synthesized tree has no stable name
+            GitRef::Rebased([rev, onto, _]) => {
+                let rev = git::resolve_ref(repo, rev).map_err(|e| e.to_string())?;
+                let onto = git::resolve_ref(repo, onto).map_err(|e| e.to_string())?;
+                Ok(format!("rebased:{rev}:{onto}"))
+            }
         }
     };
 
@@ -346,6 +362,26 @@ fn search_files(
     git::search_files(path, &ref_name, &query, limit.unwrap_or(20)).map_err(|e| e.to_string())
 }
 
+/// Search file contents at a ref (or WORKDIR) with `git grep`.
+#[tauri::command(rename_all = "camelCase")]
+fn search_content(
+    repo_path: Option<String>,
+    ref_name: String,
+    query: String,
+    options: Option<git::ContentSearchOptions>,
+    limit: Option<usize>,
+) -> Result<Vec<git::ContentMatch>, String> {
+    let path = get_repo_path(repo_path.as_deref());
+    git::search_content(
+        path,
+        &ref_name,
+        &query,
+        &options.unwrap_or_default(),
+        limit.unwrap_or(200),
+    )
+    .map_err(|e| e.to_string())
+}
+
 /// Get the content of a file at a specific ref.
 #[tauri::command(rename_all = "camelCase")]
 fn get_file_at_ref(
@@ -357,6 +393,29 @@ fn get_file_at_ref(
     git::get_file_at_ref(repo, &ref_name, &path).map_err(|e| e.to_string())
 }
 
+/// Blame a file at a specific ref (or WORKDIR).
+#[tauri::command(rename_all = "camelCase")]
+fn blame_file(
+    repo_path: Option<String>,
+    ref_name: String,
+    path: String,
+) -> Result<git::Blame, String> {
+    let repo = get_repo_path(repo_path.as_deref());
+    git::blame_file(repo, &ref_name, &path).map_err(|e| e.to_string())
+}
+
+/// List commits that touched a file, newest first, following renames.
+#[tauri::command(rename_all = "camelCase")]
+fn get_file_history(
+    repo_path: Option<String>,
+    ref_name: String,
+    path: String,
+    limit: Option<usize>,
+) -> Result<Vec<git::FileHistoryEntry>, String> {
+    let repo = get_repo_path(repo_path.as_deref());
+    git::get_file_history(repo, &ref_name, &path, limit).map_err(|e| e.to_string())
+}
+
 // =============================================================================
 // Git Commands
 // =============================================================================
@@ -396,26 +455,173 @@ fn get_merge_base(repo_path: Option<String>, ref1: String, ref2: String) -> Resu
 async fn list_diff_files(
     repo_path: Option<String>,
     spec: DiffSpec,
+    options: Option<DiffOptions>,
 ) -> Result<Vec<FileDiffSummary>, String> {
     let path = repo_path
         .map(PathBuf::from)
         .unwrap_or_else(|| PathBuf::from("."));
+    let options = options.unwrap_or_default();
     tokio::task::spawn_blocking(move || {
-        git::list_diff_files(&path, &spec).map_err(|e| e.to_string())
+        git::list_diff_files(&path, &spec, &options).map_err(|e| e.to_string())
     })
     .await
     .map_err(|e| e.to_string())?
 }
 
 /// Get full diff content for a single file.
+/// When `highlight` is given, also returns syntax highlighting token spans.
+/// Runs on a blocking thread, since highlighting large files takes a while.
 #[tauri::command(rename_all = "camelCase")]
-fn get_file_diff(
+async fn get_file_diff(
     repo_path: Option<String>,
     spec: DiffSpec,
     file_path: String,
+    options: Option<DiffOptions>,
+    highlight: Option<highlight::HighlightOptions>,
 ) -> Result<FileDiff, String> {
+    let path = repo_path
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let options = options.unwrap_or_default();
+    tokio::task::spawn_blocking(move || {
+        let mut diff = git::get_file_diff(&path, &spec, Path::new(&file_path), &options)
+            .map_err(|e| e.to_string())?;
+        if let Some(highlight) = highlight {
+            highlight::highlight_file_diff(&mut diff, &highlight);
+        }
+        Ok(diff)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Get a diff of a single file with only some lines of each side loaded.
+/// Without a `window`, files over `git::LARGE_DIFF_LINES` lines only load
+/// their changed regions.
+#[tauri::command(rename_all = "camelCase")]
+fn get_file_diff_window(
+    repo_path: Option<String>,
+    spec: DiffSpec,
+    file_path: String,
+    options: Option<DiffOptions>,
+    window: Option<git::DiffWindow>,
+) -> Result<git::FileDiffWindow, String> {
+    let path = get_repo_path(repo_path.as_deref());
+    git::get_file_diff_window(
+        path,
+        &spec,
+        Path::new(&file_path),
+        &options.unwrap_or_default(),
+        window.as_ref(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Diff a JSON/YAML/TOML file by key path.
+/// Returns null for files in other formats.
+#[tauri::command(rename_all = "camelCase")]
+fn get_structured_diff(
+    repo_path: Option<String>,
+    spec: DiffSpec,
+    file_path: String,
+) -> Result<Option<git::StructuredDiff>, String> {
+    let path = get_repo_path(repo_path.as_deref());
+    git::get_structured_diff(path, &spec, Path::new(&file_path)).map_err(|e| e.to_string())
+}
+
+/// Diff a Jupyter notebook cell by cell.
+/// Returns null for files that aren't `.ipynb`. Runs on a blocking thread,
+/// since both notebooks are parsed and their cells aligned.
+#[tauri::command(rename_all = "camelCase")]
+async fn get_notebook_diff(
+    repo_path: Option<String>,
+    spec: DiffSpec,
+    file_path: String,
+    options: Option<DiffOptions>,
+) -> Result<Option<git::NotebookDiff>, String> {
+    let path = repo_path
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let options = options.unwrap_or_default();
+    tokio::task::spawn_blocking(move || {
+        git::get_notebook_diff(&path, &spec, Path::new(&file_path), &options)
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Compare two versions of a branch (e.g. before and after a force-push)
+/// commit by commit, like `git range-diff`. Runs on a blocking thread, since
+/// every commit of both series is diffed.
+#[tauri::command(rename_all = "camelCase")]
+async fn get_range_diff(
+    repo_path: Option<String>,
+    old_sha: String,
+    new_sha: String,
+    upstream: String,
+    options: Option<DiffOptions>,
+) -> Result<git::RangeDiff, String> {
+    let path = repo_path
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let options = options.unwrap_or_default();
+    tokio::task::spawn_blocking(move || {
+        git::range_diff(&path, &old_sha, &new_sha, &upstream, &options).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Get both sides of a binary file's content, as base64 (default) or as
+/// temp-file paths, for side-by-side or onion-skin image comparison.
+#[tauri::command(rename_all = "camelCase")]
+fn get_binary_pair(
+    repo_path: Option<String>,
+    spec: DiffSpec,
+    file_path: String,
+    transport: Option<git::BinaryTransport>,
+) -> Result<git::BinaryPair, String> {
+    let path = get_repo_path(repo_path.as_deref());
+    git::get_binary_pair(
+        path,
+        &spec,
+        Path::new(&file_path),
+        transport.unwrap_or_default(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Find code blocks moved within or between the files of a diff.
+/// Runs on a blocking thread, since it loads every file in the diff.
+#[tauri::command(rename_all = "camelCase")]
+async fn detect_moves(
+    repo_path: Option<String>,
+    spec: DiffSpec,
+    options: Option<DiffOptions>,
+    move_options: Option<git::MoveOptions>,
+) -> Result<Vec<git::MovedBlock>, String> {
+    let path = repo_path
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let options = options.unwrap_or_default();
+    let move_options = move_options.unwrap_or_default();
+    tokio::task::spawn_blocking(move || {
+        git::detect_moves(&path, &spec, &options, &move_options).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Expand a submodule entry of a diff into the submodule's commits and changed files.
+#[tauri::command(rename_all = "camelCase")]
+fn get_submodule_diff(
+    repo_path: Option<String>,
+    spec: DiffSpec,
+    file_path: String,
+) -> Result<git::SubmoduleDiff, String> {
     let path = get_repo_path(repo_path.as_deref());
-    git::get_file_diff(path, &spec, Path::new(&file_path)).map_err(|e| e.to_string())
+    git::get_submodule_diff(path, &spec, Path::new(&file_path)).map_err(|e| e.to_string())
 }
 
 /// Create a commit with the specified files.
@@ -431,6 +637,81 @@ fn commit(
     git::commit(path, &paths, &message).map_err(|e| e.to_string())
 }
 
+/// Stage selected lines of a file's diff (hunk- or line-level staging).
+#[tauri::command(rename_all = "camelCase")]
+fn stage_selection(
+    repo_path: Option<String>,
+    spec: DiffSpec,
+    file_path: String,
+    selection: LineSelection,
+) -> Result<(), String> {
+    let path = get_repo_path(repo_path.as_deref());
+    git::stage_selection(path, &spec, Path::new(&file_path), &selection).map_err(|e| e.to_string())
+}
+
+/// Unstage selected lines of a file's staged changes.
+#[tauri::command(rename_all = "camelCase")]
+fn unstage_selection(
+    repo_path: Option<String>,
+    file_path: String,
+    selection: LineSelection,
+) -> Result<(), String> {
+    let path = get_repo_path(repo_path.as_deref());
+    git::unstage_selection(path, Path::new(&file_path), &selection).map_err(|e| e.to_string())
+}
+
+/// Create a commit from whole files and/or selected lines of files.
+/// Returns the short SHA of the new commit.
+#[tauri::command(rename_all = "camelCase")]
+fn commit_selection(
+    repo_path: Option<String>,
+    spec: DiffSpec,
+    files: Vec<CommitFile>,
+    message: String,
+) -> Result<String, String> {
+    let path = get_repo_path(repo_path.as_deref());
+    git::commit_selection(path, &spec, &files, &message).map_err(|e| e.to_string())
+}
+
+/// List stashes, newest first.
+#[tauri::command(rename_all = "camelCase")]
+fn list_stashes(repo_path: Option<String>) -> Result<Vec<git::StashEntry>, String> {
+    let path = get_repo_path(repo_path.as_deref());
+    git::list_stashes(path).map_err(|e| e.to_string())
+}
+
+/// Stash local changes. Returns None if there was nothing to stash.
+#[tauri::command(rename_all = "camelCase")]
+fn create_stash(
+    repo_path: Option<String>,
+    message: Option<String>,
+    include_untracked: bool,
+) -> Result<Option<git::StashEntry>, String> {
+    let path = get_repo_path(repo_path.as_deref());
+    git::create_stash(path, message.as_deref(), include_untracked).map_err(|e| e.to_string())
+}
+
+/// Apply a stash (by SHA or stash@{n}), keeping it in the stash list.
+#[tauri::command(rename_all = "camelCase")]
+fn apply_stash(repo_path: Option<String>, stash: String) -> Result<(), String> {
+    let path = get_repo_path(repo_path.as_deref());
+    git::apply_stash(path, &stash).map_err(|e| e.to_string())
+}
+
+/// Apply a stash (by SHA or stash@{n}) and drop it.
+#[tauri::command(rename_all = "camelCase")]
+fn pop_stash(repo_path: Option<String>, stash: String) -> Result<(), String> {
+    let path = get_repo_path(repo_path.as_deref());
+    git::pop_stash(path, &stash).map_err(|e| e.to_string())
+}
+
+/// Drop a stash (by SHA or stash@{n}).
+#[tauri::command(rename_all = "camelCase")]
+fn drop_stash(repo_path: Option<String>, stash: String) -> Result<(), String> {
+    let path = get_repo_path(repo_path.as_deref());
+    git::drop_stash(path, &stash).map_err(|e| e.to_string())
+}
+
 // =============================================================================
 // GitHub Commands
 // =============================================================================
@@ -529,7 +810,8 @@ async fn fetch_pr(
 /// Sync local review comments to a GitHub PR as a pending review.
 ///
 /// This will delete any existing pending review and create a new one
-/// with all the local comments. Returns the URL to the pending review.
+/// with the local comments, leaving out resolved threads. Returns the URL
+/// to the pending review.
 #[tauri::command(rename_all = "camelCase")]
 async fn sync_review_to_github(
     repo_path: Option<String>,
@@ -686,7 +968,8 @@ async fn generate_pr_description(
     };
 
     // Get list of changed files
-    let files = git::list_diff_files(&path, &spec).map_err(|e| e.to_string())?;
+    let files =
+        git::list_diff_files(&path, &spec, &DiffOptions::default()).map_err(|e| e.to_string())?;
 
     // Build a summary of changes (file list with status)
     let file_summary: String = files
@@ -723,7 +1006,7 @@ async fn generate_pr_description(
         }
 
         let file_path = file.path();
-        if let Ok(diff) = git::get_unified_diff(&path, &spec, file_path) {
+        if let Ok(diff) = git::get_unified_diff(&path, &spec, file_path, &DiffOptions::default()) {
             if !diff.is_empty() {
                 let diff_lines = diff.lines().count();
                 if total_diff_lines + diff_lines <= MAX_DIFF_LINES {
@@ -1041,12 +1324,24 @@ async fn get_buffered_segments(
 // Review Commands
 // =============================================================================
 
+/// Get (or start) the review of a diff. Runs on a blocking thread, since a
+/// new review may first be carried forward from an earlier one.
 #[tauri::command(rename_all = "camelCase")]
-fn get_review(repo_path: Option<String>, spec: DiffSpec) -> Result<Review, String> {
-    let path = get_repo_path(repo_path.as_deref());
-    let store = review::get_store().map_err(|e| e.0)?;
-    let id = make_diff_id(path, &spec)?;
-    store.get_or_create(&id).map_err(|e| e.0)
+async fn get_review(repo_path: Option<String>, spec: DiffSpec) -> Result<Review, String> {
+    let path = repo_path
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    tokio::task::spawn_blocking(move || {
+        let store = review::get_store().map_err(|e| e.0)?;
+        let id = make_diff_id(&path, &spec)?;
+        // Best-effort: a review that can't be carried forward just starts fresh
+        if let Err(e) = review::carry_forward(store, &path, &id) {
+            log::warn!("Failed to carry review forward to {id:?}: {}", e.0);
+        }
+        store.get_or_create(&id).map_err(|e| e.0)
+    })
+    .await
+    .map_err(|e| e.to_string())?
 }
 
 #[tauri::command(rename_all = "camelCase")]
@@ -1075,12 +1370,37 @@ fn delete_comment(comment_id: String) -> Result<(), String> {
     store.delete_comment(&comment_id).map_err(|e| e.0)
 }
 
+/// Reply to a comment, as the user unless another author is given.
+#[tauri::command(rename_all = "camelCase")]
+fn reply_to_comment(
+    comment_id: String,
+    content: String,
+    author: Option<CommentAuthor>,
+) -> Result<Comment, String> {
+    let store = review::get_store().map_err(|e| e.0)?;
+    store
+        .reply_to_comment(&comment_id, &content, author.unwrap_or(CommentAuthor::User))
+        .map_err(|e| e.0)
+}
+
+/// Resolve or reopen the thread a comment belongs to.
+#[tauri::command(rename_all = "camelCase")]
+fn set_comment_resolved(comment_id: String, resolved: bool) -> Result<(), String> {
+    let store = review::get_store().map_err(|e| e.0)?;
+    store.set_resolved(&comment_id, resolved).map_err(|e| e.0)
+}
+
 #[tauri::command(rename_all = "camelCase")]
 fn mark_reviewed(repo_path: Option<String>, spec: DiffSpec, path: String) -> Result<(), String> {
     let repo = get_repo_path(repo_path.as_deref());
     let store = review::get_store().map_err(|e| e.0)?;
     let id = make_diff_id(repo, &spec)?;
-    store.mark_reviewed(&id, &path).map_err(|e| e.0)
+    // Remember the content that was reviewed, so the mark survives new commits
+    // that leave the file alone
+    let blob = git::blob_at(repo, &id.after, &path).unwrap_or(None);
+    store
+        .mark_reviewed_at(&id, &path, blob.as_deref())
+        .map_err(|e| e.0)
 }
 
 #[tauri::command(rename_all = "camelCase")]
@@ -1118,6 +1438,53 @@ fn clear_review(repo_path: Option<String>, spec: DiffSpec) -> Result<(), String>
     store.delete(&id).map_err(|e| e.0)
 }
 
+/// Review a branch commit by commit. `spec` is the branch range; each
+/// step's review is the ordinary review of that commit's own diff
+/// (`parent..sha`), so the per-commit review commands apply to it.
+#[tauri::command(rename_all = "camelCase")]
+async fn get_stepped_review(
+    repo_path: Option<String>,
+    spec: DiffSpec,
+) -> Result<SteppedReview, String> {
+    if !matches!(spec.head, GitRef::Rev(_)) {
+        return Err("Stepped reviews walk commits, so the head must be a commit".to_string());
+    }
+    let repo = repo_path
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+
+    tokio::task::spawn_blocking(move || {
+        let store = review::get_store().map_err(|e| e.0)?;
+        let id = make_diff_id(&repo, &spec)?;
+        let commits = git::get_commits_with_parents(&repo, &id.before, &id.after)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|(commit, parent, files)| StepCommit {
+                sha: commit.sha,
+                parent,
+                subject: commit.subject,
+                files,
+            })
+            .collect();
+        store.get_stepped(&id, commits).map_err(|e| e.0)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Record which commit of a stepped review is being reviewed.
+#[tauri::command(rename_all = "camelCase")]
+fn set_review_step(
+    repo_path: Option<String>,
+    spec: DiffSpec,
+    sha: Option<String>,
+) -> Result<(), String> {
+    let repo = get_repo_path(repo_path.as_deref());
+    let store = review::get_store().map_err(|e| e.0)?;
+    let id = make_diff_id(repo, &spec)?;
+    store.set_current_step(&id, sha.as_deref()).map_err(|e| e.0)
+}
+
 #[tauri::command(rename_all = "camelCase")]
 fn add_reference_file(
     repo_path: Option<String>,
@@ -3690,7 +4057,10 @@ pub fn run() {
             get_home_dir,
             find_recent_repos,
             search_files,
+            search_content,
             get_file_at_ref,
+            blame_file,
+            get_file_history,
             // Git commands
             get_repo_root,
             list_refs,
@@ -3698,7 +4068,22 @@ pub fn run() {
             get_merge_base,
             list_diff_files,
             get_file_diff,
+            get_file_diff_window,
+            get_structured_diff,
+            get_binary_pair,
+            get_notebook_diff,
+            get_range_diff,
+            detect_moves,
+            get_submodule_diff,
             commit,
+            stage_selection,
+            unstage_selection,
+            commit_selection,
+            list_stashes,
+            create_stash,
+            apply_stash,
+            pop_stash,
+            drop_stash,
             // GitHub commands
             check_github_auth,
             list_pull_requests,
@@ -3731,11 +4116,15 @@ pub fn run() {
             add_comment,
             update_comment,
             delete_comment,
+            reply_to_comment,
+            set_comment_resolved,
             mark_reviewed,
             unmark_reviewed,
             record_edit,
             export_review_markdown,
             clear_review,
+            get_stepped_review,
+            set_review_step,
             add_reference_file,
             remove_reference_file,
             // Legacy artifact commands (DiffSpec-based, used by AgentPanel/Sidebar)
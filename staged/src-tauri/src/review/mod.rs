@@ -2,14 +2,15 @@
 //!
 //! Reviews are stored separately from git, keyed by DiffId.
 
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::{Mutex, OnceLock};
 
 use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Manager};
 
-use crate::git::{DiffId, Span};
+use crate::git::{self, DiffId, Span};
 
 // =============================================================================
 // Types
@@ -21,6 +22,10 @@ pub struct Review {
     pub id: DiffId,
     /// Paths that have been marked as reviewed
     pub reviewed: Vec<String>,
+    /// Blob SHA each reviewed file had when it was marked, so a file only
+    /// loses its mark when its content changes (absent for deleted files)
+    #[serde(default)]
+    pub reviewed_at: HashMap<String, String>,
     /// Comments attached to specific locations
     pub comments: Vec<Comment>,
     /// Edits made during review (stored as diffs)
@@ -34,6 +39,7 @@ impl Review {
         Self {
             id,
             reviewed: Vec::new(),
+            reviewed_at: HashMap::new(),
             comments: Vec::new(),
             edits: Vec::new(),
             reference_files: Vec::new(),
@@ -54,6 +60,8 @@ fn default_author() -> CommentAuthor {
 }
 
 /// A comment attached to a specific location in a file.
+/// A comment with a `parent_id` is a reply; it shares its thread root's
+/// path and span, and the root carries the thread's resolved state.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Comment {
     pub id: String,
@@ -66,6 +74,15 @@ pub struct Comment {
     pub category: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub created_at: Option<String>,
+    /// Thread root this replies to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    #[serde(default)]
+    pub resolved: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
 }
 
 impl Comment {
@@ -78,8 +95,58 @@ impl Comment {
             author: CommentAuthor::User,
             category: None,
             created_at: Some(chrono::Utc::now().to_rfc3339()),
+            parent_id: None,
+            resolved: false,
+            resolved_at: None,
+            updated_at: None,
         }
     }
+
+    /// A reply to the thread `root` starts, at the same location.
+    pub fn reply(root: &Comment, content: impl Into<String>, author: CommentAuthor) -> Self {
+        Self {
+            parent_id: Some(root.id.clone()),
+            author,
+            ..Self::new(root.path.clone(), root.span, content)
+        }
+    }
+}
+
+/// A thread root with its replies, oldest first.
+#[derive(Debug, Clone)]
+pub struct CommentThread<'a> {
+    pub root: &'a Comment,
+    pub replies: Vec<&'a Comment>,
+}
+
+/// Group a review's comments into threads, in the order their roots
+/// appear. Replies whose root is missing become roots themselves.
+pub fn threads(comments: &[Comment]) -> Vec<CommentThread<'_>> {
+    let ids: std::collections::HashSet<&str> = comments.iter().map(|c| c.id.as_str()).collect();
+    let is_root = |c: &Comment| !c.parent_id.as_deref().is_some_and(|p| ids.contains(p));
+
+    let mut threads: Vec<CommentThread> = comments
+        .iter()
+        .filter(|c| is_root(c))
+        .map(|root| CommentThread {
+            root,
+            replies: Vec::new(),
+        })
+        .collect();
+    for reply in comments.iter().filter(|c| !is_root(c)) {
+        if let Some(thread) = threads
+            .iter_mut()
+            .find(|t| Some(t.root.id.as_str()) == reply.parent_id.as_deref())
+        {
+            thread.replies.push(reply);
+        }
+    }
+    for thread in &mut threads {
+        thread
+            .replies
+            .sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    }
+    threads
 }
 
 /// An edit made during review, stored as a unified diff.
@@ -101,6 +168,56 @@ impl Edit {
     }
 }
 
+/// A commit of a branch reviewed commit by commit, as listed from git.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepCommit {
+    pub sha: String,
+    /// What the commit is diffed against (its first parent, or the empty tree)
+    pub parent: String,
+    pub subject: String,
+    /// Files the commit changes
+    pub files: Vec<String>,
+}
+
+impl StepCommit {
+    /// The commit's own diff, which keys its review.
+    pub fn diff_id(&self) -> DiffId {
+        DiffId::new(&self.parent, &self.sha)
+    }
+}
+
+/// One commit of a stepped review, with its own review.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitStep {
+    #[serde(flatten)]
+    pub commit: StepCommit,
+    pub review: Review,
+}
+
+/// Where a file was changed and reviewed across a branch's commits.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileRollup {
+    pub path: String,
+    /// Commits that change the file, oldest first
+    pub changed_in: Vec<String>,
+    /// Commits in which the file was marked reviewed, oldest first
+    pub reviewed_in: Vec<String>,
+}
+
+/// A branch reviewed commit by commit. Each commit's review is an ordinary
+/// review of parent..commit; the branch range only stores the position.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SteppedReview {
+    /// The branch range being walked
+    pub id: DiffId,
+    /// Oldest first
+    pub steps: Vec<CommitStep>,
+    /// SHA of the commit being reviewed, once stepping has started
+    pub current: Option<String>,
+    /// Sorted by path
+    pub files: Vec<FileRollup>,
+}
+
 /// Input for creating a new comment (from frontend).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NewComment {
@@ -283,6 +400,15 @@ impl ReviewStore {
                 FOREIGN KEY (before_ref, after_ref) REFERENCES reviews(before_ref, after_ref) ON DELETE CASCADE
             );
 
+            -- Position in a commit-by-commit review of a branch range
+            CREATE TABLE IF NOT EXISTS review_steps (
+                before_ref TEXT NOT NULL,
+                after_ref TEXT NOT NULL,
+                current_sha TEXT,
+                PRIMARY KEY (before_ref, after_ref),
+                FOREIGN KEY (before_ref, after_ref) REFERENCES reviews(before_ref, after_ref) ON DELETE CASCADE
+            );
+
             PRAGMA foreign_keys = ON;
             "#,
         )?;
@@ -293,6 +419,11 @@ impl ReviewStore {
         Self::migrate_add_column(&conn, "comments", "author", "TEXT NOT NULL DEFAULT 'user'")?;
         Self::migrate_add_column(&conn, "comments", "category", "TEXT")?;
         Self::migrate_add_column(&conn, "comments", "created_at", "TEXT")?;
+        Self::migrate_add_column(&conn, "reviewed_files", "blob_sha", "TEXT")?;
+        Self::migrate_add_column(&conn, "comments", "parent_id", "TEXT")?;
+        Self::migrate_add_column(&conn, "comments", "resolved", "INTEGER NOT NULL DEFAULT 0")?;
+        Self::migrate_add_column(&conn, "comments", "resolved_at", "TEXT")?;
+        Self::migrate_add_column(&conn, "comments", "updated_at", "TEXT")?;
 
         Ok(())
     }
@@ -356,14 +487,23 @@ impl ReviewStore {
         }
 
         // Load reviewed files
-        let mut stmt = conn
-            .prepare("SELECT path FROM reviewed_files WHERE before_ref = ?1 AND after_ref = ?2")?;
-        let reviewed: Vec<String> = stmt
-            .query_map(params![&id.before, &id.after], |row| row.get(0))?
+        let mut stmt = conn.prepare(
+            "SELECT path, blob_sha FROM reviewed_files WHERE before_ref = ?1 AND after_ref = ?2",
+        )?;
+        let marks: Vec<(String, Option<String>)> = stmt
+            .query_map(params![&id.before, &id.after], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?
             .collect::<std::result::Result<Vec<_>, _>>()?;
+        let reviewed = marks.iter().map(|(path, _)| path.clone()).collect();
+        let reviewed_at = marks
+            .into_iter()
+            .filter_map(|(path, blob)| Some((path, blob?)))
+            .collect();
 
         let mut stmt = conn.prepare(
-            "SELECT id, path, span_start, span_end, content, author, category, created_at
+            "SELECT id, path, span_start, span_end, content, author, category, created_at,
+                    parent_id, resolved, resolved_at, updated_at
              FROM comments WHERE before_ref = ?1 AND after_ref = ?2",
         )?;
         let comments: Vec<Comment> = stmt
@@ -382,6 +522,10 @@ impl ReviewStore {
                     author,
                     category: row.get(6).ok(),
                     created_at: row.get(7).ok(),
+                    parent_id: row.get(8)?,
+                    resolved: row.get(9)?,
+                    resolved_at: row.get(10)?,
+                    updated_at: row.get(11)?,
                 })
             })?
             .collect::<std::result::Result<Vec<_>, _>>()?;
@@ -409,6 +553,7 @@ impl ReviewStore {
         Ok(Review {
             id: id.clone(),
             reviewed,
+            reviewed_at,
             comments,
             edits,
             reference_files,
@@ -417,15 +562,45 @@ impl ReviewStore {
 
     /// Mark a file as reviewed.
     pub fn mark_reviewed(&self, id: &DiffId, path: &str) -> Result<()> {
+        self.mark_reviewed_at(id, path, None)
+    }
+
+    /// Mark a file as reviewed at the given blob SHA (its content in the
+    /// diff's head), replacing any earlier mark.
+    pub fn mark_reviewed_at(&self, id: &DiffId, path: &str, blob_sha: Option<&str>) -> Result<()> {
         self.get_or_create(id)?;
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "INSERT OR IGNORE INTO reviewed_files (before_ref, after_ref, path) VALUES (?1, ?2, ?3)",
-            params![&id.before, &id.after, path],
+            "INSERT OR REPLACE INTO reviewed_files (before_ref, after_ref, path, blob_sha)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![&id.before, &id.after, path, blob_sha],
         )?;
         Ok(())
     }
 
+    /// Whether a review has been started for the given diff.
+    pub fn exists(&self, id: &DiffId) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn
+            .query_row(
+                "SELECT 1 FROM reviews WHERE before_ref = ?1 AND after_ref = ?2",
+                params![&id.before, &id.after],
+                |_| Ok(true),
+            )
+            .optional()?
+            .unwrap_or(false))
+    }
+
+    /// The head sides of all reviews sharing a base.
+    pub fn heads_for_base(&self, before: &str) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT after_ref FROM reviews WHERE before_ref = ?1")?;
+        let heads = stmt
+            .query_map(params![before], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(heads)
+    }
+
     /// Unmark a file as reviewed.
     pub fn unmark_reviewed(&self, id: &DiffId, path: &str) -> Result<()> {
         let conn = self.conn.lock().unwrap();
@@ -440,45 +615,75 @@ impl ReviewStore {
     pub fn add_comment(&self, id: &DiffId, comment: &Comment) -> Result<()> {
         self.get_or_create(id)?;
         let conn = self.conn.lock().unwrap();
+        insert_comment(&conn, id, comment)
+    }
 
-        let author_str = match comment.author {
-            CommentAuthor::User => "user",
-            CommentAuthor::Ai => "ai",
-        };
-
+    /// Update a comment's content.
+    pub fn update_comment(&self, comment_id: &str, content: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
         conn.execute(
-            "INSERT INTO comments (id, before_ref, after_ref, path, span_start, span_end, content, author, category, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-            params![
-                &comment.id,
-                &id.before,
-                &id.after,
-                &comment.path,
-                comment.span.start,
-                comment.span.end,
-                &comment.content,
-                author_str,
-                &comment.category,
-                &comment.created_at
-            ],
+            "UPDATE comments SET content = ?1, updated_at = ?2 WHERE id = ?3",
+            params![content, chrono::Utc::now().to_rfc3339(), comment_id],
         )?;
         Ok(())
     }
 
-    /// Update a comment's content.
-    pub fn update_comment(&self, comment_id: &str, content: &str) -> Result<()> {
+    /// Delete a comment, and its replies if it starts a thread.
+    pub fn delete_comment(&self, comment_id: &str) -> Result<()> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "UPDATE comments SET content = ?1 WHERE id = ?2",
-            params![content, comment_id],
+            "DELETE FROM comments WHERE id = ?1 OR parent_id = ?1",
+            params![comment_id],
         )?;
         Ok(())
     }
 
-    /// Delete a comment.
-    pub fn delete_comment(&self, comment_id: &str) -> Result<()> {
+    /// Reply to a comment. Replying to a reply adds to the same thread, so
+    /// threads stay one level deep.
+    pub fn reply_to_comment(
+        &self,
+        comment_id: &str,
+        content: &str,
+        author: CommentAuthor,
+    ) -> Result<Comment> {
+        let conn = self.conn.lock().unwrap();
+        let (root_id, before, after): (String, String, String) = conn
+            .query_row(
+                "SELECT COALESCE(parent_id, id), before_ref, after_ref FROM comments WHERE id = ?1",
+                params![comment_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?
+            .ok_or_else(|| ReviewError::new(format!("No comment with id {comment_id}")))?;
+        let root = conn.query_row(
+            "SELECT path, span_start, span_end FROM comments WHERE id = ?1",
+            params![&root_id],
+            |row| {
+                let mut root = Comment::new(
+                    row.get::<_, String>(0)?,
+                    Span::new(row.get(1)?, row.get(2)?),
+                    "",
+                );
+                root.id = root_id.clone();
+                Ok(root)
+            },
+        )?;
+        drop(conn);
+
+        let reply = Comment::reply(&root, content, author);
+        self.add_comment(&DiffId::new(before, after), &reply)?;
+        Ok(reply)
+    }
+
+    /// Resolve or reopen the thread a comment belongs to.
+    pub fn set_resolved(&self, comment_id: &str, resolved: bool) -> Result<()> {
         let conn = self.conn.lock().unwrap();
-        conn.execute("DELETE FROM comments WHERE id = ?1", params![comment_id])?;
+        let resolved_at = resolved.then(|| chrono::Utc::now().to_rfc3339());
+        conn.execute(
+            "UPDATE comments SET resolved = ?1, resolved_at = ?2
+             WHERE id = (SELECT COALESCE(parent_id, id) FROM comments WHERE id = ?3)",
+            params![resolved, resolved_at, comment_id],
+        )?;
         Ok(())
     }
 
@@ -521,6 +726,102 @@ impl ReviewStore {
         Ok(())
     }
 
+    /// Load a commit-by-commit review of the branch range `id`, given its
+    /// commits (oldest first). Each commit's review is loaded by its own
+    /// `DiffId`, so it's shared with viewing that commit on its own.
+    pub fn get_stepped(&self, id: &DiffId, commits: Vec<StepCommit>) -> Result<SteppedReview> {
+        let current = {
+            let conn = self.conn.lock().unwrap();
+            conn.query_row(
+                "SELECT current_sha FROM review_steps WHERE before_ref = ?1 AND after_ref = ?2",
+                params![&id.before, &id.after],
+                |row| row.get::<_, Option<String>>(0),
+            )
+            .optional()?
+            .flatten()
+        };
+
+        let mut steps = Vec::new();
+        let mut files: std::collections::BTreeMap<String, FileRollup> =
+            std::collections::BTreeMap::new();
+        for commit in commits {
+            let review = self.get(&commit.diff_id())?;
+            for path in &commit.files {
+                files
+                    .entry(path.clone())
+                    .or_insert_with(|| FileRollup {
+                        path: path.clone(),
+                        changed_in: Vec::new(),
+                        reviewed_in: Vec::new(),
+                    })
+                    .changed_in
+                    .push(commit.sha.clone());
+            }
+            for path in &review.reviewed {
+                if let Some(rollup) = files.get_mut(path) {
+                    rollup.reviewed_in.push(commit.sha.clone());
+                }
+            }
+            steps.push(CommitStep { commit, review });
+        }
+
+        // A position outside the range (e.g. after a rebase) is stale
+        let current = current.filter(|sha| steps.iter().any(|s| &s.commit.sha == sha));
+        Ok(SteppedReview {
+            id: id.clone(),
+            steps,
+            current,
+            files: files.into_values().collect(),
+        })
+    }
+
+    /// Record which commit of a stepped review is being reviewed.
+    pub fn set_current_step(&self, id: &DiffId, sha: Option<&str>) -> Result<()> {
+        self.get_or_create(id)?;
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO review_steps (before_ref, after_ref, current_sha) VALUES (?1, ?2, ?3)
+             ON CONFLICT (before_ref, after_ref) DO UPDATE SET current_sha = excluded.current_sha",
+            params![&id.before, &id.after, sha],
+        )?;
+        Ok(())
+    }
+
+    /// Create a review with the given marks, comments and reference files,
+    /// all in one transaction. Returns false, writing nothing, if a review
+    /// for `review.id` already exists.
+    pub fn create_seeded(&self, review: &Review) -> Result<bool> {
+        let id = &review.id;
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let created = tx.execute(
+            "INSERT OR IGNORE INTO reviews (before_ref, after_ref) VALUES (?1, ?2)",
+            params![&id.before, &id.after],
+        )?;
+        if created == 0 {
+            return Ok(false);
+        }
+
+        for path in &review.reviewed {
+            tx.execute(
+                "INSERT OR REPLACE INTO reviewed_files (before_ref, after_ref, path, blob_sha)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![&id.before, &id.after, path, review.reviewed_at.get(path)],
+            )?;
+        }
+        for comment in &review.comments {
+            insert_comment(&tx, id, comment)?;
+        }
+        for path in &review.reference_files {
+            tx.execute(
+                "INSERT OR IGNORE INTO reference_files (before_ref, after_ref, path) VALUES (?1, ?2, ?3)",
+                params![&id.before, &id.after, path],
+            )?;
+        }
+        tx.commit()?;
+        Ok(true)
+    }
+
     /// Delete an entire review and all associated data.
     pub fn delete(&self, id: &DiffId) -> Result<()> {
         let conn = self.conn.lock().unwrap();
@@ -533,6 +834,137 @@ impl ReviewStore {
     }
 }
 
+/// Insert a comment into an existing review.
+fn insert_comment(conn: &Connection, id: &DiffId, comment: &Comment) -> Result<()> {
+    let author_str = match comment.author {
+        CommentAuthor::User => "user",
+        CommentAuthor::Ai => "ai",
+    };
+
+    conn.execute(
+        "INSERT INTO comments (id, before_ref, after_ref, path, span_start, span_end, content, author, category, created_at,
+                               parent_id, resolved, resolved_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+        params![
+            &comment.id,
+            &id.before,
+            &id.after,
+            &comment.path,
+            comment.span.start,
+            comment.span.end,
+            &comment.content,
+            author_str,
+            &comment.category,
+            &comment.created_at,
+            &comment.parent_id,
+            comment.resolved,
+            &comment.resolved_at,
+            &comment.updated_at
+        ],
+    )?;
+    Ok(())
+}
+
+// =============================================================================
+// Re-anchoring
+// =============================================================================
+
+/// Seed a new review from the closest earlier review of the same base,
+/// when the branch has moved on by some commits.
+///
+/// Reviewed marks carry over for files whose blob is unchanged; comments
+/// carry over with their spans mapped through the intervening changes
+/// (comments on changed lines land on the replacement lines). Comments on
+/// files that are gone from the new head (deleted or renamed) are dropped.
+/// Reference files carry over too; edits don't, as they belong to the old head.
+///
+/// Returns the review carried from, if any. Does nothing if `id` already
+/// has a review.
+pub fn carry_forward(store: &ReviewStore, repo: &Path, id: &DiffId) -> Result<Option<DiffId>> {
+    if store.exists(id)? {
+        return Ok(None);
+    }
+    let git_err = |e: git::GitError| ReviewError(e.to_string());
+    let heads = store.heads_for_base(&id.before)?;
+    let Some(from) = git::closest_ancestor(repo, &heads, &id.after).map_err(git_err)? else {
+        return Ok(None);
+    };
+    let from = DiffId::new(&id.before, from);
+    let old = store.get(&from)?;
+    let mut review = Review::new(id.clone());
+
+    for path in &old.reviewed {
+        let reviewed_blob = match old.reviewed_at.get(path) {
+            Some(blob) => Some(blob.clone()),
+            None => git::blob_at(repo, &from.after, path).map_err(git_err)?,
+        };
+        let blob = git::blob_at(repo, &id.after, path).map_err(git_err)?;
+        if let Some(blob) = blob.filter(|b| Some(b) == reviewed_blob.as_ref()) {
+            review.reviewed.push(path.clone());
+            review.reviewed_at.insert(path.clone(), blob);
+        }
+    }
+
+    // Roots first, so replies can point at their carried root
+    let mut comments: Vec<&Comment> = old.comments.iter().collect();
+    comments.sort_by_key(|c| c.parent_id.is_some());
+    let mut new_ids: HashMap<&str, String> = HashMap::new();
+    let mut dropped: HashSet<&str> = HashSet::new();
+    // None for files that no longer exist at the new head
+    let mut hunks_by_path: HashMap<&str, Option<Vec<git::LineHunk>>> = HashMap::new();
+    for comment in comments {
+        if !hunks_by_path.contains_key(comment.path.as_str()) {
+            let hunks = match git::blob_at(repo, &id.after, &comment.path).map_err(git_err)? {
+                Some(_) => Some(
+                    git::line_hunks(repo, &from.after, &id.after, &comment.path)
+                        .map_err(git_err)?,
+                ),
+                None => {
+                    log::info!(
+                        "Not carrying comments on {} forward: it's gone",
+                        comment.path
+                    );
+                    None
+                }
+            };
+            hunks_by_path.insert(&comment.path, hunks);
+        }
+        let parent_dropped = comment
+            .parent_id
+            .as_deref()
+            .is_some_and(|p| dropped.contains(p));
+        let Some(hunks) = hunks_by_path[comment.path.as_str()]
+            .as_ref()
+            .filter(|_| !parent_dropped)
+        else {
+            dropped.insert(&comment.id);
+            continue;
+        };
+        let start = git::map_line(hunks, comment.span.start);
+        let end = match comment.span.end.checked_sub(1) {
+            Some(last) if comment.span.end > comment.span.start => git::map_line(hunks, last) + 1,
+            _ => start,
+        };
+        let carried = Comment {
+            id: uuid::Uuid::new_v4().to_string(),
+            span: Span::new(start, end.max(start)),
+            parent_id: comment
+                .parent_id
+                .as_deref()
+                .and_then(|p| new_ids.get(p).cloned()),
+            ..comment.clone()
+        };
+        new_ids.insert(&comment.id, carried.id.clone());
+        review.comments.push(carried);
+    }
+
+    review.reference_files = old.reference_files.clone();
+    if !store.create_seeded(&review)? {
+        return Ok(None);
+    }
+    Ok(Some(from))
+}
+
 // =============================================================================
 // Export
 // =============================================================================
@@ -541,14 +973,14 @@ impl ReviewStore {
 pub fn export_markdown(review: &Review) -> String {
     let mut md = String::new();
 
-    // Group comments by file
-    let mut comments_by_file: std::collections::HashMap<&str, Vec<&Comment>> =
+    // Group comment threads by file
+    let mut threads_by_file: std::collections::HashMap<&str, Vec<CommentThread>> =
         std::collections::HashMap::new();
-    for comment in &review.comments {
-        comments_by_file
-            .entry(&comment.path)
+    for thread in threads(&review.comments) {
+        threads_by_file
+            .entry(&thread.root.path)
             .or_default()
-            .push(comment);
+            .push(thread);
     }
 
     // Group edits by file
@@ -559,7 +991,7 @@ pub fn export_markdown(review: &Review) -> String {
     }
 
     // Collect all files
-    let mut all_files: Vec<&str> = comments_by_file
+    let mut all_files: Vec<&str> = threads_by_file
         .keys()
         .chain(edits_by_file.keys())
         .copied()
@@ -570,15 +1002,30 @@ pub fn export_markdown(review: &Review) -> String {
     for file in all_files {
         md.push_str(&format!("## {file}\n\n"));
 
-        if let Some(comments) = comments_by_file.get(file) {
-            for comment in comments {
-                let span = &comment.span;
+        if let Some(threads) = threads_by_file.get(file) {
+            for thread in threads {
+                let span = &thread.root.span;
                 let location = if span.end == span.start + 1 {
                     format!("Line {}", span.start + 1)
                 } else {
                     format!("Lines {}-{}", span.start + 1, span.end)
                 };
-                md.push_str(&format!("- **{}**: {}\n", location, comment.content));
+                let status = if thread.root.resolved {
+                    " _(resolved)_"
+                } else {
+                    ""
+                };
+                md.push_str(&format!(
+                    "- **{}**{}: {}\n",
+                    location, status, thread.root.content
+                ));
+                for reply in &thread.replies {
+                    md.push_str(&format!(
+                        "  - _{}_: {}\n",
+                        author_label(&reply.author),
+                        reply.content
+                    ));
+                }
             }
             md.push('\n');
         }
@@ -602,6 +1049,14 @@ pub fn export_markdown(review: &Review) -> String {
     md
 }
 
+/// How a comment's author is named in exports.
+pub fn author_label(author: &CommentAuthor) -> &'static str {
+    match author {
+        CommentAuthor::User => "Reviewer",
+        CommentAuthor::Ai => "AI",
+    }
+}
+
 // =============================================================================
 // Tests
 // =============================================================================
@@ -655,6 +1110,39 @@ mod tests {
         assert!(review.comments.is_empty());
     }
 
+    #[test]
+    fn test_comment_threads() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let store = ReviewStore::open(db_path).unwrap();
+        let id = DiffId::new("main", "feature");
+
+        let root = Comment::new("src/lib.rs", Span::new(4, 6), "Why a loop here?");
+        store.add_comment(&id, &root).unwrap();
+        let answer = store
+            .reply_to_comment(&root.id, "It avoids an allocation", CommentAuthor::Ai)
+            .unwrap();
+        // Replying to a reply stays in the same thread
+        let follow_up = store
+            .reply_to_comment(&answer.id, "Makes sense", CommentAuthor::User)
+            .unwrap();
+        assert_eq!(follow_up.parent_id.as_deref(), Some(root.id.as_str()));
+        assert_eq!(follow_up.span.start, 4);
+
+        store.set_resolved(&follow_up.id, true).unwrap();
+        let review = store.get(&id).unwrap();
+        let threads = threads(&review.comments);
+        assert_eq!(threads.len(), 1);
+        assert!(threads[0].root.resolved);
+        assert!(threads[0].root.resolved_at.is_some());
+        assert_eq!(threads[0].replies.len(), 2);
+        assert_eq!(threads[0].replies[0].author, CommentAuthor::Ai);
+
+        // Deleting the root deletes the thread
+        store.delete_comment(&root.id).unwrap();
+        assert!(store.get(&id).unwrap().comments.is_empty());
+    }
+
     #[test]
     fn test_edits() {
         let dir = tempdir().unwrap();
@@ -693,6 +1181,120 @@ mod tests {
         assert!(review.comments.is_empty());
     }
 
+    #[test]
+    fn test_stepped_review() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let store = ReviewStore::open(db_path).unwrap();
+        let branch = DiffId::new("base", "c2");
+        let commits = vec![
+            StepCommit {
+                sha: "c1".into(),
+                parent: "base".into(),
+                subject: "first".into(),
+                files: vec!["a.rs".into(), "b.rs".into()],
+            },
+            StepCommit {
+                sha: "c2".into(),
+                parent: "c1".into(),
+                subject: "second".into(),
+                files: vec!["a.rs".into()],
+            },
+        ];
+
+        // Each commit has its own review, keyed by parent..commit
+        store.mark_reviewed(&commits[0].diff_id(), "a.rs").unwrap();
+        store.mark_reviewed(&commits[1].diff_id(), "a.rs").unwrap();
+        store
+            .add_comment(
+                &commits[1].diff_id(),
+                &Comment::new("a.rs", Span::new(1, 2), "why?"),
+            )
+            .unwrap();
+        store.set_current_step(&branch, Some("c2")).unwrap();
+
+        let stepped = store.get_stepped(&branch, commits.clone()).unwrap();
+        assert_eq!(stepped.current.as_deref(), Some("c2"));
+        assert_eq!(stepped.steps[1].review.comments.len(), 1);
+        assert_eq!(stepped.files.len(), 2);
+        assert_eq!(stepped.files[0].path, "a.rs");
+        assert_eq!(stepped.files[0].changed_in, vec!["c1", "c2"]);
+        assert_eq!(stepped.files[0].reviewed_in, vec!["c1", "c2"]);
+        assert!(stepped.files[1].reviewed_in.is_empty());
+
+        // A position no longer in the branch is dropped
+        store.set_current_step(&branch, Some("gone")).unwrap();
+        let stepped = store.get_stepped(&branch, commits).unwrap();
+        assert_eq!(stepped.current, None);
+    }
+
+    #[test]
+    fn test_carry_forward() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        std::fs::create_dir(&repo).unwrap();
+        let git = |args: &[&str]| {
+            let output = std::process::Command::new("git")
+                .args(args)
+                .current_dir(&repo)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {args:?} failed");
+            String::from_utf8(output.stdout).unwrap().trim().to_string()
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "test@test.com"]);
+        git(&["config", "user.name", "Test"]);
+        std::fs::write(repo.join("a.txt"), "1\n2\n3\n").unwrap();
+        std::fs::write(repo.join("b.txt"), "b\n").unwrap();
+        std::fs::write(repo.join("c.txt"), "c\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-m", "base"]);
+        let base = git(&["rev-parse", "HEAD"]);
+        std::fs::write(repo.join("a.txt"), "1\n2\n3\n4\n").unwrap();
+        std::fs::write(repo.join("b.txt"), "b2\n").unwrap();
+        git(&["commit", "-am", "first"]);
+        let first = git(&["rev-parse", "HEAD"]);
+
+        let store = ReviewStore::open(dir.path().join("test.db")).unwrap();
+        let old = DiffId::new(&base, &first);
+        let a_blob = git::blob_at(&repo, &first, "a.txt").unwrap();
+        store
+            .mark_reviewed_at(&old, "a.txt", a_blob.as_deref())
+            .unwrap();
+        store.mark_reviewed(&old, "b.txt").unwrap();
+        store
+            .add_comment(&old, &Comment::new("b.txt", Span::new(0, 1), "hmm"))
+            .unwrap();
+        store
+            .add_comment(&old, &Comment::new("a.txt", Span::new(3, 4), "four"))
+            .unwrap();
+        let c_comment = Comment::new("c.txt", Span::new(0, 1), "gone soon");
+        store.add_comment(&old, &c_comment).unwrap();
+        let mut c_reply = Comment::new("c.txt", Span::new(0, 1), "agreed");
+        c_reply.parent_id = Some(c_comment.id.clone());
+        store.add_comment(&old, &c_reply).unwrap();
+
+        // A new commit prepends a line to a.txt, deletes c.txt and leaves b.txt alone
+        std::fs::write(repo.join("a.txt"), "0\n1\n2\n3\n4\n").unwrap();
+        git(&["rm", "-q", "c.txt"]);
+        git(&["commit", "-am", "second"]);
+        let second = git(&["rev-parse", "HEAD"]);
+        let new = DiffId::new(&base, &second);
+
+        assert_eq!(carry_forward(&store, &repo, &new).unwrap(), Some(old));
+        let review = store.get(&new).unwrap();
+        assert_eq!(review.reviewed, vec!["b.txt"]);
+        let a_comment = review.comments.iter().find(|c| c.path == "a.txt").unwrap();
+        assert_eq!((a_comment.span.start, a_comment.span.end), (4, 5));
+        assert!(review.comments.iter().all(|c| c.path != "c.txt"));
+
+        // Only a review's first load is seeded
+        assert_eq!(carry_forward(&store, &repo, &new).unwrap(), None);
+        assert!(!store.create_seeded(&Review::new(new.clone())).unwrap());
+        assert_eq!(store.get(&new).unwrap().comments.len(), 2);
+    }
+
     #[test]
     fn test_export_markdown() {
         let id = DiffId::new("main", "feature");
@@ -707,6 +1309,10 @@ mod tests {
             author: CommentAuthor::User,
             category: None,
             created_at: None,
+            parent_id: None,
+            resolved: false,
+            resolved_at: None,
+            updated_at: None,
         });
 
         review.edits.push(Edit {
@@ -715,8 +1321,13 @@ mod tests {
             diff: "-old\n+new".into(),
         });
 
+        let mut reply = Comment::reply(&review.comments[0], "Done", CommentAuthor::Ai);
+        reply.id = "c2".into();
+        review.comments.push(reply);
+
         let md = export_markdown(&review);
         assert!(md.contains("## src/lib.rs"));
+        assert!(md.contains("  - _AI_: Done"));
         assert!(md.contains("Line 11")); // 0-indexed to 1-indexed
         assert!(md.contains("Fix this"));
         assert!(md.contains("-old"));
@@ -4,7 +4,7 @@
  * Pure helper functions for diff display.
  */
 
-import type { FileDiff, Alignment, FileContent } from './types';
+import type { FileDiff, Alignment, File, FileContent, LineEnding } from './types';
 
 /**
  * Get display path from a FileDiff, handling renames.
@@ -87,14 +87,59 @@ export function isBinaryDiff(diff: FileDiff): boolean {
 }
 
 /**
- * Get text lines from a file, or empty array if binary/null.
+ * Check if a diff represents a submodule (pointer change).
+ */
+export function isSubmoduleDiff(diff: FileDiff): boolean {
+  return diff.before?.content.type === 'Submodule' || diff.after?.content.type === 'Submodule';
+}
+
+/**
+ * Check if either side is an LFS pointer whose object isn't available locally.
+ */
+export function isLfsPointerDiff(diff: FileDiff): boolean {
+  return diff.before?.content.type === 'LfsPointer' || diff.after?.content.type === 'LfsPointer';
+}
+
+/**
+ * Get text lines from a file, or empty array if binary/submodule/LFS pointer/null.
  */
 export function getTextLines(diff: FileDiff, side: 'before' | 'after'): string[] {
   const file = side === 'before' ? diff.before : diff.after;
-  if (!file || file.content.type === 'Binary') return [];
+  if (!file || file.content.type !== 'Text') return [];
   return file.content.lines;
 }
 
+const LINE_ENDING_LABELS: Record<LineEnding, string> = {
+  lf: 'LF',
+  crlf: 'CRLF',
+  cr: 'CR',
+  mixed: 'mixed',
+};
+
+/**
+ * Describe encoding and line-ending changes between the two sides, e.g.
+ * ["Line endings: LF → CRLF"]. Empty if neither changed (or a side is missing).
+ */
+export function describeFormatChanges(diff: FileDiff): string[] {
+  const { before, after } = diff;
+  if (!before || !after) return [];
+
+  const changes: string[] = [];
+  const encodingLabel = (file: File) =>
+    file.encoding ? `${file.encoding.name}${file.encoding.bom ? ' with BOM' : ''}` : null;
+  const beforeEncoding = encodingLabel(before);
+  const afterEncoding = encodingLabel(after);
+  if (beforeEncoding && afterEncoding && beforeEncoding !== afterEncoding) {
+    changes.push(`Encoding: ${beforeEncoding} → ${afterEncoding}`);
+  }
+  if (before.eol && after.eol && before.eol !== after.eol) {
+    changes.push(
+      `Line endings: ${LINE_ENDING_LABELS[before.eol]} → ${LINE_ENDING_LABELS[after.eol]}`
+    );
+  }
+  return changes;
+}
+
 /**
  * Convert a reference file to a FileDiff for display.
  *
@@ -113,5 +158,6 @@ export function referenceFileAsDiff(path: string, content: FileContent): FileDif
         changed: false, // No change highlighting for reference files
       },
     ],
+    inline_changes: [],
   };
 }
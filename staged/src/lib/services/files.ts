@@ -94,6 +94,53 @@ export async function searchFiles(
   });
 }
 
+/**
+ * Options for content search.
+ */
+export interface ContentSearchOptions {
+  /** Treat the query as an extended regex instead of a fixed string */
+  regex?: boolean;
+  ignoreCase?: boolean;
+  /** Only search paths matching at least one of these globs (e.g. "*.rs") */
+  globs?: string[];
+  /** Lines of context to return before and after each match */
+  contextLines?: number;
+}
+
+/**
+ * A line matching a content search.
+ */
+export interface ContentMatch {
+  path: string;
+  /** 0-indexed line number */
+  line: number;
+  text: string;
+  contextBefore: string[];
+  contextAfter: string[];
+}
+
+/**
+ * Search file contents at a ref with git grep.
+ *
+ * For WORKDIR, searches the working tree including untracked (non-ignored) files.
+ * Returns up to `limit` matches ordered by path, then line.
+ */
+export async function searchContent(
+  refName: string,
+  query: string,
+  options?: ContentSearchOptions,
+  limit?: number,
+  repoPath?: string
+): Promise<ContentMatch[]> {
+  return invoke<ContentMatch[]>('search_content', {
+    repoPath: repoPath ?? null,
+    refName,
+    query,
+    options: options ?? null,
+    limit: limit ?? 200,
+  });
+}
+
 /**
  * Get the content of a file at a specific ref.
  *
@@ -111,3 +158,88 @@ export async function getFileAtRef(
     path,
   });
 }
+
+// =============================================================================
+// Blame & History API
+// =============================================================================
+
+/**
+ * A commit referenced from blame output.
+ */
+export interface BlameCommit {
+  sha: string;
+  shortSha: string;
+  author: string;
+  authorEmail: string;
+  /** Author time as a Unix timestamp */
+  timestamp: number;
+  summary: string;
+  /** True for working-tree lines that have not been committed */
+  isUncommitted: boolean;
+}
+
+/**
+ * Origin of a single line.
+ */
+export interface BlameLine {
+  /** Key into Blame.commits */
+  sha: string;
+  /** 0-indexed line number in that commit's version of the file */
+  originalLine: number;
+  /** Path of the file in that commit (differs after renames) */
+  originalPath: string;
+}
+
+/**
+ * Blame for a whole file: one entry per line, plus the commits they reference.
+ */
+export interface Blame {
+  lines: BlameLine[];
+  commits: Record<string, BlameCommit>;
+}
+
+/**
+ * A commit in a file's history.
+ */
+export interface FileHistoryEntry {
+  sha: string;
+  shortSha: string;
+  author: string;
+  authorEmail: string;
+  timestamp: number;
+  subject: string;
+  /** Path of the file in this commit */
+  path: string;
+  /** Previous path, if this commit renamed or copied the file */
+  previousPath: string | null;
+}
+
+/**
+ * Blame a file at a specific ref.
+ *
+ * For WORKDIR, blames the working-tree file (uncommitted lines included).
+ */
+export async function blameFile(refName: string, path: string, repoPath?: string): Promise<Blame> {
+  return invoke<Blame>('blame_file', {
+    repoPath: repoPath ?? null,
+    refName,
+    path,
+  });
+}
+
+/**
+ * List commits that touched a file, newest first, following renames.
+ */
+export async function getFileHistory(
+  refName: string,
+  path: string,
+  limit?: number,
+  repoPath?: string
+): Promise<FileHistoryEntry[]> {
+  return invoke<FileHistoryEntry[]>('get_file_history', {
+    repoPath: repoPath ?? null,
+    refName,
+    path,
+    limit: limit ?? null,
+  });
+}
@@ -3,6 +3,21 @@ import type {
   DiffSpec,
   FileDiffSummary,
   FileDiff,
+  FileDiffWindow,
+  DiffWindow,
+  DiffOptions,
+  HighlightOptions,
+  LineSelection,
+  CommitFile,
+  StashEntry,
+  SubmoduleDiff,
+  MoveOptions,
+  MovedBlock,
+  StructuredDiff,
+  BinaryPair,
+  NotebookDiff,
+  RangeDiff,
+  BinaryTransport,
   PullRequest,
   Issue,
   GitHubAuthStatus,
@@ -56,25 +71,160 @@ export async function getMergeBase(ref1: string, ref2: string, repoPath?: string
 /**
  * List files changed in a diff (for sidebar).
  */
-export async function listDiffFiles(spec: DiffSpec, repoPath?: string): Promise<FileDiffSummary[]> {
+export async function listDiffFiles(
+  spec: DiffSpec,
+  repoPath?: string,
+  options?: DiffOptions
+): Promise<FileDiffSummary[]> {
   return invoke<FileDiffSummary[]>('list_diff_files', {
     repoPath: repoPath ?? null,
     spec,
+    options: options ?? null,
   });
 }
 
 /**
  * Get full diff content for a single file.
+ * Pass `highlight` to also get server-side syntax highlighting token spans.
  */
 export async function getFileDiff(
   spec: DiffSpec,
   filePath: string,
-  repoPath?: string
+  repoPath?: string,
+  options?: DiffOptions,
+  highlight?: HighlightOptions
 ): Promise<FileDiff> {
   return invoke<FileDiff>('get_file_diff', {
     repoPath: repoPath ?? null,
     spec,
     filePath,
+    options: options ?? null,
+    highlight: highlight ?? null,
+  });
+}
+
+/**
+ * Get a diff with only some lines of each side loaded, for very large files.
+ * Without a window, large files only load their changed regions with context.
+ */
+export async function getFileDiffWindow(
+  spec: DiffSpec,
+  filePath: string,
+  window?: DiffWindow,
+  repoPath?: string,
+  options?: DiffOptions
+): Promise<FileDiffWindow> {
+  return invoke<FileDiffWindow>('get_file_diff_window', {
+    repoPath: repoPath ?? null,
+    spec,
+    filePath,
+    options: options ?? null,
+    window: window ?? null,
+  });
+}
+
+/**
+ * Expand a submodule entry of a diff into the submodule's commits and changed files.
+ */
+export async function getSubmoduleDiff(
+  spec: DiffSpec,
+  filePath: string,
+  repoPath?: string
+): Promise<SubmoduleDiff> {
+  return invoke<SubmoduleDiff>('get_submodule_diff', {
+    repoPath: repoPath ?? null,
+    spec,
+    filePath,
+  });
+}
+
+/**
+ * Diff a Jupyter notebook cell by cell, as an alternative to the line view.
+ * Returns null for files that aren't `.ipynb`.
+ */
+export async function getNotebookDiff(
+  spec: DiffSpec,
+  filePath: string,
+  options?: DiffOptions,
+  repoPath?: string
+): Promise<NotebookDiff | null> {
+  return invoke<NotebookDiff | null>('get_notebook_diff', {
+    repoPath: repoPath ?? null,
+    spec,
+    filePath,
+    options: options ?? null,
+  });
+}
+
+/**
+ * Compare two versions of a branch (e.g. `oldSha`/`newSha` from a PR
+ * update) commit by commit. Use `DiffSpec.interdiff` for the whole-branch view.
+ */
+export async function getRangeDiff(
+  oldSha: string,
+  newSha: string,
+  upstream: string,
+  options?: DiffOptions,
+  repoPath?: string
+): Promise<RangeDiff> {
+  return invoke<RangeDiff>('get_range_diff', {
+    repoPath: repoPath ?? null,
+    oldSha,
+    newSha,
+    upstream,
+    options: options ?? null,
+  });
+}
+
+/**
+ * Get both sides of a binary file for side-by-side or onion-skin views.
+ * Base64 (the default) is capped in size; temp files suit large assets and
+ * can be loaded with `convertFileSrc`.
+ */
+export async function getBinaryPair(
+  spec: DiffSpec,
+  filePath: string,
+  transport?: BinaryTransport,
+  repoPath?: string
+): Promise<BinaryPair> {
+  return invoke<BinaryPair>('get_binary_pair', {
+    repoPath: repoPath ?? null,
+    spec,
+    filePath,
+    transport: transport ?? null,
+  });
+}
+
+/**
+ * Diff a JSON/YAML/TOML file by key path, as an alternative to the line view.
+ * Returns null for files in other formats; rejects if either side doesn't parse.
+ */
+export async function getStructuredDiff(
+  spec: DiffSpec,
+  filePath: string,
+  repoPath?: string
+): Promise<StructuredDiff | null> {
+  return invoke<StructuredDiff | null>('get_structured_diff', {
+    repoPath: repoPath ?? null,
+    spec,
+    filePath,
+  });
+}
+
+/**
+ * Find code blocks moved within or between the files of a diff.
+ */
+export async function detectMoves(
+  spec: DiffSpec,
+  repoPath?: string,
+  options?: DiffOptions,
+  moveOptions?: MoveOptions
+): Promise<MovedBlock[]> {
+  return invoke<MovedBlock[]>('detect_moves', {
+    repoPath: repoPath ?? null,
+    spec,
+    options: options ?? null,
+    moveOptions: moveOptions ?? null,
   });
 }
 
@@ -90,6 +240,99 @@ export async function commit(paths: string[], message: string, repoPath?: string
   });
 }
 
+/**
+ * Stage selected lines of a file's diff (hunk- or line-level staging).
+ */
+export async function stageSelection(
+  spec: DiffSpec,
+  filePath: string,
+  selection: LineSelection,
+  repoPath?: string
+): Promise<void> {
+  return invoke<void>('stage_selection', {
+    repoPath: repoPath ?? null,
+    spec,
+    filePath,
+    selection,
+  });
+}
+
+/**
+ * Unstage selected lines of a file's staged changes.
+ */
+export async function unstageSelection(
+  filePath: string,
+  selection: LineSelection,
+  repoPath?: string
+): Promise<void> {
+  return invoke<void>('unstage_selection', {
+    repoPath: repoPath ?? null,
+    filePath,
+    selection,
+  });
+}
+
+/**
+ * Create a commit from whole files and/or selected lines of files.
+ * Returns the short SHA of the new commit.
+ */
+export async function commitSelection(
+  spec: DiffSpec,
+  files: CommitFile[],
+  message: string,
+  repoPath?: string
+): Promise<string> {
+  return invoke<string>('commit_selection', {
+    repoPath: repoPath ?? null,
+    spec,
+    files,
+    message,
+  });
+}
+
+/**
+ * List stashes, newest first.
+ */
+export async function listStashes(repoPath?: string): Promise<StashEntry[]> {
+  return invoke<StashEntry[]>('list_stashes', { repoPath: repoPath ?? null });
+}
+
+/**
+ * Stash local changes. Returns null if there was nothing to stash.
+ */
+export async function createStash(
+  message?: string,
+  includeUntracked = false,
+  repoPath?: string
+): Promise<StashEntry | null> {
+  return invoke<StashEntry | null>('create_stash', {
+    repoPath: repoPath ?? null,
+    message: message ?? null,
+    includeUntracked,
+  });
+}
+
+/**
+ * Apply a stash (by SHA or stash@{n}), keeping it in the stash list.
+ */
+export async function applyStash(stash: string, repoPath?: string): Promise<void> {
+  return invoke<void>('apply_stash', { repoPath: repoPath ?? null, stash });
+}
+
+/**
+ * Apply a stash (by SHA or stash@{n}) and drop it.
+ */
+export async function popStash(stash: string, repoPath?: string): Promise<void> {
+  return invoke<void>('pop_stash', { repoPath: repoPath ?? null, stash });
+}
+
+/**
+ * Drop a stash (by SHA or stash@{n}).
+ */
+export async function dropStash(stash: string, repoPath?: string): Promise<void> {
+  return invoke<void>('drop_stash', { repoPath: repoPath ?? null, stash });
+}
+
 // =============================================================================
 // GitHub Commands
 // =============================================================================
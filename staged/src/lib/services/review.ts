@@ -1,8 +1,19 @@
 import { invoke } from '@tauri-apps/api/core';
-import type { DiffSpec, Review, Comment, Edit, NewComment, NewEdit } from '../types';
+import type {
+  DiffSpec,
+  Review,
+  Comment,
+  CommentAuthor,
+  Edit,
+  NewComment,
+  NewEdit,
+  SteppedReview,
+} from '../types';
 
 /**
- * Get or create a review for a diff.
+ * Get or create a review for a diff. A new review is seeded from the closest
+ * earlier review of the same base: unchanged files stay reviewed and comments
+ * follow their lines.
  */
 export async function getReview(spec: DiffSpec, repoPath?: string): Promise<Review> {
   return invoke<Review>('get_review', { repoPath: repoPath ?? null, spec });
@@ -33,6 +44,24 @@ export async function deleteComment(commentId: string): Promise<void> {
   return invoke('delete_comment', { commentId });
 }
 
+/**
+ * Reply to a comment. Replies to a reply join the same thread.
+ */
+export async function replyToComment(
+  commentId: string,
+  content: string,
+  author?: CommentAuthor
+): Promise<Comment> {
+  return invoke<Comment>('reply_to_comment', { commentId, content, author: author ?? null });
+}
+
+/**
+ * Resolve or reopen the thread a comment belongs to.
+ */
+export async function setCommentResolved(commentId: string, resolved: boolean): Promise<void> {
+  return invoke('set_comment_resolved', { commentId, resolved });
+}
+
 /**
  * Mark a file as reviewed.
  */
@@ -120,3 +149,22 @@ export async function getArtifacts(spec: DiffSpec, repoPath?: string): Promise<A
 export async function deleteArtifactFromDb(artifactId: string): Promise<void> {
   return invoke('delete_artifact', { artifactId });
 }
+
+/**
+ * Review a branch commit by commit. `spec` is the branch range; use
+ * `DiffSpec.commitStep` with the per-commit review functions for each step.
+ */
+export async function getSteppedReview(spec: DiffSpec, repoPath?: string): Promise<SteppedReview> {
+  return invoke<SteppedReview>('get_stepped_review', { repoPath: repoPath ?? null, spec });
+}
+
+/**
+ * Record which commit of a stepped review is being reviewed.
+ */
+export async function setReviewStep(
+  spec: DiffSpec,
+  sha: string | null,
+  repoPath?: string
+): Promise<void> {
+  return invoke('set_review_step', { repoPath: repoPath ?? null, spec, sha });
+}
@@ -5,13 +5,22 @@
 /** A reference to a point in git history (or working tree) */
 export type GitRef =
   | { type: 'WorkingTree' }
+  | { type: 'Index' }
   | { type: 'Rev'; value: string }
   | { type: 'MergeBase' }
-  | { type: 'MergeBaseOf'; value: [string, string] };
+  | { type: 'MergeBaseOf'; value: [string, string] }
+  | { type: 'Stash'; value: string }
+  /** A file or directory on disk, outside git; only valid paired with another Path */
+  | { type: 'Path'; value: string }
+  /** A patch/diff/mbox file: as head, its changes; as base, its own pre-image */
+  | { type: 'Patch'; value: string }
+  /** [rev, onto, upstream]: rev rebased onto where onto branched from upstream */
+  | { type: 'Rebased'; value: [string, string, string] };
 
 /** Get display string for a GitRef */
 export function gitRefDisplay(ref: GitRef): string {
   if (ref.type === 'WorkingTree') return '@';
+  if (ref.type === 'Index') return 'index';
   if (ref.type === 'MergeBase') return 'merge-base';
   if (ref.type === 'MergeBaseOf') return 'merge-base';
   return ref.value;
@@ -20,6 +29,7 @@ export function gitRefDisplay(ref: GitRef): string {
 /** Get a ref name suitable for git commands (e.g., for loading reference files) */
 export function gitRefName(ref: GitRef): string {
   if (ref.type === 'WorkingTree') return 'HEAD';
+  if (ref.type === 'Index') return 'HEAD';
   if (ref.type === 'MergeBase') return 'HEAD'; // For file loading, use HEAD
   if (ref.type === 'MergeBaseOf') return ref.value[1]; // Use head ref for file loading
   return ref.value;
@@ -65,6 +75,22 @@ export const DiffSpec = {
     };
   },
 
+  /** Staged changes: HEAD..index */
+  staged(): DiffSpec {
+    return {
+      base: { type: 'Rev', value: 'HEAD' },
+      head: { type: 'Index' },
+    };
+  },
+
+  /** Unstaged changes: index..@ */
+  unstaged(): DiffSpec {
+    return {
+      base: { type: 'Index' },
+      head: { type: 'WorkingTree' },
+    };
+  },
+
   /** Last commit: HEAD~1..HEAD */
   lastCommit(): DiffSpec {
     return {
@@ -89,6 +115,46 @@ export const DiffSpec = {
     };
   },
 
+  /** A stash (SHA or stash@{n}): its parent commit..stashed changes incl. untracked */
+  stash(stash: string): DiffSpec {
+    return {
+      base: { type: 'Rev', value: `${stash}^1` },
+      head: { type: 'Stash', value: stash },
+    };
+  },
+
+  /** Two files or directories on disk, outside git (repoPath is ignored) */
+  paths(before: string, after: string): DiffSpec {
+    return {
+      base: { type: 'Path', value: before },
+      head: { type: 'Path', value: after },
+    };
+  },
+
+  /** A patch file, against baseRev or else the blobs in its `index` lines */
+  patch(file: string, baseRev?: string): DiffSpec {
+    return {
+      base: baseRev ? { type: 'Rev', value: baseRev } : { type: 'Patch', value: file },
+      head: { type: 'Patch', value: file },
+    };
+  },
+
+  /** Interdiff between two versions of a branch, leaving out upstream changes */
+  interdiff(oldSha: string, newSha: string, upstream: string): DiffSpec {
+    return {
+      base: { type: 'Rebased', value: [oldSha, newSha, upstream] },
+      head: { type: 'Rev', value: newSha },
+    };
+  },
+
+  /** One commit of a stepped review: parent..sha */
+  commitStep(step: StepCommit): DiffSpec {
+    return {
+      base: { type: 'Rev', value: step.parent },
+      head: { type: 'Rev', value: step.sha },
+    };
+  },
+
   /** Custom range */
   custom(base: GitRef, head: GitRef): DiffSpec {
     return { base, head };
@@ -106,6 +172,7 @@ export const DiffSpec = {
   display(spec: DiffSpec): string {
     const formatRef = (ref: GitRef): string => {
       if (ref.type === 'WorkingTree') return '@';
+      if (ref.type === 'Index') return 'index';
       if (ref.type === 'MergeBase') return 'merge-base';
       if (ref.type === 'MergeBaseOf') return 'merge-base';
       return ref.value;
@@ -114,6 +181,39 @@ export const DiffSpec = {
   },
 };
 
+/** A stash entry, as returned by listStashes */
+export interface StashEntry {
+  /** Position in the stash list (the n in stash@{n}) */
+  index: number;
+  /** SHA of the stash commit; stable across pushes and drops */
+  sha: string;
+  message: string;
+  timestamp: number;
+  hasUntracked: boolean;
+}
+
+/** How whitespace differences are treated when diffing */
+export type WhitespaceMode = 'none' | 'ignore_all' | 'ignore_change' | 'ignore_eol';
+
+/** Line diff algorithm */
+export type DiffAlgorithm = 'myers' | 'minimal' | 'patience' | 'histogram';
+
+/** Options controlling how a DiffSpec is diffed. Omitted fields use git's defaults. */
+export interface DiffOptions {
+  whitespace?: WhitespaceMode;
+  /** Ignore changes whose lines are all blank */
+  ignore_blank_lines?: boolean;
+  algorithm?: DiffAlgorithm;
+  /** Context lines for unified diff output (null = git's default of 3) */
+  context_lines?: number | null;
+  /** Similarity percentage (0-100) for rename detection (null = git's default of 50) */
+  rename_threshold?: number | null;
+  /** Similarity percentage (0-100) for copy detection (null = don't detect copies) */
+  copy_threshold?: number | null;
+  /** Tokenizer for intra-line changes */
+  inline_tokenizer?: InlineTokenizer;
+}
+
 // =============================================================================
 // File types
 // =============================================================================
@@ -124,19 +224,272 @@ export interface Span {
   end: number;
 }
 
-/** Content of a file - either text lines or binary marker */
-export type FileContent = { type: 'Text'; lines: string[] } | { type: 'Binary' };
+/** Pixel dimensions of an image */
+export interface ImageSize {
+  width: number;
+  height: number;
+}
+
+/** Size, hash and type of a binary file's content */
+export interface BinaryInfo {
+  /** Size in bytes */
+  size: number;
+  /** Git blob id of the content; equal hashes mean identical content */
+  hash: string;
+  /** MIME type, from magic bytes or else the file extension */
+  mime: string;
+  /** Pixel dimensions, for images in a recognized format */
+  image?: ImageSize;
+}
+
+/**
+ * Content of a file - text lines, binary metadata, or a submodule/LFS pointer.
+ * `dirty` is only set on the working tree side. `LfsPointer` means the LFS
+ * object (`oid` is its SHA-256, `size` in bytes) isn't in the local store.
+ */
+export type FileContent =
+  | { type: 'Text'; lines: string[] }
+  | ({ type: 'Binary' } & BinaryInfo)
+  | { type: 'Submodule'; commit: string; dirty: boolean }
+  | { type: 'LfsPointer'; oid: string; size: number };
 
 /** A file with its path and content */
 export interface File {
   path: string;
   content: FileContent;
+  /** Detected text encoding (text content only) */
+  encoding?: TextEncoding;
+  /** Line ending style (text content with line breaks only) */
+  eol?: LineEnding;
 }
 
+/** Encoding a text file was decoded from */
+export interface TextEncoding {
+  /** WHATWG encoding name, e.g. "UTF-8", "UTF-16LE", "windows-1252" */
+  name: string;
+  /** Whether the file starts with a byte-order mark */
+  bom: boolean;
+}
+
+/** Line ending style; 'mixed' means more than one style in the file */
+export type LineEnding = 'lf' | 'crlf' | 'cr' | 'mixed';
+
+/** Kind of a tree entry, from its git file mode */
+export type FileMode = 'regular' | 'executable' | 'symlink' | 'submodule';
+
 /** Summary of a file in the diff (for sidebar) */
 export interface FileDiffSummary {
   before: string | null;
   after: string | null;
+  /** Lines added (null for binary files or if unknown) */
+  additions?: number | null;
+  /** Lines removed (null for binary files or if unknown) */
+  deletions?: number | null;
+  binary?: boolean;
+  /** Mode before the change (null if added or unknown) */
+  old_mode?: FileMode | null;
+  /** Mode after the change (null if deleted or unknown) */
+  new_mode?: FileMode | null;
+  /** Marked `linguist-generated` in .gitattributes (collapse by default) */
+  generated?: boolean;
+  /** Marked `linguist-vendored` in .gitattributes */
+  vendored?: boolean;
+  /** Dependency changes, when the file is a recognized lockfile */
+  lockfile?: LockfileSummary;
+  /** Only a patch's hunks are known: lines outside them are blank */
+  hunks_only?: boolean;
+}
+
+/** How `getBinaryPair` hands over file content */
+export type BinaryTransport = 'base64' | 'temp_file';
+
+/** Content of one side of a binary file */
+export type BinaryData = { type: 'base64'; data: string } | { type: 'temp_file'; path: string };
+
+/** One side of a binary file: its metadata and content */
+export interface BinarySide {
+  info: BinaryInfo;
+  data: BinaryData;
+}
+
+/** Both sides of a binary file; a side is null if the file doesn't exist there */
+export interface BinaryPair {
+  before: BinarySide | null;
+  after: BinarySide | null;
+}
+
+/** Package manager a lockfile belongs to */
+export type LockfileKind = 'cargo' | 'npm' | 'yarn' | 'poetry';
+
+/** How a locked dependency changed */
+export type DependencyChangeKind = 'added' | 'removed' | 'upgraded' | 'downgraded' | 'source_changed';
+
+/** A dependency whose locked version or source changed */
+export interface DependencyChange {
+  name: string;
+  kind: DependencyChangeKind;
+  old_version: string | null;
+  new_version: string | null;
+  old_source: string | null;
+  new_source: string | null;
+}
+
+/** Dependency-level summary of a lockfile change */
+export interface LockfileSummary {
+  kind: LockfileKind;
+  /** Sorted by package name */
+  changes: DependencyChange[];
+}
+
+/** A commit inside a submodule */
+export interface SubmoduleCommit {
+  sha: string;
+  shortSha: string;
+  subject: string;
+  author: string;
+  timestamp: number;
+}
+
+/** What changed inside a submodule between the two sides of a diff */
+export interface SubmoduleDiff {
+  path: string;
+  /** Pinned commit before (null if the submodule was added) */
+  oldCommit: string | null;
+  /** Pinned (or checked-out) commit after (null if removed) */
+  newCommit: string | null;
+  /** Whether the submodule's working tree has uncommitted changes */
+  dirty: boolean;
+  /** Commits in new but not old, newest first */
+  commits: SubmoduleCommit[];
+  /** Commits in old but not new (the pin moved backwards or sideways) */
+  removedCommits: SubmoduleCommit[];
+  /** Files changed in the submodule, including uncommitted changes when dirty */
+  files: FileDiffSummary[];
+}
+
+/** A config format that can be diffed structurally */
+export type StructuredFormat = 'json' | 'yaml' | 'toml';
+
+/** Kind of a structural change; 'moved' means an identical value changed path */
+export type StructuredChangeKind = 'added' | 'removed' | 'changed' | 'moved';
+
+/** A change at a single key path */
+export interface StructuredChange {
+  kind: StructuredChangeKind;
+  /** Key path, e.g. `dependencies.serde` or `services[2].image` */
+  path: string;
+  /** Previous path of a moved value */
+  oldPath: string | null;
+  oldValue: unknown;
+  newValue: unknown;
+}
+
+/** Key-path-level changes between the two sides of a config file */
+export interface StructuredDiff {
+  format: StructuredFormat;
+  changes: StructuredChange[];
+}
+
+/** Jupyter cell type */
+export type CellKind = 'code' | 'markdown' | 'raw';
+
+/** How a notebook cell changed; `unchanged` cells may still have new outputs */
+export type CellChange = 'added' | 'removed' | 'modified' | 'unchanged';
+
+/** A cell output, summarized instead of shown inline */
+export interface OutputSummary {
+  /** `stream`, `execute_result`, `display_data` or `error` */
+  outputType: string;
+  /** Stream text, `text/plain` data, or `ename: evalue` (truncated) */
+  text: string | null;
+  /** Other data (images, HTML, ...) by MIME type, without the content */
+  data: BinaryInfo[];
+}
+
+/** One aligned pair of notebook cells */
+export interface NotebookCellDiff {
+  kind: CellKind;
+  change: CellChange;
+  /** Cell id (nbformat 4.5+) */
+  id: string | null;
+  beforeIndex: number | null;
+  afterIndex: number | null;
+  /** The cell source, diffed as a file */
+  source: FileDiff;
+  beforeExecutionCount: number | null;
+  afterExecutionCount: number | null;
+  beforeOutputs: OutputSummary[];
+  afterOutputs: OutputSummary[];
+  outputsChanged: boolean;
+  metadataChanged: boolean;
+}
+
+/** Cell-by-cell diff of a notebook, as an alternative to its FileDiff */
+export interface NotebookDiff {
+  /** Kernel language, e.g. `python` */
+  language: string | null;
+  metadataChanged: boolean;
+  cells: NotebookCellDiff[];
+}
+
+/** How a commit changed between two versions of a branch */
+export type RangeDiffStatus = 'unchanged' | 'modified' | 'added' | 'removed';
+
+/** A commit in a range-diff series */
+export interface RangeCommit {
+  sha: string;
+  shortSha: string;
+  subject: string;
+  author: string;
+  timestamp: number;
+}
+
+/**
+ * How one file's patch changed within a commit pair: `diff.before` is the
+ * old commit's patch for the file, `diff.after` the new one's.
+ */
+export interface RangeDiffFile {
+  path: string;
+  diff: FileDiff;
+}
+
+/** A commit pair, or a commit only in one version */
+export interface RangeDiffEntry {
+  status: RangeDiffStatus;
+  old: RangeCommit | null;
+  new: RangeCommit | null;
+  messageChanged: boolean;
+  /** Files whose patch differs; for added/removed commits, all of them */
+  files: RangeDiffFile[];
+}
+
+/** Commits of two versions of a branch, paired up like `git range-diff` */
+export interface RangeDiff {
+  oldBase: string;
+  newBase: string;
+  commits: RangeDiffEntry[];
+}
+
+/** Options for moved-code detection */
+export interface MoveOptions {
+  /** Smallest block, in lines, reported as a move (default 3) */
+  minLines?: number;
+  /** Match lines regardless of whitespace (e.g. re-indented code) */
+  ignoreWhitespace?: boolean;
+}
+
+/** One end of a moved block: a line range in a file's FileDiff */
+export interface MoveLocation {
+  /** Path of the file, as in the diff's file list */
+  path: string;
+  /** Lines in the before side (source) or after side (destination) */
+  lines: Span;
+}
+
+/** A block of lines removed in one place and added in another */
+export interface MovedBlock {
+  from: MoveLocation;
+  to: MoveLocation;
 }
 
 /** Maps a region in the before file to a region in the after file */
@@ -147,6 +500,45 @@ export interface Alignment {
   changed: boolean;
 }
 
+/**
+ * Lines picked out of a file's diff for partial staging or committing.
+ * Spans index into the before/after sides of the diff.
+ */
+export interface LineSelection {
+  /** Removed lines to include (before side) */
+  before: Span[];
+  /** Added lines to include (after side) */
+  after: Span[];
+}
+
+/** A file to include in a commit, optionally restricted to selected lines */
+export interface CommitFile {
+  path: string;
+  /** Lines to commit (null commits the whole file) */
+  selection: LineSelection | null;
+}
+
+/** How lines are split into tokens for intra-line diffing */
+export type InlineTokenizer = 'word' | 'char';
+
+/** A range of characters within a line (0-indexed, exclusive end, in UTF-16 code units) */
+export interface CharRange {
+  start: number;
+  end: number;
+}
+
+/** Intra-line changes for a pair of lines in a changed region */
+export interface InlineChange {
+  /** Line index in the before file */
+  before_line: number;
+  /** Line index in the after file */
+  after_line: number;
+  /** Changed ranges within the before line */
+  before: CharRange[];
+  /** Changed ranges within the after line */
+  after: CharRange[];
+}
+
 /** Full diff content for rendering a single file */
 export interface FileDiff {
   /** File before the change (null if added) */
@@ -155,6 +547,82 @@ export interface FileDiff {
   after: File | null;
   /** Alignments mapping regions between before/after */
   alignments: Alignment[];
+  /** Intra-line changes for paired lines in changed alignments */
+  inline_changes: InlineChange[];
+  /** Syntax highlighting, present only when requested */
+  highlights?: DiffHighlights;
+}
+
+/**
+ * Which lines of each side to load in a windowed diff: explicit ranges
+ * (e.g. the viewport), or changed regions plus `context` lines.
+ */
+export type DiffWindow =
+  | { mode: 'ranges'; before?: Span[]; after?: Span[] }
+  | { mode: 'hunks'; context: number };
+
+/** Consecutive lines of one side, starting at line `start` (0-indexed) */
+export interface LineChunk {
+  start: number;
+  lines: string[];
+}
+
+/** One side of a windowed diff: file metadata and only the loaded lines */
+export interface FileWindow {
+  path: string;
+  /** Total lines in the file (0 unless text) */
+  line_count: number;
+  /** Loaded line ranges, sorted and non-overlapping (text only) */
+  chunks: LineChunk[];
+  /** Content when it isn't text (binary, submodule, LFS pointer) */
+  content?: FileContent;
+  encoding?: TextEncoding;
+  eol?: LineEnding;
+}
+
+/** A FileDiff with only some lines of each side, for very large files */
+export interface FileDiffWindow {
+  before: FileWindow | null;
+  after: FileWindow | null;
+  /** Alignments for the whole file */
+  alignments: Alignment[];
+  /** Intra-line changes for paired lines within the loaded chunks */
+  inline_changes: InlineChange[];
+}
+
+/** A styled run of characters within a line (character offsets, end exclusive) */
+export interface TokenSpan {
+  start: number;
+  end: number;
+  /** Foreground color as #rrggbb or #rrggbbaa */
+  color: string;
+  bold: boolean;
+  italic: boolean;
+  underline: boolean;
+}
+
+/** Token spans for every line of a file */
+export interface FileHighlights {
+  /** Syntax used, e.g. "Rust" */
+  syntax: string;
+  lines: TokenSpan[][];
+}
+
+/** Server-side syntax highlighting for both sides of a diff */
+export interface DiffHighlights {
+  /** Name of the theme the colors come from */
+  theme: string;
+  foreground: string;
+  background: string;
+  /** Null when missing, binary, or an unrecognised language */
+  before: FileHighlights | null;
+  after: FileHighlights | null;
+}
+
+/** Options for server-side highlighting */
+export interface HighlightOptions {
+  /** Custom theme name or bundled syntect theme name; defaults to a dark theme */
+  theme?: string | null;
 }
 
 // =============================================================================
@@ -215,10 +683,23 @@ export interface Comment {
   /** The line range this comment applies to (0-indexed, exclusive end) */
   span: Span;
   content: string;
+  /** Who wrote the comment */
+  author?: CommentAuthor;
   /** When the comment was created (ISO timestamp) */
   created_at?: string;
+  /** Thread root this replies to; replies share the root's path and span */
+  parent_id?: string;
+  /** Whether the thread is resolved (set on thread roots) */
+  resolved?: boolean;
+  /** When the thread was resolved (ISO timestamp) */
+  resolved_at?: string;
+  /** When the content was last edited (ISO timestamp) */
+  updated_at?: string;
 }
 
+/** Who authored a comment */
+export type CommentAuthor = 'user' | 'ai';
+
 /** An edit made during review, stored as a unified diff */
 export interface Edit {
   id: string;
@@ -230,11 +711,49 @@ export interface Edit {
 export interface Review {
   id: DiffId;
   reviewed: string[];
+  /** Blob SHA each reviewed file had when marked (absent for deleted files) */
+  reviewed_at?: Record<string, string>;
   comments: Comment[];
   edits: Edit[];
   reference_files: string[];
 }
 
+/** A commit of a branch reviewed commit by commit */
+export interface StepCommit {
+  sha: string;
+  /** What the commit is diffed against (its first parent, or the empty tree) */
+  parent: string;
+  subject: string;
+  /** Files the commit changes */
+  files: string[];
+}
+
+/** One commit of a stepped review; its review is keyed by parent..sha */
+export interface CommitStep extends StepCommit {
+  review: Review;
+}
+
+/** Where a file was changed and reviewed across a branch's commits */
+export interface FileRollup {
+  path: string;
+  /** Commits that change the file, oldest first */
+  changed_in: string[];
+  /** Commits in which the file was marked reviewed, oldest first */
+  reviewed_in: string[];
+}
+
+/** A branch reviewed commit by commit */
+export interface SteppedReview {
+  /** The branch range being walked */
+  id: DiffId;
+  /** Oldest first */
+  steps: CommitStep[];
+  /** SHA of the commit being reviewed, once stepping has started */
+  current: string | null;
+  /** Sorted by path */
+  files: FileRollup[];
+}
+
 /** Input for creating a new comment */
 export interface NewComment {
   path: string;